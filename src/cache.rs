@@ -0,0 +1,275 @@
+use std::{
+  collections::{hash_map::DefaultHasher, BTreeSet},
+  hash::{Hash, Hasher},
+  path::{Component, Path, PathBuf},
+};
+
+use oapi::OApiTag;
+use serde::{Deserialize, Serialize};
+use simplelog::debug;
+
+use crate::findings::{Finding, Findings};
+
+/// Folder the cache is kept in when no other folder is given
+pub const DEFAULT_CACHE_DIR: &str = ".sv-cache";
+
+/// The results of validating the OpenAPI documents, kept in a folder with a file per document so
+/// documents that haven't changed aren't validated again
+#[derive(Debug, Clone)]
+pub struct ValidationCache {
+  /// The folder the entries are written to
+  dir: PathBuf,
+}
+
+/// The findings and tags of a validated document
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+  /// The sv version that validated the document
+  version: String,
+  /// The findings reported while validating the document
+  findings: Vec<Finding>,
+  /// The tags of the document, or `None` if it is invalid
+  tags: Option<Vec<CachedTag>>,
+}
+
+/// A tag of a cached document
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedTag {
+  name: String,
+  description: Option<String>,
+}
+
+impl ValidationCache {
+  /// Creates a cache that keeps its entries in `dir`, which is created when the first entry is
+  /// written
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    ValidationCache { dir: dir.into() }
+  }
+
+  /// The key of a document, which changes when the file, its contents, the contents of the files
+  /// it references, the settings or the sv version change
+  fn key(file: &Path, contents: &str, skip_cyclic: bool) -> String {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    file.hash(&mut hasher);
+    contents.hash(&mut hasher);
+    skip_cyclic.hash(&mut hasher);
+    referenced_files(file, contents).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+  }
+
+  /// Reports the findings cached for the document and returns its tags, or validates it with
+  /// `validate` and caches the result
+  pub(crate) fn validate(
+    &self,
+    file: &Path,
+    contents: &str,
+    skip_cyclic: bool,
+    findings: &mut Findings,
+    validate: impl FnOnce(&mut Findings) -> Option<Vec<OApiTag>>,
+  ) -> Option<Vec<OApiTag>> {
+    let path = self
+      .dir
+      .join(Self::key(file, contents, skip_cyclic))
+      .with_extension("json");
+    if let Some(entry) = read_entry(&path) {
+      debug!("Using the cached result for {:?}", file);
+      entry
+        .findings
+        .into_iter()
+        .for_each(|finding| findings.push(finding));
+      return entry
+        .tags
+        .map(|tags| tags.into_iter().filter_map(to_tag).collect());
+    }
+    let mut reported = Vec::new();
+    let mut sink = |finding| reported.push(finding);
+    let tags = validate(&mut Findings::with_sink(&mut sink));
+    let entry = CacheEntry {
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      findings: reported,
+      tags: tags.as_ref().map(|tags| {
+        tags
+          .iter()
+          .map(|tag| CachedTag {
+            name: tag.name().clone(),
+            description: tag.description().clone(),
+          })
+          .collect()
+      }),
+    };
+    if let Err(e) = write_entry(&self.dir, &path, &entry) {
+      debug!("Failed to cache the result for {:?}: {}", file, e);
+    }
+    entry
+      .findings
+      .into_iter()
+      .for_each(|finding| findings.push(finding));
+    tags
+  }
+}
+
+/// The files the document references with `$ref`, directly or through other referenced files, and
+/// their contents, `None` for the files that can't be read
+fn referenced_files(file: &Path, contents: &str) -> Vec<(PathBuf, Option<String>)> {
+  let mut visited = BTreeSet::from([normalize(file)]);
+  let mut referenced = Vec::new();
+  let mut pending = vec![(file.to_path_buf(), contents.to_string())];
+  while let Some((file, contents)) = pending.pop() {
+    let Ok(doc) = serde_yaml::from_str::<serde_yaml::Value>(&contents) else {
+      continue;
+    };
+    let mut refs = Vec::new();
+    collect_refs(&doc, &mut refs);
+    let dir = file.parent().unwrap_or(Path::new(""));
+    for reference in refs {
+      let path = normalize(&dir.join(reference));
+      if !visited.insert(path.clone()) {
+        continue;
+      }
+      let contents = std::fs::read_to_string(&path).ok();
+      if let Some(contents) = &contents {
+        pending.push((path.clone(), contents.clone()));
+      }
+      referenced.push((path, contents));
+    }
+  }
+  referenced
+}
+
+/// Collects the files of the `$ref`s in the value, skipping references within the document and to
+/// URLs
+fn collect_refs(value: &serde_yaml::Value, refs: &mut Vec<String>) {
+  match value {
+    serde_yaml::Value::Mapping(mapping) => {
+      for (key, value) in mapping {
+        if let (Some("$ref"), Some(reference)) = (key.as_str(), value.as_str()) {
+          let file = reference.split('#').next().unwrap_or_default();
+          if !file.is_empty() && !file.contains("://") {
+            refs.push(file.to_string());
+          }
+        }
+        collect_refs(value, refs);
+      }
+    }
+    serde_yaml::Value::Sequence(sequence) => {
+      sequence.iter().for_each(|value| collect_refs(value, refs));
+    }
+    _ => {}
+  }
+}
+
+/// Removes the `.` and `..` components of the path without touching the file system, so a file
+/// referenced through different relative paths is only read once
+fn normalize(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir
+        if matches!(
+          normalized.components().next_back(),
+          Some(Component::Normal(_))
+        ) =>
+      {
+        normalized.pop();
+      }
+      component => normalized.push(component),
+    }
+  }
+  normalized
+}
+
+/// Reads the entry, which is ignored if it can't be read or is from another sv version
+fn read_entry(path: &Path) -> Option<CacheEntry> {
+  let contents = std::fs::read_to_string(path).ok()?;
+  let entry: CacheEntry = serde_json::from_str(&contents).ok()?;
+  (entry.version == env!("CARGO_PKG_VERSION")).then_some(entry)
+}
+
+/// Writes the entry, creating the cache folder if needed
+fn write_entry(dir: &Path, path: &Path, entry: &CacheEntry) -> anyhow::Result<()> {
+  std::fs::create_dir_all(dir)?;
+  std::fs::write(path, serde_json::to_string(entry)?)?;
+  Ok(())
+}
+
+/// Converts a cached tag back to the tag of the OpenAPI document
+fn to_tag(tag: CachedTag) -> Option<OApiTag> {
+  serde_json::from_value(serde_json::json!({
+    "name": tag.name,
+    "description": tag.description,
+  }))
+  .ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::findings::FindingCode;
+
+  #[test]
+  fn test_validation_cache() {
+    let dir = std::env::temp_dir().join("sv_test_validation_cache");
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = ValidationCache::new(&dir);
+    let file = Path::new("apis/items.yaml");
+    let validate = |findings: &mut Findings| {
+      findings.push(Finding::warning(FindingCode::CyclicRef, "Cyclic").in_file(file));
+      Some(Vec::new())
+    };
+
+    let mut findings = Findings::default();
+    let tags = cache.validate(file, "openapi: 3.0.1", true, &mut findings, validate);
+    assert_eq!(tags.map(|tags| tags.len()), Some(0));
+    assert_eq!(findings.warning_count(), 1);
+
+    let mut cached = Findings::default();
+    let tags = cache.validate(file, "openapi: 3.0.1", true, &mut cached, |_| {
+      panic!("The cached result should be used")
+    });
+    assert_eq!(tags.map(|tags| tags.len()), Some(0));
+    assert_eq!(
+      cached.iter().collect::<Vec<_>>(),
+      findings.iter().collect::<Vec<_>>()
+    );
+
+    let mut changed = Findings::default();
+    assert!(cache
+      .validate(file, "openapi: 3.0.2", true, &mut changed, |_| None)
+      .is_none());
+    assert_eq!(changed.warning_count(), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_validation_cache_referenced_files() {
+    let dir = std::env::temp_dir().join("sv_test_validation_cache_referenced_files");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("apis/schemas")).unwrap();
+    let file = dir.join("apis/items.yaml");
+    let contents = "components:\n  schemas:\n    Item:\n      $ref: './schemas/item.yaml#/Item'\n";
+    std::fs::write(
+      dir.join("apis/schemas/item.yaml"),
+      "Item:\n  $ref: '../common.yaml#/Id'\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("apis/common.yaml"), "Id:\n  type: string\n").unwrap();
+    let cache = ValidationCache::new(dir.join("cache"));
+
+    let mut findings = Findings::default();
+    cache.validate(&file, contents, false, &mut findings, |_| Some(Vec::new()));
+    cache.validate(&file, contents, false, &mut findings, |_| {
+      panic!("The cached result should be used")
+    });
+
+    std::fs::write(dir.join("apis/common.yaml"), "Id:\n  type: integer\n").unwrap();
+    let mut validated = false;
+    cache.validate(&file, contents, false, &mut findings, |_| {
+      validated = true;
+      None
+    });
+    assert!(validated);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,221 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+  error::SvError,
+  findings::{FindingCode, SeverityOverride},
+};
+
+/// Name of the optional config file read from the current directory
+pub const CONFIG_FILE_NAME: &str = "sv.toml";
+
+/// Settings read from `sv.toml`, command line options take precedence over them
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// File stems of the OpenAPI documents that are merged into every other document
+  pub shared_schema_names: Option<Vec<String>>,
+  /// Name of the Terraform module that creates the lambdas
+  pub lambda_module_name: Option<String>,
+  /// Bounds for the lambda settings, the policy isn't checked when it isn't set
+  pub lambda_policy: Option<LambdaPolicy>,
+  /// Named environments that are selected with `sv verify --env <NAME>`
+  pub environments: BTreeMap<String, Environment>,
+  /// The severity to report each finding code with, e.g. `POST_NO_BODY = "error"`
+  pub severity: BTreeMap<FindingCode, SeverityOverride>,
+  /// The strictness preset, the other settings take precedence over it
+  pub profile: Option<Profile>,
+}
+
+/// Presets for how strict the validation is
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+  /// Don't report the request body and method warnings
+  Relaxed,
+  /// The default checks
+  #[default]
+  Standard,
+  /// Check the request bodies and the methods of the permissions and fail on any warning
+  Strict,
+}
+
+impl Profile {
+  /// The finding codes that the relaxed profile turns off
+  pub const RELAXED_CODES: [FindingCode; 3] = [
+    FindingCode::PostNoBody,
+    FindingCode::OptionalRequestBody,
+    FindingCode::OptionsMethod,
+  ];
+}
+
+/// The paths and overrides of an environment, e.g. `dev` or `prod`
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Environment {
+  /// The path to the OpenAPI files
+  pub api_path: Option<PathBuf>,
+  /// The path to the Terraform files
+  pub terraform: Option<PathBuf>,
+  /// A `.tfvars` file with the values of the Terraform variables
+  pub terraform_var_file: Option<PathBuf>,
+  /// Overrides the file stems of the shared OpenAPI documents
+  pub shared_schema_names: Option<Vec<String>>,
+  /// Overrides the name of the Terraform module that creates the lambdas
+  pub lambda_module_name: Option<String>,
+  /// Overrides the bounds for the lambda settings
+  pub lambda_policy: Option<LambdaPolicy>,
+}
+
+/// Governance rules for the settings of each lambda in `lambda.tf`
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LambdaPolicy {
+  /// Warn when a lambda doesn't set `timeout`
+  pub require_timeout: bool,
+  /// Minimum `timeout` in seconds
+  pub min_timeout: Option<u64>,
+  /// Maximum `timeout` in seconds
+  pub max_timeout: Option<u64>,
+  /// Warn when a lambda doesn't set `memory_size`
+  pub require_memory_size: bool,
+  /// Minimum `memory_size` in MB
+  pub min_memory_size: Option<u64>,
+  /// Maximum `memory_size` in MB
+  pub max_memory_size: Option<u64>,
+}
+
+impl Config {
+  /// Reads the config from the file
+  pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+    let contents = std::fs::read_to_string(path)
+      .map_err(|e| SvError::Io(format!("Failed to read {:?}: {}", path, e)))?;
+    toml::from_str(&contents)
+      .map_err(|e| SvError::Config(format!("Invalid config {:?}: {}", path, e)).into())
+  }
+
+  /// Applies the overrides of the environment to the config and returns the environment
+  pub fn environment(&mut self, name: &str) -> anyhow::Result<Environment> {
+    let mut environment = self.environments.remove(name).ok_or_else(|| {
+      SvError::Config(format!(
+        "The environment {} is not defined in {}",
+        name, CONFIG_FILE_NAME
+      ))
+    })?;
+    if let Some(shared_schema_names) = environment.shared_schema_names.take() {
+      self.shared_schema_names = Some(shared_schema_names);
+    }
+    if let Some(lambda_module_name) = environment.lambda_module_name.take() {
+      self.lambda_module_name = Some(lambda_module_name);
+    }
+    if let Some(lambda_policy) = environment.lambda_policy.take() {
+      self.lambda_policy = Some(lambda_policy);
+    }
+    Ok(environment)
+  }
+
+  /// Reads `sv.toml` from the folder, or uses the defaults if it doesn't exist
+  pub fn load(dir: &Path) -> anyhow::Result<Self> {
+    let path = dir.join(CONFIG_FILE_NAME);
+    if path.is_file() {
+      Self::from_file(&path)
+    } else {
+      Ok(Self::default())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_config_shared_schema_names() {
+    let config: Config = toml::from_str(r#"shared_schema_names = ["common", "_shared"]"#).unwrap();
+    assert_eq!(
+      config.shared_schema_names,
+      Some(vec!["common".to_string(), "_shared".to_string()])
+    );
+    assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+    assert!(toml::from_str::<Config>("unknown = true").is_err());
+  }
+
+  #[test]
+  fn test_config_lambda_policy() {
+    let config: Config = toml::from_str(
+      r#"
+[lambda_policy]
+require_timeout = true
+max_timeout = 300
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+      config.lambda_policy,
+      Some(LambdaPolicy {
+        require_timeout: true,
+        max_timeout: Some(300),
+        ..Default::default()
+      })
+    );
+  }
+
+  #[test]
+  fn test_config_environment() {
+    let mut config: Config = toml::from_str(
+      r#"
+lambda_module_name = "functions"
+shared_schema_names = ["common"]
+
+[environments.dev]
+api_path = "apis"
+terraform = "terraform/dev"
+
+[environments.prod]
+api_path = "apis"
+terraform = "terraform/prod"
+lambda_module_name = "prod_functions"
+"#,
+    )
+    .unwrap();
+    let environment = config.environment("prod").unwrap();
+    assert_eq!(environment.terraform, Some(PathBuf::from("terraform/prod")));
+    assert_eq!(
+      config.lambda_module_name,
+      Some("prod_functions".to_string())
+    );
+    assert_eq!(config.shared_schema_names, Some(vec!["common".to_string()]));
+    assert!(config.environment("staging").is_err());
+  }
+
+  #[test]
+  fn test_config_severity() {
+    let config: Config = toml::from_str(
+      r#"
+[severity]
+POST_NO_BODY = "error"
+OPTIONS_METHOD = "off"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+      config.severity,
+      BTreeMap::from([
+        (FindingCode::PostNoBody, SeverityOverride::Error),
+        (FindingCode::OptionsMethod, SeverityOverride::Off),
+      ])
+    );
+    assert!(toml::from_str::<Config>("[severity]\nUNKNOWN_CODE = \"error\"").is_err());
+  }
+
+  #[test]
+  fn test_config_profile() {
+    let config: Config = toml::from_str(r#"profile = "strict""#).unwrap();
+    assert_eq!(config.profile, Some(Profile::Strict));
+    assert!(toml::from_str::<Config>(r#"profile = "paranoid""#).is_err());
+  }
+}
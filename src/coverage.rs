@@ -0,0 +1,167 @@
+use serde::Serialize;
+
+use crate::{open_api::OpenAPIData, util::HttpMethod};
+
+/// The methods that are shown for each route, in the order of the columns
+pub const COVERAGE_METHODS: [HttpMethod; 5] = [
+  HttpMethod::Get,
+  HttpMethod::Post,
+  HttpMethod::Put,
+  HttpMethod::Patch,
+  HttpMethod::Delete,
+];
+
+/// Which of the common methods are defined for a route
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RouteCoverage {
+  /// The route path
+  pub route: String,
+  /// GET is defined
+  pub get: bool,
+  /// POST is defined
+  pub post: bool,
+  /// PUT is defined
+  pub put: bool,
+  /// PATCH is defined
+  pub patch: bool,
+  /// DELETE is defined
+  pub delete: bool,
+}
+
+impl RouteCoverage {
+  /// Whether the method is defined for the route, the methods that aren't shown are never defined
+  pub fn has(&self, method: &HttpMethod) -> bool {
+    match method {
+      HttpMethod::Get => self.get,
+      HttpMethod::Post => self.post,
+      HttpMethod::Put => self.put,
+      HttpMethod::Patch => self.patch,
+      HttpMethod::Delete => self.delete,
+      _ => false,
+    }
+  }
+
+  /// The methods that aren't defined for the route
+  pub fn missing(&self) -> Vec<&'static HttpMethod> {
+    COVERAGE_METHODS
+      .iter()
+      .filter(|method| !self.has(method))
+      .collect()
+  }
+}
+
+/// Lists the routes sorted by path with the methods that are defined for each
+pub fn route_coverage(data: &[OpenAPIData]) -> Vec<RouteCoverage> {
+  let mut routes: Vec<&String> = data.iter().map(|api| &api.path).collect();
+  routes.sort();
+  routes.dedup();
+  routes
+    .into_iter()
+    .map(|route| {
+      let defined = |method: HttpMethod| {
+        data
+          .iter()
+          .any(|api| api.path == *route && api.method == method)
+      };
+      RouteCoverage {
+        route: route.clone(),
+        get: defined(HttpMethod::Get),
+        post: defined(HttpMethod::Post),
+        put: defined(HttpMethod::Put),
+        patch: defined(HttpMethod::Patch),
+        delete: defined(HttpMethod::Delete),
+      }
+    })
+    .collect()
+}
+
+/// Formats the coverage as a table with a column per method, `-` for the methods that aren't
+/// defined, and the missing methods of each route at the end of its row
+pub fn format_coverage_table(coverage: &[RouteCoverage]) -> String {
+  let width = coverage
+    .iter()
+    .map(|route| route.route.len())
+    .chain(std::iter::once("Route".len()))
+    .max()
+    .unwrap_or_default();
+  let mut header = format!("{:width$}", "Route");
+  for method in &COVERAGE_METHODS {
+    header.push_str(&format!("  {}", method));
+  }
+  header.push_str("  Missing");
+  let mut lines = vec![header];
+  for route in coverage {
+    let mut line = format!("{:width$}", route.route);
+    for method in &COVERAGE_METHODS {
+      let cell = if route.has(method) { "x" } else { "-" };
+      line.push_str(&format!("  {:len$}", cell, len = method.to_string().len()));
+    }
+    let missing = route.missing();
+    line.push_str("  ");
+    line.push_str(
+      &missing
+        .iter()
+        .map(|method| method.to_string())
+        .collect::<Vec<_>>()
+        .join(", "),
+    );
+    lines.push(line.trim_end().to_string());
+  }
+  lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{open_api::ExecutionType, util::GatewayType};
+
+  fn api(method: HttpMethod, path: &str) -> OpenAPIData {
+    OpenAPIData {
+      path: path.to_string(),
+      method,
+      uri:
+        "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_arn}/invocations"
+          .to_string(),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    }
+  }
+
+  #[test]
+  fn test_route_coverage() {
+    let data = vec![
+      api(HttpMethod::Post, "/users"),
+      api(HttpMethod::Get, "/users"),
+      api(HttpMethod::Get, "/users/{id}"),
+      api(HttpMethod::Put, "/users/{id}"),
+      api(HttpMethod::Patch, "/users/{id}"),
+      api(HttpMethod::Delete, "/users/{id}"),
+      api(HttpMethod::Post, "/users/{id}"),
+    ];
+    let coverage = route_coverage(&data);
+    assert_eq!(
+      coverage[0],
+      RouteCoverage {
+        route: "/users".to_string(),
+        get: true,
+        post: true,
+        ..Default::default()
+      }
+    );
+    assert_eq!(
+      coverage[0].missing(),
+      vec![&HttpMethod::Put, &HttpMethod::Patch, &HttpMethod::Delete]
+    );
+    assert!(coverage[1].missing().is_empty());
+    assert_eq!(
+      format_coverage_table(&coverage),
+      [
+        "Route        GET  POST  PUT  PATCH  DELETE  Missing",
+        "/users       x    x     -    -      -       PUT, PATCH, DELETE",
+        "/users/{id}  x    x     x    x      x",
+      ]
+      .join("\n")
+    );
+  }
+}
@@ -1,26 +1,52 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
 use openapiv3::Operation;
-use simplelog::{debug, error, warn};
+use simplelog::{debug, warn};
 
 use crate::{
+  findings::{Finding, FindingCode, Findings},
   open_api::{ExecutionType, OpenAPIData},
-  terraform::{APIPath, Lambda},
-  util::HttpMethod,
+  terraform::{APIPath, Lambda, LambdaTriggerType},
+  util::{GatewayType, HttpMethod},
 };
 
 pub fn cross_validation(
   lambda_data: Vec<Lambda>,
   open_api_data: Vec<OpenAPIData>,
+  findings: &mut Findings,
 ) -> anyhow::Result<()> {
-  let mut valid = true;
+  let failures = findings.failures();
   for lambda_item in &lambda_data {
     if let Some(arn_key) = &lambda_item.arn_template_key {
-      lambda_item.apis.iter().for_each(|api| {
-        if !validate_lambda_against_open_api(&open_api_data, arn_key, &lambda_item.key, api) {
-          valid = false;
-        }
-      });
+      for api in &lambda_item.apis {
+        validate_lambda_against_open_api(&open_api_data, arn_key, &lambda_item.key, api, findings);
+      }
     }
   }
+  find_undocumented_routes(&lambda_data, &open_api_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  find_deprecated_routes(&lambda_data, &open_api_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  find_conflicting_routes(&lambda_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  find_function_name_mismatches(&lambda_data, &open_api_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  find_integration_type_mismatches(&lambda_data, &open_api_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  find_trigger_type_mismatches(&lambda_data, &open_api_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  find_gateway_type_mismatches(&lambda_data, &open_api_data)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
   let lambda_apis: Vec<APIPath> = lambda_data.iter().flat_map(|x| x.apis.clone()).collect();
   open_api_data
     .iter()
@@ -34,48 +60,586 @@ pub fn cross_validation(
           }
         }
         debug!("Filtered lambdas: {:?}", filtered_lambdas);
-        if filtered_lambdas.is_empty() {
-          valid = false;
-          error!(
-            "The path {} is not defined in Terraform",
-            open_api_item.path
+        let case_mismatch = if filtered_lambdas.is_empty() {
+          find_case_mismatch(
+            &open_api_item.path,
+            lambda_apis.iter().map(|api| &api.route),
+          )
+        } else {
+          None
+        };
+        if let Some(route) = case_mismatch {
+          findings.push(
+            Finding::warning(
+              FindingCode::RouteCaseMismatch,
+              format!(
+                "The path {} is not defined in Terraform but {} is, the casing differs",
+                open_api_item.path, route
+              ),
+            )
+            .for_route(&open_api_item.path),
+          );
+        } else if filtered_lambdas.is_empty() {
+          findings.push(
+            Finding::error(
+              FindingCode::PathNotInTerraform,
+              format!(
+                "The path {} is not defined in Terraform",
+                open_api_item.path
+              ),
+            )
+            .for_route(&open_api_item.path),
           );
         } else if !filtered_lambdas.contains(&open_api_item.method)
           && !filtered_lambdas.contains(&HttpMethod::Any)
         {
-          valid = false;
-          error!(
-            "The {} method is not defined for the path {} in Terraform",
-            open_api_item.method, open_api_item.path
+          findings.push(
+            Finding::error(
+              FindingCode::MethodNotInTerraform,
+              format!(
+                "The {} method is not defined for the path {} in Terraform, found: {}",
+                open_api_item.method,
+                open_api_item.path,
+                join_methods(filtered_lambdas)
+              ),
+            )
+            .for_operation(&open_api_item.method, &open_api_item.path),
           );
         }
       }
       ExecutionType::SQS => warn!("SQS Functions are currently not handled"), // TODO: Handle SQS
       ExecutionType::StepFunction => warn!("Step Functions are currently not handled"), // TODO: Handle Step Functions
     });
-  if !valid {
+  if findings.failures() > failures {
     return Err(anyhow::anyhow!("Invalid Terraform and OpenAPI documents"));
   }
   Ok(())
 }
 
+/// Cross validates each OpenAPI document on its own against the lambdas' permissions for the
+/// routes it defines, then reports the permissions for routes that none of the documents define
+///
+/// The findings about a document are reported in its file
+pub fn cross_validation_per_file(
+  lambda_data: Vec<Lambda>,
+  documents: Vec<(PathBuf, Vec<OpenAPIData>)>,
+  findings: &mut Findings,
+) -> anyhow::Result<()> {
+  let failures = findings.failures();
+  for (file, open_api_data) in &documents {
+    let routes: Vec<&str> = open_api_data.iter().map(|x| x.path.as_str()).collect();
+    let lambdas = lambdas_for_routes(&lambda_data, |route| routes.contains(&route));
+    let mut reported = Vec::new();
+    let mut sink = |finding: Finding| reported.push(finding);
+    // Whether the document is valid depends on which of its findings are reported below
+    let _ = cross_validation(
+      lambdas,
+      open_api_data.clone(),
+      &mut Findings::with_sink(&mut sink),
+    );
+    for finding in reported {
+      match finding.file {
+        Some(_) => findings.push(finding),
+        None => findings.push(finding.in_file(file)),
+      }
+    }
+  }
+  let defined: Vec<&str> = documents
+    .iter()
+    .flat_map(|(_, open_api_data)| open_api_data.iter().map(|x| x.path.as_str()))
+    .collect();
+  let undefined = lambdas_for_routes(&lambda_data, |route| !defined.contains(&route));
+  let _ = cross_validation(undefined, Vec::new(), findings);
+  if findings.failures() > failures {
+    return Err(anyhow::anyhow!("Invalid Terraform and OpenAPI documents"));
+  }
+  Ok(())
+}
+
+/// Runs the cross validation with `validate`, attaching the file and line the route is defined on
+/// to its findings, the path in the OpenAPI document that defines the route or the `source_arn` in
+/// `permissions` when none of the `documents` does
+///
+/// The findings that already have a file only get the line of the route in that file
+pub fn cross_validation_located(
+  documents: &[PathBuf],
+  permissions: Option<&Path>,
+  findings: &mut Findings,
+  validate: impl FnOnce(&mut Findings) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+  let failures = findings.failures();
+  let mut reported = Vec::new();
+  let mut sink = |finding: Finding| reported.push(finding);
+  // Whether the cross validation passes depends on which of its findings are reported below
+  let _ = validate(&mut Findings::with_sink(&mut sink));
+  let read = |file: &Path| Some((file.to_path_buf(), std::fs::read_to_string(file).ok()?));
+  let documents: Vec<(PathBuf, String)> = documents.iter().filter_map(|file| read(file)).collect();
+  let permissions = permissions.and_then(read);
+  for finding in reported {
+    findings.push(locate(finding, &documents, permissions.as_ref()));
+  }
+  if findings.failures() > failures {
+    return Err(anyhow::anyhow!("Invalid Terraform and OpenAPI documents"));
+  }
+  Ok(())
+}
+
+/// Attaches the file and line of the finding's route, see [`cross_validation_located`]
+fn locate(
+  finding: Finding,
+  documents: &[(PathBuf, String)],
+  permissions: Option<&(PathBuf, String)>,
+) -> Finding {
+  let Some(route) = finding.route.clone().filter(|_| finding.line.is_none()) else {
+    return finding;
+  };
+  let path_line = |contents: &str| {
+    contents.lines().position(|line| {
+      line
+        .trim()
+        .split_once(':')
+        .is_some_and(|(key, _)| key.trim_matches(['"', '\'']) == route)
+    })
+  };
+  if let Some((file, line)) = documents
+    .iter()
+    .filter(|(file, _)| finding.file.as_ref().is_none_or(|x| x == file))
+    .find_map(|(file, contents)| Some((file, path_line(contents)?)))
+  {
+    return finding.in_file(file).at_line(line + 1);
+  }
+  let Some((file, contents)) = permissions.filter(|_| finding.file.is_none()) else {
+    return finding;
+  };
+  let arns = [
+    finding
+      .method
+      .as_ref()
+      .map(|method| format!("/{}{}", method, route)),
+    finding.method.as_ref().map(|_| format!("/*{}", route)),
+    Some(route.clone()),
+  ];
+  let arn_line = arns.iter().flatten().find_map(|arn| {
+    contents.lines().position(|line| {
+      line.contains("source_arn") && line.trim().trim_end_matches([',', '"']).ends_with(arn)
+    })
+  });
+  match arn_line {
+    Some(line) => finding.in_file(file).at_line(line + 1),
+    None => finding.in_file(file),
+  }
+}
+
+/// The lambdas with a permission for a route that matches, keeping only those permissions
+fn lambdas_for_routes(lambda_data: &[Lambda], matches: impl Fn(&str) -> bool) -> Vec<Lambda> {
+  lambda_data
+    .iter()
+    .filter_map(|lambda| {
+      let apis: Vec<APIPath> = lambda
+        .apis
+        .iter()
+        .filter(|api| matches(&api.route))
+        .cloned()
+        .collect();
+      (!apis.is_empty()).then(|| Lambda {
+        apis,
+        ..lambda.clone()
+      })
+    })
+    .collect()
+}
+
+/// Warns about the routes granted in lambda_permissions.tf that aren't documented in OpenAPI, as
+/// undocumented endpoints are easily missed when reviewing the API
+///
+/// The lambdas with an `arn_template_key` are skipped as their routes are already checked against
+/// the OpenAPI documents by `validate_lambda_against_open_api`
+fn find_undocumented_routes(lambda_data: &[Lambda], open_api_data: &[OpenAPIData]) -> Vec<Finding> {
+  lambda_data
+    .iter()
+    .filter(|lambda_item| lambda_item.arn_template_key.is_none())
+    .flat_map(|lambda_item| {
+      lambda_item
+        .apis
+        .iter()
+        .filter(|api| {
+          !open_api_data.iter().any(|x| {
+            x.path == api.route && (api.method == HttpMethod::Any || x.method == api.method)
+          })
+        })
+        .filter(|api| {
+          find_case_mismatch(&api.route, open_api_data.iter().map(|x| &x.path)).is_none()
+        })
+        .map(|api| {
+          Finding::warning(
+            FindingCode::UndocumentedRoute,
+            format!(
+              "The {} {} route for the lambda {} is not documented in OpenAPI",
+              api.method, api.route, lambda_item.key
+            ),
+          )
+          .for_operation(&api.method, &api.route)
+        })
+    })
+    .collect()
+}
+
+/// Warns about the routes granted for any method (`*`) in lambda_permissions.tf that the OpenAPI
+/// documents only define some methods for, as the permission lets the lambda be invoked for the
+/// methods that aren't documented
+pub fn find_wildcard_method_permissions(
+  lambda_data: &[Lambda],
+  open_api_data: &[OpenAPIData],
+) -> Vec<Finding> {
+  lambda_data
+    .iter()
+    .flat_map(|lambda_item| {
+      lambda_item
+        .apis
+        .iter()
+        .filter(|api| api.method == HttpMethod::Any)
+        .filter_map(|api| {
+          let methods: Vec<HttpMethod> = open_api_data
+            .iter()
+            .filter(|x| x.path == api.route)
+            .map(|x| x.method.clone())
+            .collect();
+          if methods.is_empty() {
+            return None;
+          }
+          Some(
+            Finding::warning(
+              FindingCode::WildcardMethodPermission,
+              format!(
+                "The lambda {} is granted any method for {} but OpenAPI only defines {}",
+                lambda_item.key,
+                api.route,
+                join_methods(methods)
+              ),
+            )
+            .for_route(&api.route),
+          )
+        })
+    })
+    .collect()
+}
+
+/// Finds the routes that more than one lambda has an API permission for, as only one of them can
+/// be the route's integration in the API Gateway
+fn find_conflicting_routes(lambda_data: &[Lambda]) -> Vec<Finding> {
+  let mut claims: BTreeMap<(&String, &HttpMethod), Vec<&String>> = BTreeMap::new();
+  for lambda_item in lambda_data {
+    for api in &lambda_item.apis {
+      let keys = claims.entry((&api.route, &api.method)).or_default();
+      if !keys.contains(&&lambda_item.key) {
+        keys.push(&lambda_item.key);
+      }
+    }
+  }
+  claims
+    .into_iter()
+    .filter(|(_, keys)| keys.len() > 1)
+    .map(|((route, method), keys)| {
+      Finding::error(
+        FindingCode::ConflictingRoute,
+        format!(
+          "The {} {} route is granted to more than one lambda: {}",
+          method,
+          route,
+          keys
+            .iter()
+            .map(|key| key.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+        ),
+      )
+      .for_operation(method, route)
+    })
+    .collect()
+}
+
+/// Finds the routes whose integration URI names a concrete function, e.g.
+/// `...:function:users-get/invocations`, that doesn't match the key or the handler of the lambda
+/// with the permission for the route
+///
+/// The `${...}` placeholders in the function name are ignored, so `${stage}-users-get` matches the
+/// lambda `users-get`
+fn find_function_name_mismatches(
+  lambda_data: &[Lambda],
+  open_api_data: &[OpenAPIData],
+) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  for operation in open_api_data
+    .iter()
+    .filter(|operation| operation.execution_type == ExecutionType::Lambda)
+  {
+    let Some(function) = function_name(&operation.uri) else {
+      continue;
+    };
+    let granted = lambda_data.iter().filter(|lambda_item| {
+      lambda_item.apis.iter().any(|api| {
+        api.route == operation.path
+          && (api.method == operation.method || api.method == HttpMethod::Any)
+      })
+    });
+    for lambda_item in granted {
+      if function_matches_lambda(&function, lambda_item) {
+        continue;
+      }
+      findings.push(Finding::error(
+        FindingCode::FunctionNameMismatch,
+        format!(
+          "The integration URI of {} {} names the function {} but the route is granted to the lambda {} with the handler {}",
+          operation.method, operation.path, function, lambda_item.key, lambda_item.handler
+        ),
+      ).for_operation(&operation.method, &operation.path));
+    }
+  }
+  findings
+}
+
+/// Gets the concrete function name in a Lambda integration URI without its `${...}` placeholders,
+/// e.g. `users-get` for `...:function:users-get/invocations`, `None` when the function is only a
+/// placeholder
+fn function_name(uri: &str) -> Option<String> {
+  let (_, rest) = uri.split_once(":function:")?;
+  let name = rest.split(['/', ':']).next()?;
+  let mut concrete = String::new();
+  let mut remaining = name;
+  while let Some(start) = remaining.find("${") {
+    concrete.push_str(&remaining[..start]);
+    remaining = match remaining[start..].find('}') {
+      Some(end) => &remaining[start + end + 1..],
+      None => "",
+    };
+  }
+  concrete.push_str(remaining);
+  let concrete = concrete.trim_matches(['-', '_']);
+  (!concrete.is_empty()).then(|| concrete.to_string())
+}
+
+/// Whether the function name contains the lambda's key or the module of its handler, ignoring the
+/// casing and whether the words are separated by `-`, `_` or `.`
+fn function_matches_lambda(function: &str, lambda_item: &Lambda) -> bool {
+  let normalize = |name: &str| name.to_lowercase().replace(['_', '.'], "-");
+  let function = normalize(function);
+  let module = lambda_item
+    .handler
+    .rsplit_once('.')
+    .map_or(lambda_item.handler.as_str(), |(module, _)| module);
+  [lambda_item.key.as_str(), module]
+    .iter()
+    .filter(|name| !name.is_empty())
+    .any(|name| function.contains(&normalize(name)))
+}
+
+/// Finds a route that only differs from `route` in its casing, e.g. `/users` for `/Users`, as API
+/// Gateway matches the paths case-sensitively and a different casing is usually a typo
+fn find_case_mismatch<'a>(
+  route: &str,
+  routes: impl IntoIterator<Item = &'a String>,
+) -> Option<&'a String> {
+  routes
+    .into_iter()
+    .find(|other| *other != route && other.eq_ignore_ascii_case(route))
+}
+
+/// Lists the methods that are defined for a route, sorted and without duplicates
+fn join_methods(mut methods: Vec<HttpMethod>) -> String {
+  methods.sort();
+  methods.dedup();
+  methods
+    .iter()
+    .map(|method| method.to_string())
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+/// Finds the routes that are a Step Function or SQS integration in OpenAPI but are granted to a
+/// lambda in lambda_permissions.tf, as the request would never reach the lambda
+fn find_integration_type_mismatches(
+  lambda_data: &[Lambda],
+  open_api_data: &[OpenAPIData],
+) -> Vec<Finding> {
+  open_api_data
+    .iter()
+    .filter(|x| x.execution_type != ExecutionType::Lambda)
+    .flat_map(|x| {
+      lambda_data
+        .iter()
+        .filter(|lambda_item| {
+          lambda_item.apis.iter().any(|api| {
+            api.route == x.path && (api.method == HttpMethod::Any || api.method == x.method)
+          })
+        })
+        .map(|lambda_item| {
+          Finding::error(
+            FindingCode::IntegrationTypeMismatch,
+            format!(
+              "The {} {} route uses the {} integration in OpenAPI but is granted to the lambda {} in Terraform",
+              x.method, x.path, x.execution_type, lambda_item.key
+            ),
+          ).for_operation(&x.method, &x.path)
+        })
+    })
+    .collect()
+}
+
+/// Finds the routes that are a Lambda integration in OpenAPI using the ARN placeholder of a lambda
+/// that Terraform only grants to another service, e.g. an EventBridge rule, as the route's lambda
+/// is wired to the wrong trigger
+fn find_trigger_type_mismatches(
+  lambda_data: &[Lambda],
+  open_api_data: &[OpenAPIData],
+) -> Vec<Finding> {
+  open_api_data
+    .iter()
+    .filter(|x| x.execution_type == ExecutionType::Lambda)
+    .flat_map(|x| {
+      lambda_data
+        .iter()
+        .filter(|lambda_item| {
+          lambda_item.lambda_type != LambdaTriggerType::ApiGateway
+            && lambda_item
+              .arn_template_key
+              .as_ref()
+              .is_some_and(|placeholder| x.uri.contains(placeholder.as_str()))
+            && !lambda_item.apis.iter().any(|api| {
+              api.route == x.path && (api.method == HttpMethod::Any || api.method == x.method)
+            })
+        })
+        .map(|lambda_item| {
+          Finding::error(
+            FindingCode::TriggerTypeMismatch,
+            format!(
+              "The {} {} route is a {} integration in OpenAPI but its lambda {} is triggered by {} in Terraform, not API Gateway",
+              x.method, x.path, x.execution_type, lambda_item.key, lambda_item.lambda_type
+            ),
+          ).for_operation(&x.method, &x.path)
+        })
+    })
+    .collect()
+}
+
+/// Finds the Lambda routes whose permissions are all for the other type of API than the OpenAPI
+/// document the route is in, e.g. a route of an HTTP API granted with the execution ARN of a REST
+/// API, as the API isn't allowed to invoke the lambda
+///
+/// The type is compared per operation so a folder with both REST and HTTP API documents is
+/// matched against the permissions of each API, and a permission whose `source_arn` doesn't tell
+/// the type matches either
+fn find_gateway_type_mismatches(
+  lambda_data: &[Lambda],
+  open_api_data: &[OpenAPIData],
+) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  for operation in open_api_data
+    .iter()
+    .filter(|operation| operation.execution_type == ExecutionType::Lambda)
+  {
+    debug!(
+      "The {} {} route is in the {}",
+      operation.method, operation.path, operation.gateway
+    );
+    let mut granted: Vec<(&str, GatewayType)> = Vec::new();
+    let mut matched = false;
+    for lambda_item in lambda_data {
+      for api in lambda_item.apis.iter().filter(|api| {
+        api.route == operation.path
+          && (api.method == operation.method || api.method == HttpMethod::Any)
+      }) {
+        match api.gateway {
+          Some(gateway) if gateway != operation.gateway => {
+            granted.push((&lambda_item.key, gateway))
+          }
+          _ => matched = true,
+        }
+      }
+    }
+    if matched {
+      continue;
+    }
+    granted.dedup();
+    for (key, gateway) in granted {
+      findings.push(Finding::error(
+        FindingCode::GatewayTypeMismatch,
+        format!(
+          "The {} {} route is in an OpenAPI document of type {} but the permission of the lambda {} is of type {}",
+          operation.method, operation.path, operation.gateway, key, gateway
+        ),
+      ).for_operation(&operation.method, &operation.path));
+    }
+  }
+  findings
+}
+
+/// Warns about the deprecated operations that still have a lambda permission, as deprecated
+/// routes are usually meant to be removed
+fn find_deprecated_routes(lambda_data: &[Lambda], open_api_data: &[OpenAPIData]) -> Vec<Finding> {
+  lambda_data
+    .iter()
+    .flat_map(|lambda_item| {
+      lambda_item
+        .apis
+        .iter()
+        .filter(|api| {
+          open_api_data.iter().any(|x| {
+            x.deprecated
+              && x.path == api.route
+              && (api.method == HttpMethod::Any || x.method == api.method)
+          })
+        })
+        .map(|api| {
+          Finding::warning(
+            FindingCode::DeprecatedRoute,
+            format!(
+              "The {} {} route is deprecated but still backed by the lambda {}",
+              api.method, api.route, lambda_item.key
+            ),
+          )
+          .for_operation(&api.method, &api.route)
+        })
+    })
+    .collect()
+}
+
 fn validate_lambda_against_open_api(
   open_api_data: &[OpenAPIData],
   arn_key: &str,
   lambda_key: &str,
   api: &APIPath,
+  findings: &mut Findings,
 ) -> bool {
   debug!("API details: {:?}", api);
   let mut valid = true;
   let filtered = open_api_data.iter().filter(|x| x.path == api.route);
-  if filtered.clone().count() == 0 {
+  let case_mismatch = find_case_mismatch(&api.route, open_api_data.iter().map(|x| &x.path));
+  if let (0, Some(route)) = (filtered.clone().count(), case_mismatch) {
+    findings.push(
+      Finding::warning(
+        FindingCode::RouteCaseMismatch,
+        format!(
+          "The path {} is not defined in OpenAPI for the lambda {} but {} is, the casing differs",
+          api.route, lambda_key, route
+        ),
+      )
+      .for_route(&api.route),
+    );
+  } else if filtered.clone().count() == 0 {
     valid = false;
-    error!(
-      "The path {} is not defined in OpenAPI for the lambda {}",
-      api.route, lambda_key
+    findings.push(
+      Finding::error(
+        FindingCode::PathNotInOpenApi,
+        format!(
+          "The path {} is not defined in OpenAPI for the lambda {}",
+          api.route, lambda_key
+        ),
+      )
+      .for_route(&api.route),
     );
   } else {
     debug!("Routes: {:#?}", filtered.clone().collect::<Vec<_>>());
+    let found = join_methods(filtered.clone().map(|x| x.method.clone()).collect());
     let filtered = filtered.filter(|x| api.method == HttpMethod::Any || x.method == api.method);
     debug!(
       "Filtered routes and methods: {:#?}",
@@ -83,18 +647,27 @@ fn validate_lambda_against_open_api(
     );
     if filtered.clone().count() == 0 {
       valid = false;
-      error!(
-        "The {} method is not defined for the path {} for the lambda {}",
-        api.method, api.route, lambda_key
+      findings.push(
+        Finding::error(
+          FindingCode::MethodNotInOpenApi,
+          format!(
+            "The {} method is not defined for the path {} for the lambda {}, found: {}",
+            api.method, api.route, lambda_key, found
+          ),
+        )
+        .for_operation(&api.method, &api.route),
       );
     } else {
       filtered.for_each(|x| {
         if x.execution_type == ExecutionType::Lambda && !x.uri.contains(arn_key) {
           valid = false;
-          error!(
-            "The 'uri' doesn't contain the ARN placeholder '{}' in the 'x-amazon-apigateway-integration' extension for {} {} for the lambda {}",
-            arn_key, api.method, api.route, lambda_key
-          );
+          findings.push(Finding::error(
+            FindingCode::ArnMismatch,
+            format!(
+              "The 'uri' doesn't contain the ARN placeholder '{}' in the 'x-amazon-apigateway-integration' extension for {} {} for the lambda {}",
+              arn_key, api.method, api.route, lambda_key
+            ),
+          ).for_operation(&api.method, &api.route));
         }
       });
     }
@@ -107,6 +680,7 @@ pub fn validate_aws_api_gateway_integration(
   lambda_key: &str,
   arn_key: &str,
   api: &APIPath,
+  findings: &mut Findings,
 ) -> bool {
   let mut valid = true;
   match config.extensions.get("x-amazon-apigateway-integration") {
@@ -115,24 +689,39 @@ pub fn validate_aws_api_gateway_integration(
         let uri_path = uri.as_str().expect("Failed to convert URI to string");
         if !uri_path.contains(arn_key) {
           valid = false;
-          error!("The 'uri' doesn't contain the ARN placeholder '{}' in the 'x-amazon-apigateway-integration' extension for {} {} for the lambda {}", arn_key, api.method, api.route, lambda_key);
+          findings.push(Finding::error(
+            FindingCode::ArnMismatch,
+            format!("The 'uri' doesn't contain the ARN placeholder '{}' in the 'x-amazon-apigateway-integration' extension for {} {} for the lambda {}", arn_key, api.method, api.route, lambda_key),
+          ).for_operation(&api.method, &api.route));
         }
         if uri_path.contains("state:action") {
           valid = false;
-          error!(
-            "The 'uri' for {} {} is set up for step functions instead of the lambda {}",
-            api.method, api.route, lambda_key
+          findings.push(
+            Finding::error(
+              FindingCode::StepFunctionUri,
+              format!(
+                "The 'uri' for {} {} is set up for step functions instead of the lambda {}",
+                api.method, api.route, lambda_key
+              ),
+            )
+            .for_operation(&api.method, &api.route),
           );
         }
       }
       None => {
         valid = false;
-        error!("The 'uri' doesn't exist in the 'x-amazon-apigateway-integration' extension for {} {} for the lambda {}", api.method, api.route, lambda_key);
+        findings.push(Finding::error(
+          FindingCode::MissingIntegrationUri,
+          format!("The 'uri' doesn't exist in the 'x-amazon-apigateway-integration' extension for {} {} for the lambda {}", api.method, api.route, lambda_key),
+        ).for_operation(&api.method, &api.route));
       }
     },
     None => {
       valid = false;
-      error!("The 'x-amazon-apigateway-integration' extension doesn't exist for the {} {} for the lambda {}", api.method, api.route, lambda_key);
+      findings.push(Finding::error(
+        FindingCode::MissingIntegration,
+        format!("The 'x-amazon-apigateway-integration' extension doesn't exist for the {} {} for the lambda {}", api.method, api.route, lambda_key),
+      ).for_operation(&api.method, &api.route));
     }
   }
   valid
@@ -198,14 +787,469 @@ mod tests {
 
   use super::*;
 
+  #[test]
+  fn test_find_undocumented_routes() {
+    let open_api_data = vec![OpenAPIData {
+      path: "/test".to_string(),
+      method: HttpMethod::Get,
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      uri: "arn".to_string(),
+      gateway: GatewayType::Rest,
+    }];
+    let lambda_data = vec![Lambda {
+      key: "internal".to_string(),
+      arn_template_key: Some("arn".to_string()),
+      apis: vec![
+        APIPath {
+          route: "/test".to_string(),
+          method: HttpMethod::Get,
+          gateway: None,
+        },
+        APIPath {
+          route: "/internal".to_string(),
+          method: HttpMethod::Post,
+          gateway: None,
+        },
+      ],
+      ..Default::default()
+    }];
+    assert!(find_undocumented_routes(&lambda_data, &open_api_data).is_empty());
+    let lambda_data = vec![Lambda {
+      arn_template_key: None,
+      ..lambda_data[0].clone()
+    }];
+    let findings = find_undocumented_routes(&lambda_data, &open_api_data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::UndocumentedRoute);
+    assert_eq!(
+      findings[0].message,
+      "The POST /internal route for the lambda internal is not documented in OpenAPI"
+    );
+  }
+
+  #[test]
+  fn test_find_wildcard_method_permissions() {
+    let operation = |path: &str, method: HttpMethod| OpenAPIData {
+      path: path.to_string(),
+      method,
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      uri: "${arn}".to_string(),
+      gateway: GatewayType::Rest,
+    };
+    let open_api_data = vec![
+      operation("/items", HttpMethod::Get),
+      operation("/items", HttpMethod::Post),
+      operation("/orders", HttpMethod::Get),
+    ];
+    let api = |route: &str, method: HttpMethod| APIPath {
+      route: route.to_string(),
+      method,
+      gateway: None,
+    };
+    let lambda_data = vec![Lambda {
+      key: "items".to_string(),
+      apis: vec![
+        api("/items", HttpMethod::Any),
+        api("/orders", HttpMethod::Get),
+        api("/proxy", HttpMethod::Any),
+      ],
+      ..Default::default()
+    }];
+    let findings = find_wildcard_method_permissions(&lambda_data, &open_api_data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::WildcardMethodPermission);
+    assert_eq!(
+      findings[0].message,
+      "The lambda items is granted any method for /items but OpenAPI only defines GET, POST"
+    );
+  }
+
+  #[test]
+  fn test_find_gateway_type_mismatches() {
+    let operation = |path: &str, gateway: GatewayType| OpenAPIData {
+      path: path.to_string(),
+      method: HttpMethod::Get,
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      uri: "${arn}".to_string(),
+      gateway,
+    };
+    let open_api_data = vec![
+      operation("/items", GatewayType::Rest),
+      operation("/orders", GatewayType::Http),
+      operation("/users", GatewayType::Http),
+    ];
+    let api = |route: &str, gateway: Option<GatewayType>| APIPath {
+      route: route.to_string(),
+      method: HttpMethod::Get,
+      gateway,
+    };
+    let lambda_data = vec![Lambda {
+      key: "mixed".to_string(),
+      apis: vec![
+        api("/items", Some(GatewayType::Rest)),
+        api("/orders", Some(GatewayType::Rest)),
+        api("/users", None),
+      ],
+      ..Default::default()
+    }];
+    let findings = find_gateway_type_mismatches(&lambda_data, &open_api_data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::GatewayTypeMismatch);
+    assert_eq!(
+      findings[0].message,
+      "The GET /orders route is in an OpenAPI document of type HTTP API but the permission of the lambda mixed is of type REST API"
+    );
+  }
+
+  #[test]
+  fn test_cross_validation_per_file() {
+    let operation = |path: &str, uri: &str| OpenAPIData {
+      path: path.to_string(),
+      method: HttpMethod::Get,
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      uri: format!("functions/${{{}}}/invocations", uri),
+      gateway: GatewayType::Rest,
+    };
+    let documents = vec![
+      (
+        PathBuf::from("users.yaml"),
+        vec![operation("/users", "users_arn")],
+      ),
+      (
+        PathBuf::from("orders.yaml"),
+        vec![operation("/orders", "users_arn")],
+      ),
+    ];
+    let lambda = |key: &str, route: &str| Lambda {
+      key: key.to_string(),
+      arn_template_key: Some(format!("{}_arn", key)),
+      apis: vec![APIPath {
+        route: route.to_string(),
+        method: HttpMethod::Get,
+        gateway: None,
+      }],
+      ..Default::default()
+    };
+    let lambda_data = vec![
+      lambda("users", "/users"),
+      lambda("orders", "/orders"),
+      lambda("stale", "/stale"),
+    ];
+    let mut findings = Findings::default();
+    assert!(cross_validation_per_file(lambda_data, documents, &mut findings).is_err());
+    let reported: Vec<(FindingCode, Option<PathBuf>)> = findings
+      .iter()
+      .map(|finding| (finding.code, finding.file.clone()))
+      .collect();
+    assert_eq!(
+      reported,
+      vec![
+        (FindingCode::ArnMismatch, Some(PathBuf::from("orders.yaml"))),
+        (FindingCode::PathNotInOpenApi, None),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_find_integration_type_mismatches() {
+    let open_api_data = vec![
+      OpenAPIData {
+        path: "/process".to_string(),
+        method: HttpMethod::Post,
+        execution_type: ExecutionType::SQS,
+        uri: "arn:aws:apigateway:${region}:sqs:action/SendMessage".to_string(),
+        deprecated: false,
+        gateway: GatewayType::Rest,
+      },
+      OpenAPIData {
+        path: "/queue".to_string(),
+        method: HttpMethod::Post,
+        execution_type: ExecutionType::SQS,
+        uri: "arn:aws:apigateway:${region}:sqs:action/SendMessage".to_string(),
+        deprecated: false,
+        gateway: GatewayType::Rest,
+      },
+    ];
+    let lambda_data = vec![Lambda {
+      key: "processor".to_string(),
+      arn_template_key: Some("processor_arn".to_string()),
+      apis: vec![APIPath {
+        route: "/process".to_string(),
+        method: HttpMethod::Post,
+        gateway: None,
+      }],
+      ..Default::default()
+    }];
+    let findings = find_integration_type_mismatches(&lambda_data, &open_api_data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::IntegrationTypeMismatch);
+    assert_eq!(
+      findings[0].message,
+      "The POST /process route uses the SQS integration in OpenAPI but is granted to the lambda processor in Terraform"
+    );
+  }
+
+  #[test]
+  fn test_find_trigger_type_mismatches() {
+    let open_api_data = vec![OpenAPIData {
+      path: "/events".to_string(),
+      method: HttpMethod::Post,
+      execution_type: ExecutionType::Lambda,
+      uri:
+        "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${publish_arn}/invocations"
+          .to_string(),
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    }];
+    let lambda = |lambda_type: LambdaTriggerType, apis: Vec<APIPath>| Lambda {
+      key: "publish".to_string(),
+      arn_template_key: Some("publish_arn".to_string()),
+      lambda_type,
+      apis,
+      ..Default::default()
+    };
+    let findings = find_trigger_type_mismatches(
+      &[lambda(LambdaTriggerType::EventBridge, Vec::new())],
+      &open_api_data,
+    );
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::TriggerTypeMismatch);
+    assert_eq!(
+      findings[0].message,
+      "The POST /events route is a Lambda integration in OpenAPI but its lambda publish is triggered by EventBridge in Terraform, not API Gateway"
+    );
+    let granted = vec![APIPath {
+      route: "/events".to_string(),
+      method: HttpMethod::Post,
+      gateway: None,
+    }];
+    assert!(find_trigger_type_mismatches(
+      &[lambda(LambdaTriggerType::EventBridge, granted)],
+      &open_api_data
+    )
+    .is_empty());
+    assert!(find_trigger_type_mismatches(
+      &[lambda(LambdaTriggerType::ApiGateway, Vec::new())],
+      &open_api_data
+    )
+    .is_empty());
+  }
+
+  #[test]
+  fn test_find_deprecated_routes() {
+    let open_api_data = vec![
+      OpenAPIData {
+        path: "/old".to_string(),
+        method: HttpMethod::Get,
+        execution_type: ExecutionType::Lambda,
+        uri: "arn".to_string(),
+        deprecated: true,
+        gateway: GatewayType::Rest,
+      },
+      OpenAPIData {
+        path: "/new".to_string(),
+        method: HttpMethod::Get,
+        execution_type: ExecutionType::Lambda,
+        uri: "arn".to_string(),
+        deprecated: false,
+        gateway: GatewayType::Rest,
+      },
+    ];
+    let lambda_data = vec![Lambda {
+      key: "legacy".to_string(),
+      arn_template_key: Some("arn".to_string()),
+      apis: vec![
+        APIPath {
+          route: "/old".to_string(),
+          method: HttpMethod::Get,
+          gateway: None,
+        },
+        APIPath {
+          route: "/new".to_string(),
+          method: HttpMethod::Get,
+          gateway: None,
+        },
+      ],
+      ..Default::default()
+    }];
+    let findings = find_deprecated_routes(&lambda_data, &open_api_data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::DeprecatedRoute);
+    assert_eq!(
+      findings[0].message,
+      "The GET /old route is deprecated but still backed by the lambda legacy"
+    );
+  }
+
+  #[test]
+  fn test_find_conflicting_routes() {
+    let health = |key: &str| Lambda {
+      key: key.to_string(),
+      apis: vec![
+        APIPath {
+          route: "/health".to_string(),
+          method: HttpMethod::Get,
+          gateway: None,
+        },
+        APIPath {
+          route: format!("/{}", key),
+          method: HttpMethod::Get,
+          gateway: None,
+        },
+      ],
+      ..Default::default()
+    };
+    let findings = find_conflicting_routes(&[health("status"), health("ping")]);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::ConflictingRoute);
+    assert_eq!(
+      findings[0].message,
+      "The GET /health route is granted to more than one lambda: status, ping"
+    );
+  }
+
+  #[test]
+  fn test_find_function_name_mismatches() {
+    let operation = |path: &str, function: &str| {
+      OpenAPIData {
+      path: path.to_string(),
+      method: HttpMethod::Get,
+      uri: format!(
+        "arn:aws:apigateway:${{region}}:lambda:path/2015-03-31/functions/arn:aws:lambda:${{region}}:123456789012:function:{}/invocations",
+        function
+      ),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false, gateway: GatewayType::Rest, }
+    };
+    let lambda = |key: &str, handler: &str, route: &str| Lambda {
+      key: key.to_string(),
+      handler: handler.to_string(),
+      apis: vec![APIPath {
+        route: route.to_string(),
+        method: HttpMethod::Get,
+        gateway: None,
+      }],
+      ..Default::default()
+    };
+    let lambda_data = vec![
+      lambda("users-get", "users.handler", "/users"),
+      lambda("orders", "list_orders.handler", "/orders"),
+      lambda("items", "items.handler", "/items"),
+    ];
+    let open_api_data = vec![
+      operation("/users", "${stage}-users-get"),
+      operation("/orders", "shop-list-orders"),
+      operation("/items", "orders-get"),
+      operation("/placeholder", "${items_name}"),
+    ];
+    let findings = find_function_name_mismatches(&lambda_data, &open_api_data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::FunctionNameMismatch);
+    assert_eq!(
+      findings[0].message,
+      "The integration URI of GET /items names the function orders-get but the route is granted to the lambda items with the handler items.handler"
+    );
+  }
+
+  #[test]
+  fn test_route_case_mismatch() {
+    let open_api_data = vec![OpenAPIData {
+      path: "/Users".to_string(),
+      method: HttpMethod::Get,
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      uri: "arn".to_string(),
+      gateway: GatewayType::Rest,
+    }];
+    let lambda_data = vec![Lambda {
+      key: "users".to_string(),
+      arn_template_key: Some("arn".to_string()),
+      apis: vec![APIPath {
+        route: "/users".to_string(),
+        method: HttpMethod::Get,
+        gateway: None,
+      }],
+      ..Default::default()
+    }];
+    let mut findings = Findings::default();
+    assert!(cross_validation(lambda_data, open_api_data, &mut findings).is_ok());
+    let messages: Vec<_> = findings
+      .iter()
+      .map(|finding| (finding.code, finding.message.as_str()))
+      .collect();
+    assert_eq!(
+      messages,
+      vec![
+        (
+          FindingCode::RouteCaseMismatch,
+          "The path /users is not defined in OpenAPI for the lambda users but /Users is, the casing differs"
+        ),
+        (
+          FindingCode::RouteCaseMismatch,
+          "The path /Users is not defined in Terraform but /users is, the casing differs"
+        ),
+      ]
+    );
+    assert_eq!(
+      find_case_mismatch("/users", &["/users".to_string(), "/USERS".to_string()]),
+      Some(&"/USERS".to_string())
+    );
+  }
+
   // validate_lambda_against_open_api tests
+  #[test]
+  fn test_validate_lambda_against_open_api_method_mismatch() {
+    let open_api_data = vec![
+      OpenAPIData {
+        path: "/test".to_string(),
+        method: HttpMethod::Put,
+        execution_type: ExecutionType::Lambda,
+        deprecated: false,
+        uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
+      },
+      OpenAPIData {
+        path: "/test".to_string(),
+        method: HttpMethod::Get,
+        execution_type: ExecutionType::Lambda,
+        deprecated: false,
+        uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
+      },
+    ];
+    let mut findings = Findings::default();
+    assert!(!validate_lambda_against_open_api(
+      &open_api_data,
+      "arn",
+      "test",
+      &APIPath {
+        route: "/test".to_string(),
+        method: HttpMethod::Post,
+        gateway: None,
+      },
+      &mut findings
+    ));
+    let finding = findings.iter().next().expect("Expected a finding");
+    assert_eq!(finding.code, FindingCode::MethodNotInOpenApi);
+    assert_eq!(
+      finding.message,
+      "The POST method is not defined for the path /test for the lambda test, found: GET, PUT"
+    );
+  }
+
   #[test]
   fn test_validate_lambda_against_open_api_arn() {
     let open_api_data = vec![OpenAPIData {
       path: "/test".to_string(),
       method: HttpMethod::Get,
       execution_type: ExecutionType::Lambda,
+      deprecated: false,
       uri: "arn".to_string(),
+      gateway: GatewayType::Rest,
     }];
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -214,7 +1258,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -223,7 +1269,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Post,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -232,7 +1280,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
   }
 
@@ -242,7 +1292,9 @@ mod tests {
       path: "/test".to_string(),
       method: HttpMethod::Get,
       execution_type: ExecutionType::StepFunction,
+      deprecated: false,
       uri: "state:action".to_string(),
+      gateway: GatewayType::Rest,
     }];
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -251,7 +1303,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -260,7 +1314,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Post,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -269,7 +1325,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
   }
 
@@ -280,13 +1338,17 @@ mod tests {
         path: "/test".to_string(),
         method: HttpMethod::Get,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test2".to_string(),
         method: HttpMethod::Get,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
     ];
     assert!(validate_lambda_against_open_api(
@@ -296,7 +1358,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -305,7 +1369,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -314,7 +1380,9 @@ mod tests {
       &APIPath {
         route: "/test3".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
   }
 
@@ -325,13 +1393,17 @@ mod tests {
         path: "/test".to_string(),
         method: HttpMethod::Get,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test".to_string(),
         method: HttpMethod::Post,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
     ];
 
@@ -342,7 +1414,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -351,7 +1425,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Post,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -360,7 +1436,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Put,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
   }
 
@@ -371,43 +1449,57 @@ mod tests {
         path: "/test".to_string(),
         method: HttpMethod::Get,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test".to_string(),
         method: HttpMethod::Post,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test2".to_string(),
         method: HttpMethod::Get,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test2".to_string(),
         method: HttpMethod::Post,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test2".to_string(),
         method: HttpMethod::Put,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test2".to_string(),
         method: HttpMethod::Patch,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
       OpenAPIData {
         path: "/test2".to_string(),
         method: HttpMethod::Delete,
         execution_type: ExecutionType::Lambda,
+        deprecated: false,
         uri: "arn".to_string(),
+        gateway: GatewayType::Rest,
       },
     ];
     assert!(validate_lambda_against_open_api(
@@ -417,7 +1509,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -426,7 +1520,9 @@ mod tests {
       &APIPath {
         route: "/test".to_string(),
         method: HttpMethod::Post,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -435,7 +1531,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -444,7 +1542,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Post,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -453,7 +1553,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Put,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -462,7 +1564,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Patch,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(validate_lambda_against_open_api(
       &open_api_data,
@@ -471,7 +1575,9 @@ mod tests {
       &APIPath {
         route: "/test2".to_string(),
         method: HttpMethod::Delete,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
     assert!(!validate_lambda_against_open_api(
       &open_api_data,
@@ -480,7 +1586,44 @@ mod tests {
       &APIPath {
         route: "/test3".to_string(),
         method: HttpMethod::Get,
-      }
+        gateway: None,
+      },
+      &mut Findings::default()
     ));
   }
+
+  #[test]
+  fn test_locate() {
+    let documents = vec![(
+      PathBuf::from("open_api/users.yaml"),
+      "paths:\n  /users:\n    get: {}\n  '/users/{id}':\n    get: {}\n".to_string(),
+    )];
+    let permissions = (
+      PathBuf::from("terraform/lambda_permissions.tf"),
+      "locals {\n  lambdas_permissions = {\n    orders = [\n      {\n        source_arn = \"${arn}/*/POST/orders\"\n      },\n      {\n        source_arn = \"${arn}/*/GET/orders\"\n      }\n    ]\n  }\n}\n".to_string(),
+    );
+    let located = locate(
+      Finding::error(FindingCode::PathNotInTerraform, "").for_route("/users/{id}"),
+      &documents,
+      Some(&permissions),
+    );
+    assert_eq!(located.file, Some(PathBuf::from("open_api/users.yaml")));
+    assert_eq!(located.line, Some(4));
+    let located = locate(
+      Finding::error(FindingCode::PathNotInOpenApi, "").for_operation(&HttpMethod::Get, "/orders"),
+      &documents,
+      Some(&permissions),
+    );
+    assert_eq!(
+      located.file,
+      Some(PathBuf::from("terraform/lambda_permissions.tf"))
+    );
+    assert_eq!(located.line, Some(8));
+    let located = locate(
+      Finding::error(FindingCode::DuplicateTag, ""),
+      &documents,
+      Some(&permissions),
+    );
+    assert_eq!((located.file, located.line), (None, None));
+  }
 }
@@ -0,0 +1,104 @@
+use crate::{open_api::OpenAPIData, terraform::APIPath};
+
+/// The routes that differ between the source OpenAPI documents and the deployed API
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RouteDiff {
+  /// Routes in the source documents that aren't deployed
+  pub added: Vec<APIPath>,
+  /// Routes that are deployed but no longer in the source documents
+  pub removed: Vec<APIPath>,
+}
+
+impl RouteDiff {
+  /// Whether the deployed API matches the source documents
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty()
+  }
+}
+
+/// Compares the routes and methods of the source OpenAPI documents to the deployed API
+pub fn diff_routes(source: &[OpenAPIData], deployed: &[OpenAPIData]) -> RouteDiff {
+  let source = to_api_paths(source);
+  let deployed = to_api_paths(deployed);
+  RouteDiff {
+    added: source
+      .iter()
+      .filter(|api| !deployed.contains(api))
+      .cloned()
+      .collect(),
+    removed: deployed
+      .iter()
+      .filter(|api| !source.contains(api))
+      .cloned()
+      .collect(),
+  }
+}
+
+/// Gets the sorted and deduplicated routes from the API data
+fn to_api_paths(data: &[OpenAPIData]) -> Vec<APIPath> {
+  let mut paths: Vec<APIPath> = data
+    .iter()
+    .map(|api| APIPath {
+      method: api.method.clone(),
+      route: api.path.clone(),
+      gateway: None,
+    })
+    .collect();
+  paths.sort();
+  paths.dedup();
+  paths
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    open_api::ExecutionType,
+    util::{GatewayType, HttpMethod},
+  };
+
+  fn api(method: HttpMethod, path: &str) -> OpenAPIData {
+    OpenAPIData {
+      path: path.to_string(),
+      method,
+      uri:
+        "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_arn}/invocations"
+          .to_string(),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    }
+  }
+
+  #[test]
+  fn test_diff_routes() {
+    let source = vec![
+      api(HttpMethod::Get, "/a"),
+      api(HttpMethod::Post, "/a"),
+      api(HttpMethod::Get, "/b"),
+    ];
+    let deployed = vec![
+      api(HttpMethod::Get, "/a"),
+      api(HttpMethod::Get, "/b"),
+      api(HttpMethod::Delete, "/c"),
+    ];
+    let diff = diff_routes(&source, &deployed);
+    assert_eq!(
+      diff.added,
+      vec![APIPath {
+        method: HttpMethod::Post,
+        route: "/a".to_string(),
+        gateway: None,
+      }]
+    );
+    assert_eq!(
+      diff.removed,
+      vec![APIPath {
+        method: HttpMethod::Delete,
+        route: "/c".to_string(),
+        gateway: None,
+      }]
+    );
+    assert!(diff_routes(&source, &source).is_empty());
+  }
+}
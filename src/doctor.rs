@@ -0,0 +1,212 @@
+use std::{ffi::OsStr, path::Path};
+
+use crate::{
+  logger::Glyphs,
+  open_api::{find_api_files, find_files, DEFAULT_API_EXTENSIONS},
+  terraform::{self, SPLIT_FILES},
+  util::validating_path,
+};
+
+/// A precondition for running the verification and whether it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+  /// What was checked, e.g. `The OpenAPI path "api" exists`
+  pub name: String,
+  /// What's wrong and how to fix it, `None` when the check passed
+  pub problem: Option<String>,
+}
+
+impl Check {
+  /// A check that passed
+  pub fn ok(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      problem: None,
+    }
+  }
+
+  /// A check that found a problem, with the guidance for fixing it
+  pub fn problem(name: impl Into<String>, problem: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      problem: Some(problem.into()),
+    }
+  }
+}
+
+/// Checks the paths and the files in them that `sv verify` reads, without validating the files
+///
+/// The files in a path are only checked when the path exists
+pub fn check_environment(
+  api_path: &Path,
+  terraform: &Path,
+  api_extensions: &[String],
+) -> Vec<Check> {
+  let mut checks = Vec::new();
+  match validating_path(api_path) {
+    Ok(()) => {
+      checks.push(Check::ok(format!("The OpenAPI path {:?} exists", api_path)));
+      checks.push(check_api_files(api_path, api_extensions));
+    }
+    Err(e) => checks.push(Check::problem(
+      format!("The OpenAPI path {:?} exists", api_path),
+      format!(
+        "{}, pass the folder with the OpenAPI documents to --api-path",
+        e
+      ),
+    )),
+  }
+  match validating_path(terraform) {
+    Ok(()) => {
+      checks.push(Check::ok(format!(
+        "The Terraform path {:?} exists",
+        terraform
+      )));
+      checks.push(check_terraform_files(terraform));
+    }
+    Err(e) => checks.push(Check::problem(
+      format!("The Terraform path {:?} exists", terraform),
+      format!(
+        "{}, pass the folder with the Terraform files to --terraform",
+        e
+      ),
+    )),
+  }
+  checks
+}
+
+/// Checks that there are OpenAPI documents in the path
+fn check_api_files(api_path: &Path, api_extensions: &[String]) -> Check {
+  match find_api_files(api_path, api_extensions).len() {
+    0 => {
+      let extensions: Vec<&str> = DEFAULT_API_EXTENSIONS
+        .iter()
+        .copied()
+        .chain(api_extensions.iter().map(|e| e.trim_start_matches('.')))
+        .collect();
+      Check::problem(
+        "OpenAPI documents are found",
+        format!(
+          "No files with the extension {} in {:?} or the folders in it, pass --api-ext for documents with other extensions and check .svignore",
+          extensions.join(", "),
+          api_path
+        ),
+      )
+    }
+    1 => Check::ok("Found 1 OpenAPI document"),
+    count => Check::ok(format!("Found {} OpenAPI documents", count)),
+  }
+}
+
+/// Checks that there are Terraform files in the path and that the lambdas, the permissions and
+/// the API gateway can be found in them
+fn check_terraform_files(terraform: &Path) -> Check {
+  if find_files(terraform, OsStr::new("tf")).is_empty() {
+    return Check::problem(
+      "Terraform files are found",
+      format!(
+        "No .tf files in {:?} or the folders in it, check the path and .svignore",
+        terraform
+      ),
+    );
+  }
+  let name = if SPLIT_FILES.iter().any(|name| terraform.join(name).exists()) {
+    format!("Found {}", SPLIT_FILES.join(", "))
+  } else {
+    "Found the lambdas, permissions and API gateway in the Terraform files".to_string()
+  };
+  match terraform::check_sources(terraform) {
+    Ok(()) => Check::ok(name),
+    Err(e) => Check::problem(
+      "The lambdas, permissions and API gateway are found in the Terraform files",
+      format!(
+        "{}, either add all of {} or define the lambdas and lambdas_permissions locals and an API gateway with a templatefile of the OpenAPI document",
+        e,
+        SPLIT_FILES.join(", ")
+      ),
+    ),
+  }
+}
+
+/// Formats the checks as a checklist, with the problem and its guidance below each check that
+/// failed
+pub fn format_checklist(checks: &[Check], glyphs: Glyphs) -> String {
+  let mut lines = Vec::new();
+  for check in checks {
+    lines.push(match (glyphs, &check.problem) {
+      (Glyphs::Unicode, None) => glyphs.pass(&check.name),
+      (Glyphs::Unicode, Some(_)) => glyphs.fail(&check.name),
+      (Glyphs::Ascii, None) => format!("ok: {}", check.name),
+      (Glyphs::Ascii, Some(_)) => format!("problem: {}", check.name),
+    });
+    if let Some(problem) = &check.problem {
+      lines.push(format!("  {}", problem));
+    }
+  }
+  lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_environment() {
+    let checks = check_environment(
+      Path::new("test_files/open_api"),
+      Path::new("test_files/combined"),
+      &[],
+    );
+    assert_eq!(
+      checks,
+      vec![
+        Check::ok("The OpenAPI path \"test_files/open_api\" exists"),
+        Check::ok("Found 3 OpenAPI documents"),
+        Check::ok("The Terraform path \"test_files/combined\" exists"),
+        Check::ok("Found the lambdas, permissions and API gateway in the Terraform files"),
+      ]
+    );
+    let checks = check_environment(
+      Path::new("test_files/api_ext"),
+      Path::new("test_files/api_gateway"),
+      &[],
+    );
+    assert_eq!(checks.len(), 4);
+    assert_eq!(checks[1].name, "OpenAPI documents are found");
+    assert!(checks[1]
+      .problem
+      .as_deref()
+      .is_some_and(|problem| problem.starts_with("No files with the extension yml, yaml")));
+    assert_eq!(
+      checks[3].name,
+      "The lambdas, permissions and API gateway are found in the Terraform files"
+    );
+    assert!(checks[3]
+      .problem
+      .as_deref()
+      .is_some_and(|problem| problem.starts_with("File lambda.tf doesn't exist")));
+    let checks = check_environment(
+      Path::new("test_files/missing"),
+      Path::new("test_files/missing"),
+      &[],
+    );
+    assert_eq!(checks.len(), 2);
+    assert!(checks.iter().all(|check| check.problem.is_some()));
+  }
+
+  #[test]
+  fn test_format_checklist() {
+    let checks = vec![
+      Check::ok("Found 1 OpenAPI document"),
+      Check::problem("Terraform files are found", "No .tf files in \"tf\""),
+    ];
+    assert_eq!(
+      format_checklist(&checks, Glyphs::Unicode),
+      "✓ Found 1 OpenAPI document\n✗ Terraform files are found\n  No .tf files in \"tf\""
+    );
+    assert_eq!(
+      format_checklist(&checks, Glyphs::Ascii),
+      "ok: Found 1 OpenAPI document\nproblem: Terraform files are found\n  No .tf files in \"tf\""
+    );
+  }
+}
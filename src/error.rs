@@ -0,0 +1,58 @@
+/// Exit code when the files were checked and are invalid
+pub const EXIT_INVALID: u8 = 1;
+/// Exit code when sv couldn't check the files, e.g. a path doesn't exist
+pub const EXIT_TOOL_ERROR: u8 = 2;
+
+/// An error where sv couldn't run the checks, rather than the checks finding the files invalid,
+/// so the two can be told apart by the exit code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvError {
+  /// A path that was given doesn't exist, isn't the right kind or has nothing to check
+  Path(String),
+  /// A file couldn't be read or written
+  Io(String),
+  /// The config, a variable file or a report is invalid, or a setting is missing
+  Config(String),
+}
+
+impl std::fmt::Display for SvError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SvError::Path(message) | SvError::Io(message) | SvError::Config(message) => {
+        write!(f, "{}", message)
+      }
+    }
+  }
+}
+
+impl std::error::Error for SvError {}
+
+/// Gets the exit code for the error, `EXIT_TOOL_ERROR` when sv couldn't run the checks and
+/// `EXIT_INVALID` otherwise
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+  if error.chain().any(|cause| cause.is::<SvError>()) {
+    EXIT_TOOL_ERROR
+  } else {
+    EXIT_INVALID
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::validating_path;
+
+  #[test]
+  fn test_exit_code() {
+    let missing = validating_path(std::path::Path::new("test_files/missing")).unwrap_err();
+    assert_eq!(exit_code(&missing), EXIT_TOOL_ERROR);
+    assert_eq!(
+      exit_code(&missing.context("Failed to verify")),
+      EXIT_TOOL_ERROR
+    );
+    assert_eq!(
+      exit_code(&anyhow::anyhow!("Invalid Terraform and OpenAPI documents")),
+      EXIT_INVALID
+    );
+  }
+}
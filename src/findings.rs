@@ -0,0 +1,962 @@
+use core::fmt::Display;
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use simplelog::{error, info, warn};
+
+use crate::{
+  hints::suggestion_for,
+  logger::Glyphs,
+  suppressions::Suppression,
+  util::{matches_route_glob, HttpMethod},
+};
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  /// Something that is likely a mistake but doesn't fail validation
+  Warning,
+  /// Something that fails validation
+  Error,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Severity::Warning => write!(f, "warning"),
+      Severity::Error => write!(f, "error"),
+    }
+  }
+}
+
+/// The severity a finding code is reported with, set in the `[severity]` section of `sv.toml`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityOverride {
+  /// Report it as an error and fail validation
+  Error,
+  /// Report it as a warning
+  Warning,
+  /// Don't report it
+  Off,
+}
+
+/// The part of the validation a finding comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Section {
+  /// Validating the OpenAPI documents
+  OpenApi,
+  /// Validating the Terraform files
+  Terraform,
+  /// Checking the OpenAPI documents and the Terraform files against each other
+  CrossValidation,
+}
+
+impl Section {
+  /// The sections in the order they are validated
+  pub const ALL: [Section; 3] = [
+    Section::OpenApi,
+    Section::Terraform,
+    Section::CrossValidation,
+  ];
+}
+
+impl Display for Section {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Section::OpenApi => write!(f, "OpenAPI"),
+      Section::Terraform => write!(f, "Terraform"),
+      Section::CrossValidation => write!(f, "Cross-validation"),
+    }
+  }
+}
+
+/// Stable identifier for the kind of problem a finding reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FindingCode {
+  /// The same tag is declared more than once across the OpenAPI documents
+  DuplicateTag,
+  /// An integration's `passthroughBehavior` is unknown or rejects the requests
+  IntegrationPassthrough,
+  /// An integration's `requestParameters` entry is malformed or maps a parameter that doesn't exist
+  IntegrationRequestParameter,
+  /// An operation uses a tag that isn't declared in the document's `tags`
+  UndefinedTag,
+  /// A document's `info.version` is missing or isn't a semver version
+  InfoVersion,
+  /// The documents that are merged declare different `info.version`s
+  ConflictingInfoVersion,
+  /// The documents that are merged declare different `info.title`s
+  ConflictingInfoTitle,
+  /// The documents that are merged declare OpenAPI versions with a different major or minor
+  /// version, e.g. `3.0.0` and `3.1.0`
+  ConflictingOpenApiVersion,
+  /// Paths were lost or combined when merging the OpenAPI documents
+  MergedPathCollision,
+  /// An OpenAPI document has logic errors
+  InvalidDocument,
+  /// An OpenAPI document couldn't be parsed
+  UnparsableDocument,
+  /// An OpenAPI document couldn't be read, e.g. it was deleted or isn't UTF-8
+  UnreadableDocument,
+  /// An OpenAPI document has cyclic references and was skipped
+  CyclicRef,
+  /// A `$ref` points to something that doesn't exist in the document
+  UnresolvedRef,
+  /// A parameter in the route isn't declared as an `in: path` parameter
+  UndeclaredPathParameter,
+  /// An `in: path` parameter is declared but isn't in the route
+  OrphanPathParameter,
+  /// A POST, PUT or PATCH operation has no request body or parameters
+  PostNoBody,
+  /// A POST, PUT or PATCH request body isn't marked as required
+  OptionalRequestBody,
+  /// An OPTIONS operation should be double checked
+  OptionsMethod,
+  /// A path has no OPTIONS method although the document uses CORS
+  MissingCorsOptions,
+  /// A security requirement uses a scheme that isn't in `components.securitySchemes`
+  UndefinedSecurityScheme,
+  /// An operation has no security requirement
+  MissingSecurity,
+  /// A 2xx response of a Lambda proxy integration only declares content types other than JSON
+  NonJsonProxyResponse,
+  /// An operation has no tags
+  UntaggedOperation,
+  /// An HTTP method that API Gateway doesn't support is used
+  UnsupportedMethod,
+  /// More than one document defines the same path and method differently
+  ConflictingOperation,
+  /// Different path keys in a document are the same route, e.g. `/users` and `/users/`
+  DuplicateRoute,
+  /// A `{proxy+}` route has explicit sibling routes with the same method under the same base path
+  ProxyShadowsPath,
+  /// A Terraform variable is used but isn't set in the var file
+  UnresolvedVariable,
+  /// A lambda key is declared more than once in lambda.tf
+  DuplicateLambdaKey,
+  /// Several lambdas use the same handler
+  DuplicateHandler,
+  /// A lambda in lambda.tf doesn't set a handler
+  MissingHandler,
+  /// `lambda_permissions.tf` has a key that isn't a lambda, or is a lambda without a handler
+  #[serde(alias = "EXTRA_PERMISSION_KEY")]
+  UndefinedPermissionLambda,
+  /// A lambda key is declared more than once in lambda_permissions.tf
+  DuplicatePermissionKey,
+  /// The lambda module's `for_each` has a key that isn't in the `lambdas` local
+  UnknownForEachKey,
+  /// A lambda permission's principal is a service that isn't handled yet
+  UnknownPrincipal,
+  /// A lambda is referenced more than once in api_gateway.tf
+  DuplicateArnKey,
+  /// api_gateway.tf references a lambda key that isn't in lambda.tf
+  UnknownLambdaKey,
+  /// A lambda has API permissions but isn't referenced in api_gateway.tf
+  LambdaNotInApiGateway,
+  /// A lambda is referenced in api_gateway.tf but has no API permissions
+  LambdaNotInPermissions,
+  /// A lambda isn't used anywhere
+  UnusedLambda,
+  /// A lambda used by a step function also has API gateway permissions
+  StepFunctionApiPermission,
+  /// A lambda used by a step function has no permission for Step Functions to invoke it
+  MissingStepFunctionPermission,
+  /// A lambda's timeout is missing or outside the configured bounds
+  LambdaTimeout,
+  /// A lambda's memory size is missing or outside the configured bounds
+  LambdaMemorySize,
+  /// A lambda's ARN placeholder doesn't follow the `<lambda><suffix>` naming convention
+  ArnPlaceholderName,
+  /// A lambda with API permissions isn't allowed `lambda:InvokeFunction` in the IAM policy
+  MissingInvokePermission,
+  /// The IAM policy allows `lambda:InvokeFunction` on every lambda
+  BroadInvokePermission,
+  /// A route in Terraform isn't defined in the OpenAPI documents
+  PathNotInOpenApi,
+  /// A method in Terraform isn't defined for the route in the OpenAPI documents
+  MethodNotInOpenApi,
+  /// A route granted in lambda_permissions.tf isn't documented in the OpenAPI documents
+  UndocumentedRoute,
+  /// A route is granted for any method in lambda_permissions.tf but the OpenAPI documents only
+  /// define some methods
+  WildcardMethodPermission,
+  /// More than one lambda has an API permission for the same route and method
+  ConflictingRoute,
+  /// A route is a Step Function or SQS integration in OpenAPI but a lambda permission in Terraform
+  IntegrationTypeMismatch,
+  /// A route is a Lambda integration in OpenAPI but its lambda is triggered by another service in
+  /// Terraform, e.g. EventBridge, without an API permission for the route
+  TriggerTypeMismatch,
+  /// A route is in the OpenAPI document of one type of API, e.g. an HTTP API, but its lambda
+  /// permission is only for the other type, e.g. a REST API
+  GatewayTypeMismatch,
+  /// A deprecated operation still has a lambda permission
+  DeprecatedRoute,
+  /// A route in the OpenAPI documents isn't defined in Terraform
+  PathNotInTerraform,
+  /// A route only matches a route on the other side when ignoring the casing
+  RouteCaseMismatch,
+  /// A method in the OpenAPI documents isn't defined for the route in Terraform
+  MethodNotInTerraform,
+  /// The integration URI doesn't contain the lambda's ARN placeholder
+  ArnMismatch,
+  /// The integration URI is for a step function instead of a lambda
+  StepFunctionUri,
+  /// The function named in a concrete integration URI isn't the lambda with the route's permission
+  FunctionNameMismatch,
+  /// A Lambda integration isn't an `aws_proxy` integration
+  LambdaIntegrationType,
+  /// The integration extension has no `uri`
+  MissingIntegrationUri,
+  /// The operation has no `x-amazon-apigateway-integration` extension
+  MissingIntegration,
+}
+
+impl FindingCode {
+  /// The part of the validation that reports the code
+  pub fn section(&self) -> Section {
+    match self {
+      FindingCode::DuplicateTag
+      | FindingCode::IntegrationPassthrough
+      | FindingCode::IntegrationRequestParameter
+      | FindingCode::UndefinedTag
+      | FindingCode::InfoVersion
+      | FindingCode::ConflictingInfoVersion
+      | FindingCode::ConflictingInfoTitle
+      | FindingCode::ConflictingOpenApiVersion
+      | FindingCode::MergedPathCollision
+      | FindingCode::InvalidDocument
+      | FindingCode::UnparsableDocument
+      | FindingCode::UnreadableDocument
+      | FindingCode::CyclicRef
+      | FindingCode::UnresolvedRef
+      | FindingCode::UndeclaredPathParameter
+      | FindingCode::OrphanPathParameter
+      | FindingCode::PostNoBody
+      | FindingCode::OptionalRequestBody
+      | FindingCode::OptionsMethod
+      | FindingCode::MissingCorsOptions
+      | FindingCode::UndefinedSecurityScheme
+      | FindingCode::MissingSecurity
+      | FindingCode::NonJsonProxyResponse
+      | FindingCode::UntaggedOperation
+      | FindingCode::UnsupportedMethod
+      | FindingCode::ConflictingOperation
+      | FindingCode::DuplicateRoute
+      | FindingCode::ProxyShadowsPath
+      | FindingCode::LambdaIntegrationType => Section::OpenApi,
+      FindingCode::UnresolvedVariable
+      | FindingCode::DuplicateLambdaKey
+      | FindingCode::DuplicateHandler
+      | FindingCode::MissingHandler
+      | FindingCode::UndefinedPermissionLambda
+      | FindingCode::DuplicatePermissionKey
+      | FindingCode::UnknownForEachKey
+      | FindingCode::UnknownPrincipal
+      | FindingCode::DuplicateArnKey
+      | FindingCode::UnknownLambdaKey
+      | FindingCode::LambdaNotInApiGateway
+      | FindingCode::LambdaNotInPermissions
+      | FindingCode::UnusedLambda
+      | FindingCode::StepFunctionApiPermission
+      | FindingCode::MissingStepFunctionPermission
+      | FindingCode::LambdaTimeout
+      | FindingCode::LambdaMemorySize
+      | FindingCode::ArnPlaceholderName
+      | FindingCode::MissingInvokePermission
+      | FindingCode::BroadInvokePermission => Section::Terraform,
+      FindingCode::PathNotInOpenApi
+      | FindingCode::MethodNotInOpenApi
+      | FindingCode::UndocumentedRoute
+      | FindingCode::WildcardMethodPermission
+      | FindingCode::ConflictingRoute
+      | FindingCode::IntegrationTypeMismatch
+      | FindingCode::TriggerTypeMismatch
+      | FindingCode::GatewayTypeMismatch
+      | FindingCode::DeprecatedRoute
+      | FindingCode::PathNotInTerraform
+      | FindingCode::RouteCaseMismatch
+      | FindingCode::MethodNotInTerraform
+      | FindingCode::ArnMismatch
+      | FindingCode::StepFunctionUri
+      | FindingCode::FunctionNameMismatch
+      | FindingCode::MissingIntegrationUri
+      | FindingCode::MissingIntegration => Section::CrossValidation,
+    }
+  }
+}
+
+impl Display for FindingCode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FindingCode::DuplicateTag => write!(f, "DUPLICATE_TAG"),
+      FindingCode::IntegrationPassthrough => write!(f, "INTEGRATION_PASSTHROUGH"),
+      FindingCode::IntegrationRequestParameter => write!(f, "INTEGRATION_REQUEST_PARAMETER"),
+      FindingCode::UndefinedTag => write!(f, "UNDEFINED_TAG"),
+      FindingCode::InfoVersion => write!(f, "INFO_VERSION"),
+      FindingCode::ConflictingInfoVersion => write!(f, "CONFLICTING_INFO_VERSION"),
+      FindingCode::ConflictingInfoTitle => write!(f, "CONFLICTING_INFO_TITLE"),
+      FindingCode::ConflictingOpenApiVersion => write!(f, "CONFLICTING_OPEN_API_VERSION"),
+      FindingCode::MergedPathCollision => write!(f, "MERGED_PATH_COLLISION"),
+      FindingCode::InvalidDocument => write!(f, "INVALID_DOCUMENT"),
+      FindingCode::UnparsableDocument => write!(f, "UNPARSABLE_DOCUMENT"),
+      FindingCode::UnreadableDocument => write!(f, "UNREADABLE_DOCUMENT"),
+      FindingCode::CyclicRef => write!(f, "CYCLIC_REF"),
+      FindingCode::UnresolvedRef => write!(f, "UNRESOLVED_REF"),
+      FindingCode::UndeclaredPathParameter => write!(f, "UNDECLARED_PATH_PARAMETER"),
+      FindingCode::OrphanPathParameter => write!(f, "ORPHAN_PATH_PARAMETER"),
+      FindingCode::PostNoBody => write!(f, "POST_NO_BODY"),
+      FindingCode::OptionalRequestBody => write!(f, "OPTIONAL_REQUEST_BODY"),
+      FindingCode::OptionsMethod => write!(f, "OPTIONS_METHOD"),
+      FindingCode::MissingCorsOptions => write!(f, "MISSING_CORS_OPTIONS"),
+      FindingCode::UndefinedSecurityScheme => write!(f, "UNDEFINED_SECURITY_SCHEME"),
+      FindingCode::MissingSecurity => write!(f, "MISSING_SECURITY"),
+      FindingCode::NonJsonProxyResponse => write!(f, "NON_JSON_PROXY_RESPONSE"),
+      FindingCode::UntaggedOperation => write!(f, "UNTAGGED_OPERATION"),
+      FindingCode::UnsupportedMethod => write!(f, "UNSUPPORTED_METHOD"),
+      FindingCode::ConflictingOperation => write!(f, "CONFLICTING_OPERATION"),
+      FindingCode::DuplicateRoute => write!(f, "DUPLICATE_ROUTE"),
+      FindingCode::ProxyShadowsPath => write!(f, "PROXY_SHADOWS_PATH"),
+      FindingCode::UnresolvedVariable => write!(f, "UNRESOLVED_VARIABLE"),
+      FindingCode::DuplicateLambdaKey => write!(f, "DUPLICATE_LAMBDA_KEY"),
+      FindingCode::DuplicateHandler => write!(f, "DUPLICATE_HANDLER"),
+      FindingCode::MissingHandler => write!(f, "MISSING_HANDLER"),
+      FindingCode::UndefinedPermissionLambda => write!(f, "UNDEFINED_PERMISSION_LAMBDA"),
+      FindingCode::DuplicatePermissionKey => write!(f, "DUPLICATE_PERMISSION_KEY"),
+      FindingCode::UnknownPrincipal => write!(f, "UNKNOWN_PRINCIPAL"),
+      FindingCode::UnknownForEachKey => write!(f, "UNKNOWN_FOR_EACH_KEY"),
+      FindingCode::DuplicateArnKey => write!(f, "DUPLICATE_ARN_KEY"),
+      FindingCode::UnknownLambdaKey => write!(f, "UNKNOWN_LAMBDA_KEY"),
+      FindingCode::LambdaNotInApiGateway => write!(f, "LAMBDA_NOT_IN_API_GATEWAY"),
+      FindingCode::LambdaNotInPermissions => write!(f, "LAMBDA_NOT_IN_PERMISSIONS"),
+      FindingCode::UnusedLambda => write!(f, "UNUSED_LAMBDA"),
+      FindingCode::StepFunctionApiPermission => write!(f, "STEP_FUNCTION_API_PERMISSION"),
+      FindingCode::MissingStepFunctionPermission => {
+        write!(f, "MISSING_STEP_FUNCTION_PERMISSION")
+      }
+      FindingCode::LambdaTimeout => write!(f, "LAMBDA_TIMEOUT"),
+      FindingCode::LambdaMemorySize => write!(f, "LAMBDA_MEMORY_SIZE"),
+      FindingCode::ArnPlaceholderName => write!(f, "ARN_PLACEHOLDER_NAME"),
+      FindingCode::MissingInvokePermission => write!(f, "MISSING_INVOKE_PERMISSION"),
+      FindingCode::BroadInvokePermission => write!(f, "BROAD_INVOKE_PERMISSION"),
+      FindingCode::PathNotInOpenApi => write!(f, "PATH_NOT_IN_OPEN_API"),
+      FindingCode::MethodNotInOpenApi => write!(f, "METHOD_NOT_IN_OPEN_API"),
+      FindingCode::UndocumentedRoute => write!(f, "UNDOCUMENTED_ROUTE"),
+      FindingCode::WildcardMethodPermission => write!(f, "WILDCARD_METHOD_PERMISSION"),
+      FindingCode::ConflictingRoute => write!(f, "CONFLICTING_ROUTE"),
+      FindingCode::IntegrationTypeMismatch => write!(f, "INTEGRATION_TYPE_MISMATCH"),
+      FindingCode::TriggerTypeMismatch => write!(f, "TRIGGER_TYPE_MISMATCH"),
+      FindingCode::GatewayTypeMismatch => write!(f, "GATEWAY_TYPE_MISMATCH"),
+      FindingCode::DeprecatedRoute => write!(f, "DEPRECATED_ROUTE"),
+      FindingCode::PathNotInTerraform => write!(f, "PATH_NOT_IN_TERRAFORM"),
+      FindingCode::RouteCaseMismatch => write!(f, "ROUTE_CASE_MISMATCH"),
+      FindingCode::MethodNotInTerraform => write!(f, "METHOD_NOT_IN_TERRAFORM"),
+      FindingCode::ArnMismatch => write!(f, "ARN_MISMATCH"),
+      FindingCode::StepFunctionUri => write!(f, "STEP_FUNCTION_URI"),
+      FindingCode::FunctionNameMismatch => write!(f, "FUNCTION_NAME_MISMATCH"),
+      FindingCode::LambdaIntegrationType => write!(f, "LAMBDA_INTEGRATION_TYPE"),
+      FindingCode::MissingIntegrationUri => write!(f, "MISSING_INTEGRATION_URI"),
+      FindingCode::MissingIntegration => write!(f, "MISSING_INTEGRATION"),
+    }
+  }
+}
+
+/// A problem found while validating the OpenAPI and Terraform files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+  /// The kind of problem
+  pub code: FindingCode,
+  /// How serious the problem is
+  pub severity: Severity,
+  /// Human readable description of the problem
+  pub message: String,
+  /// The file the problem is in, if known
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub file: Option<PathBuf>,
+  /// The line of the file the problem is on, starting at 1, if known
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub line: Option<usize>,
+  /// The route the problem is about, if it's about one
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub route: Option<String>,
+  /// The method of the route the problem is about, if it's about one
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub method: Option<HttpMethod>,
+}
+
+impl Finding {
+  /// Creates a finding that fails validation
+  pub fn error(code: FindingCode, message: impl Into<String>) -> Self {
+    Finding {
+      code,
+      severity: Severity::Error,
+      message: message.into(),
+      file: None,
+      line: None,
+      route: None,
+      method: None,
+    }
+  }
+
+  /// Creates a finding that doesn't fail validation
+  pub fn warning(code: FindingCode, message: impl Into<String>) -> Self {
+    Finding {
+      code,
+      severity: Severity::Warning,
+      message: message.into(),
+      file: None,
+      line: None,
+      route: None,
+      method: None,
+    }
+  }
+
+  /// Sets the file the problem is in
+  pub fn in_file(mut self, file: &Path) -> Self {
+    self.file = Some(file.to_path_buf());
+    self
+  }
+
+  /// Sets the line of the file the problem is on, starting at 1
+  pub fn at_line(mut self, line: usize) -> Self {
+    self.line = Some(line);
+    self
+  }
+
+  /// Sets the route the problem is about
+  pub fn for_route(mut self, route: &str) -> Self {
+    self.route = Some(route.to_string());
+    self
+  }
+
+  /// Sets the route and method the problem is about
+  pub fn for_operation(mut self, method: &HttpMethod, route: &str) -> Self {
+    self.method = Some(method.clone());
+    self.for_route(route)
+  }
+
+  /// Whether the findings are the same problem, ignoring the severity, which the config can change
+  ///
+  /// The route and method are part of the message, so the code, file and message identify it
+  pub fn is_same(&self, other: &Finding) -> bool {
+    self.code == other.code && self.file == other.file && self.message == other.message
+  }
+
+  /// Formats the finding as a GitHub Actions workflow command so it is shown as an annotation
+  pub fn to_github_annotation(&self) -> String {
+    let mut properties = Vec::new();
+    if let Some(file) = &self.file {
+      properties.push(format!(
+        "file={}",
+        escape_property(&file.display().to_string())
+      ));
+      if let Some(line) = self.line {
+        properties.push(format!("line={}", line));
+      }
+    }
+    properties.push(format!("title={}", escape_property(&self.code.to_string())));
+    format!(
+      "::{} {}::{}",
+      self.severity,
+      properties.join(","),
+      escape_data(&self.message)
+    )
+  }
+
+  /// The top-level segment of the route the finding is about, e.g. `/users` for `/users/{id}`
+  pub fn route_prefix(&self) -> Option<String> {
+    let route = self.route.as_deref()?;
+    let segment = route.split('/').find(|segment| !segment.is_empty());
+    Some(format!("/{}", segment.unwrap_or_default()))
+  }
+
+  /// Logs the finding at the level matching its severity, marked with the glyphs, and the
+  /// suggestion for fixing it below it when `hints` is set
+  pub fn log(&self, glyphs: Glyphs, hints: bool) {
+    match self.severity {
+      Severity::Warning => warn!("{}", glyphs.warning(&self.message)),
+      Severity::Error => error!("{}", glyphs.fail(&self.message)),
+    }
+    if let Some(suggestion) = suggestion_for(self).filter(|_| hints) {
+      info!("  hint: {}", suggestion);
+    }
+  }
+}
+
+/// Groups the findings by the prefix of their route, sorted by the prefix, with the findings that
+/// aren't about a route in a last group without a prefix
+pub fn group_by_prefix<'a>(
+  findings: impl IntoIterator<Item = &'a Finding>,
+) -> Vec<(Option<String>, Vec<&'a Finding>)> {
+  let mut groups: BTreeMap<String, Vec<&Finding>> = BTreeMap::new();
+  let mut other = Vec::new();
+  for finding in findings {
+    match finding.route_prefix() {
+      Some(prefix) => groups.entry(prefix).or_default().push(finding),
+      None => other.push(finding),
+    }
+  }
+  let mut groups: Vec<_> = groups
+    .into_iter()
+    .map(|(prefix, findings)| (Some(prefix), findings))
+    .collect();
+  if !other.is_empty() {
+    groups.push((None, other));
+  }
+  groups
+}
+
+/// Escapes the message of a workflow command
+fn escape_data(value: &str) -> String {
+  value
+    .replace('%', "%25")
+    .replace('\r', "%0D")
+    .replace('\n', "%0A")
+}
+
+/// Escapes a property of a workflow command
+fn escape_property(value: &str) -> String {
+  escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Collects the findings produced while validating
+#[derive(Default)]
+pub struct Findings<'a> {
+  /// The findings that were reported, unless they are passed to `sink`
+  findings: Vec<Finding>,
+  /// Called with each finding as it is reported instead of logging and keeping it
+  sink: Option<&'a mut dyn FnMut(Finding)>,
+  /// Number of errors that were reported
+  errors: usize,
+  /// Number of warnings that were reported
+  warnings: usize,
+  /// Stop reporting findings once this many errors have been reported
+  max_errors: Option<usize>,
+  /// Number of findings that weren't reported because `max_errors` was reached
+  suppressed: usize,
+  /// Severities that replace the default severity of the finding codes
+  severities: BTreeMap<FindingCode, SeverityOverride>,
+  /// Number of warnings that were reported as errors because of `severities`
+  raised: usize,
+  /// Number of errors that fail validation, including the ones over `max_errors`, but not the
+  /// ones hidden or downgraded by `severities`, `route_filter` or `suppressions`
+  failures: usize,
+  /// Number of findings that were reported for each code
+  counts: BTreeMap<FindingCode, usize>,
+  /// How the findings are marked when they are logged
+  glyphs: Glyphs,
+  /// Keep the findings without logging them as they are reported
+  silent: bool,
+  /// Flush the log after each finding and report the findings as soon as they are found rather
+  /// than in a fixed order
+  stream: bool,
+  /// Log the suggestion for fixing each finding below it
+  hints: bool,
+  /// Only report the findings about the routes matching the glob, and the ones without a route
+  route_filter: Option<String>,
+  /// Number of findings that weren't reported because their route doesn't match `route_filter`
+  filtered: usize,
+  /// The `# sv:ignore` comments in the files, the findings they match aren't reported
+  suppressions: Vec<Suppression>,
+  /// Number of findings that weren't reported because of `suppressions`
+  ignored: usize,
+  /// Stop validating at the first error
+  fail_fast: bool,
+}
+
+impl std::fmt::Debug for Findings<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Findings")
+      .field("findings", &self.findings)
+      .field("errors", &self.errors)
+      .field("warnings", &self.warnings)
+      .field("max_errors", &self.max_errors)
+      .field("suppressed", &self.suppressed)
+      .field("severities", &self.severities)
+      .field("raised", &self.raised)
+      .field("failures", &self.failures)
+      .field("counts", &self.counts)
+      .field("glyphs", &self.glyphs)
+      .field("silent", &self.silent)
+      .field("stream", &self.stream)
+      .field("hints", &self.hints)
+      .field("route_filter", &self.route_filter)
+      .field("filtered", &self.filtered)
+      .field("suppressions", &self.suppressions)
+      .field("ignored", &self.ignored)
+      .field("fail_fast", &self.fail_fast)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<'a> Findings<'a> {
+  /// Creates a collector that stops reporting after `max_errors` errors, if set
+  pub fn new(max_errors: Option<usize>) -> Self {
+    Findings {
+      max_errors,
+      ..Default::default()
+    }
+  }
+
+  /// Creates a collector that passes each finding to `sink` as it is reported, so the findings
+  /// can be streamed rather than kept until validation is done
+  pub fn with_sink(sink: &'a mut dyn FnMut(Finding)) -> Self {
+    Findings {
+      sink: Some(sink),
+      ..Default::default()
+    }
+  }
+
+  /// Sets the severities that replace the default severity of the finding codes
+  pub fn set_severities(&mut self, severities: BTreeMap<FindingCode, SeverityOverride>) {
+    self.severities = severities;
+  }
+
+  /// Sets how the findings are marked when they are logged
+  pub fn set_glyphs(&mut self, glyphs: Glyphs) {
+    self.glyphs = glyphs;
+  }
+
+  /// How the findings are marked when they are logged
+  pub fn glyphs(&self) -> Glyphs {
+    self.glyphs
+  }
+
+  /// Logs the suggestion for fixing each finding below it, for the findings that have one
+  pub fn set_hints(&mut self, hints: bool) {
+    self.hints = hints;
+  }
+
+  /// Whether the suggestion for fixing each finding is logged below it
+  pub fn hints(&self) -> bool {
+    self.hints
+  }
+
+  /// Only reports the findings about the routes matching the glob, and the ones without a route
+  pub fn set_route_filter(&mut self, route_filter: Option<String>) {
+    self.route_filter = route_filter;
+  }
+
+  /// Sets the `# sv:ignore` comments, the findings they match aren't reported
+  pub fn set_suppressions(&mut self, suppressions: Vec<Suppression>) {
+    self.suppressions = suppressions;
+  }
+
+  /// Keeps the findings without logging them as they are reported, e.g. to only log some of them
+  /// once validation is done
+  pub fn set_silent(&mut self, silent: bool) {
+    self.silent = silent;
+  }
+
+  /// Flushes the log after each finding, and has the validators report the findings as soon as
+  /// they are found, so they appear progressively during a long run
+  pub fn set_stream(&mut self, stream: bool) {
+    self.stream = stream;
+  }
+
+  /// Whether the findings are reported as soon as they are found, in the order they are found
+  pub fn streaming(&self) -> bool {
+    self.stream
+  }
+
+  /// Logs and records the finding, or passes it to the sink, unless the error limit has been
+  /// reached, it's turned off, its route is filtered out or a `# sv:ignore` comment matches it
+  pub fn push(&mut self, mut finding: Finding) {
+    if let (Some(glob), Some(route)) = (&self.route_filter, finding.route.as_deref()) {
+      if !matches_route_glob(glob, route) {
+        self.filtered += 1;
+        return;
+      }
+    }
+    if self
+      .suppressions
+      .iter()
+      .any(|suppression| suppression.matches(&finding))
+    {
+      self.ignored += 1;
+      return;
+    }
+    let default_severity = finding.severity;
+    match self.severities.get(&finding.code) {
+      Some(SeverityOverride::Off) => return,
+      Some(SeverityOverride::Error) => {
+        if finding.severity == Severity::Warning {
+          self.raised += 1;
+        }
+        finding.severity = Severity::Error;
+      }
+      Some(SeverityOverride::Warning) => finding.severity = Severity::Warning,
+      None => {}
+    }
+    // Raised warnings fail validation once every check has run, see `raised`
+    if finding.severity == Severity::Error && default_severity == Severity::Error {
+      self.failures += 1;
+    }
+    if self.is_limit_reached() {
+      self.suppressed += 1;
+      return;
+    }
+    match finding.severity {
+      Severity::Error => self.errors += 1,
+      Severity::Warning => self.warnings += 1,
+    }
+    *self.counts.entry(finding.code).or_default() += 1;
+    match &mut self.sink {
+      Some(sink) => sink(finding),
+      None => {
+        if !self.silent {
+          finding.log(self.glyphs, self.hints);
+          if self.stream {
+            log::logger().flush();
+          }
+        }
+        self.findings.push(finding);
+      }
+    }
+  }
+
+  /// Stops validating at the first error, only the first error is reported
+  pub fn set_fail_fast(&mut self, fail_fast: bool) {
+    self.fail_fast = fail_fast;
+    if fail_fast {
+      self.max_errors = Some(1);
+    }
+  }
+
+  /// Whether the validators should return early because an error was reported and `fail_fast` is
+  /// set
+  pub fn should_stop(&self) -> bool {
+    self.fail_fast && self.errors > 0
+  }
+
+  /// Whether the maximum number of errors has been reported
+  pub fn is_limit_reached(&self) -> bool {
+    self
+      .max_errors
+      .is_some_and(|max_errors| self.error_count() >= max_errors)
+  }
+
+  /// Number of errors that were reported
+  pub fn error_count(&self) -> usize {
+    self.errors
+  }
+
+  /// Number of warnings that were reported
+  pub fn warning_count(&self) -> usize {
+    self.warnings
+  }
+
+  /// Number of warnings that were reported as errors because of the configured severities
+  pub fn raised(&self) -> usize {
+    self.raised
+  }
+
+  /// Number of errors that fail validation, the validators compare it before and after their
+  /// checks so the errors that are turned off, downgraded, filtered out or ignored don't fail them
+  pub fn failures(&self) -> usize {
+    self.failures
+  }
+
+  /// Number of findings that weren't reported because their route doesn't match the route filter
+  pub fn filtered(&self) -> usize {
+    self.filtered
+  }
+
+  /// Number of findings that weren't reported because of a `# sv:ignore` comment
+  pub fn ignored(&self) -> usize {
+    self.ignored
+  }
+
+  /// Number of findings that weren't reported because the error limit was reached
+  pub fn suppressed(&self) -> usize {
+    self.suppressed
+  }
+
+  /// Number of findings with the code that were reported
+  pub fn count(&self, code: FindingCode) -> usize {
+    self.counts.get(&code).copied().unwrap_or_default()
+  }
+
+  /// The reported findings, which is empty when they are passed to a sink
+  pub fn iter(&self) -> std::slice::Iter<'_, Finding> {
+    self.findings.iter()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_findings_route_filter() {
+    let mut findings = Findings::default();
+    findings.set_route_filter(Some("/users/**".to_string()));
+    findings.push(
+      Finding::error(
+        FindingCode::PathNotInTerraform,
+        "The path /users/{id} is not defined in Terraform",
+      )
+      .for_route("/users/{id}"),
+    );
+    findings.push(
+      Finding::error(
+        FindingCode::PathNotInTerraform,
+        "The path /orders is not defined in Terraform",
+      )
+      .for_route("/orders"),
+    );
+    findings.push(Finding::warning(
+      FindingCode::InfoVersion,
+      "No info.version",
+    ));
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The path /users/{id} is not defined in Terraform",
+        "No info.version"
+      ]
+    );
+    assert_eq!(findings.filtered(), 1);
+    assert_eq!(findings.error_count(), 1);
+  }
+
+  #[test]
+  fn test_findings_max_errors() {
+    let mut findings = Findings::new(Some(2));
+    findings.push(Finding::warning(FindingCode::PostNoBody, "warning"));
+    findings.push(Finding::error(FindingCode::DuplicateTag, "first"));
+    findings.push(Finding::error(FindingCode::DuplicateTag, "second"));
+    findings.push(Finding::error(FindingCode::DuplicateTag, "third"));
+    findings.push(Finding::warning(FindingCode::PostNoBody, "another warning"));
+    assert!(findings.is_limit_reached());
+    assert_eq!(findings.error_count(), 2);
+    assert_eq!(findings.warning_count(), 1);
+    assert_eq!(findings.suppressed(), 2);
+    assert_eq!(findings.count(FindingCode::DuplicateTag), 2);
+    assert_eq!(findings.count(FindingCode::PostNoBody), 1);
+  }
+
+  #[test]
+  fn test_findings_fail_fast() {
+    let mut findings = Findings::default();
+    findings.set_fail_fast(true);
+    findings.push(Finding::warning(FindingCode::PostNoBody, "warning"));
+    assert!(!findings.should_stop());
+    findings.push(Finding::error(FindingCode::DuplicateTag, "first"));
+    findings.push(Finding::error(FindingCode::DuplicateTag, "second"));
+    assert!(findings.should_stop());
+    assert_eq!(findings.error_count(), 1);
+    assert_eq!(findings.suppressed(), 1);
+  }
+
+  #[test]
+  fn test_to_github_annotation() {
+    let finding = Finding::error(
+      FindingCode::DuplicateLambdaKey,
+      "Key is duplicated: a\n100%",
+    )
+    .in_file(Path::new("terraform/lambda.tf"));
+    assert_eq!(
+      finding.to_github_annotation(),
+      "::error file=terraform/lambda.tf,title=DUPLICATE_LAMBDA_KEY::Key is duplicated: a%0A100%25"
+    );
+    let finding = Finding::error(
+      FindingCode::PathNotInTerraform,
+      "The path /users is not defined in Terraform",
+    )
+    .in_file(Path::new("open_api/users.yaml"))
+    .at_line(4);
+    assert_eq!(
+      finding.to_github_annotation(),
+      "::error file=open_api/users.yaml,line=4,title=PATH_NOT_IN_TERRAFORM::The path /users is not defined in Terraform"
+    );
+    let finding = Finding::warning(FindingCode::UndocumentedRoute, "GET /a, GET /b");
+    assert_eq!(
+      finding.to_github_annotation(),
+      "::warning title=UNDOCUMENTED_ROUTE::GET /a, GET /b"
+    );
+  }
+
+  #[test]
+  fn test_findings_severities() {
+    let mut findings = Findings::default();
+    findings.set_severities(BTreeMap::from([
+      (FindingCode::PostNoBody, SeverityOverride::Error),
+      (FindingCode::OptionsMethod, SeverityOverride::Off),
+      (FindingCode::DuplicateTag, SeverityOverride::Warning),
+    ]));
+    findings.push(Finding::warning(FindingCode::PostNoBody, "no body"));
+    findings.push(Finding::warning(FindingCode::OptionsMethod, "options"));
+    findings.push(Finding::error(FindingCode::DuplicateTag, "tag"));
+    assert_eq!(findings.error_count(), 1);
+    assert_eq!(findings.warning_count(), 1);
+    assert_eq!(findings.raised(), 1);
+    assert_eq!(findings.iter().count(), 2);
+    assert_eq!(findings.failures(), 0);
+    findings.push(Finding::error(FindingCode::DuplicateLambdaKey, "key"));
+    assert_eq!(findings.failures(), 1);
+  }
+
+  #[test]
+  fn test_findings_sink() {
+    let mut streamed = Vec::new();
+    let mut sink = |finding: Finding| streamed.push(finding.code);
+    let mut findings = Findings::with_sink(&mut sink);
+    findings.push(Finding::warning(FindingCode::PostNoBody, "warning"));
+    findings.push(Finding::error(FindingCode::DuplicateTag, "error"));
+    assert_eq!(findings.error_count(), 1);
+    assert_eq!(findings.warning_count(), 1);
+    assert_eq!(findings.iter().count(), 0);
+    assert_eq!(
+      streamed,
+      vec![FindingCode::PostNoBody, FindingCode::DuplicateTag]
+    );
+  }
+
+  #[test]
+  fn test_findings_unlimited() {
+    let mut findings = Findings::default();
+    for _ in 0..100 {
+      findings.push(Finding::error(FindingCode::DuplicateTag, "error"));
+    }
+    assert!(!findings.is_limit_reached());
+    assert_eq!(findings.error_count(), 100);
+    assert_eq!(findings.suppressed(), 0);
+  }
+
+  #[test]
+  fn test_group_by_prefix() {
+    let findings = [
+      Finding::error(
+        FindingCode::PathNotInTerraform,
+        "The path /users/{id} is not defined in Terraform",
+      )
+      .for_route("/users/{id}"),
+      Finding::warning(
+        FindingCode::PostNoBody,
+        "The POST method for /orders does not have a request body or parameters (queries)",
+      )
+      .for_operation(&HttpMethod::Post, "/orders"),
+      Finding::error(FindingCode::DuplicateLambdaKey, "Key is duplicated: a"),
+      Finding::warning(
+        FindingCode::OptionalRequestBody,
+        "The request body for the PUT method for /users is not marked as required",
+      )
+      .for_operation(&HttpMethod::Put, "/users"),
+    ];
+    assert_eq!(findings[0].route_prefix(), Some("/users".to_string()));
+    assert_eq!(findings[2].route_prefix(), None);
+    let groups = group_by_prefix(&findings);
+    let prefixes: Vec<_> = groups.iter().map(|(prefix, _)| prefix.as_deref()).collect();
+    assert_eq!(prefixes, vec![Some("/orders"), Some("/users"), None]);
+    assert_eq!(groups[1].1, vec![&findings[0], &findings[3]]);
+    assert_eq!(groups[2].1, vec![&findings[2]]);
+  }
+}
@@ -0,0 +1,148 @@
+use crate::findings::{Finding, FindingCode};
+
+/// Suggests how to fix the finding, for the findings that have a common fix
+///
+/// The suggestion is skipped when the finding doesn't have the route or method it needs, the
+/// placeholder is read from the message
+pub fn suggestion_for(finding: &Finding) -> Option<String> {
+  let message = finding.message.as_str();
+  let route = finding.route.as_deref();
+  let method = finding.method.as_ref();
+  match finding.code {
+    FindingCode::ArnMismatch => {
+      let placeholder = message.split_once("placeholder '")?.1.split_once('\'')?.0;
+      Some(format!(
+        "Use the ARN placeholder in the uri: arn:aws:apigateway:${{region}}:lambda:path/2015-03-31/functions/${{{}}}/invocations",
+        placeholder
+      ))
+    }
+    FindingCode::PathNotInTerraform => Some(format!(
+      "Add an entry to lambdas_permissions with a source_arn ending in /<METHOD>{}",
+      route?
+    )),
+    FindingCode::MethodNotInTerraform => Some(format!(
+      "Add an entry to lambdas_permissions with a source_arn ending in /{}{}",
+      method?,
+      route?
+    )),
+    FindingCode::PathNotInOpenApi => Some(format!(
+      "Add the path {} to the OpenAPI documents, or remove it from lambdas_permissions",
+      route?
+    )),
+    FindingCode::MethodNotInOpenApi | FindingCode::UndocumentedRoute => Some(format!(
+      "Add the {} operation to the path {} in the OpenAPI documents, or remove it from lambdas_permissions",
+      method?,
+      route?
+    )),
+    FindingCode::WildcardMethodPermission => Some(format!(
+      "Replace the * in the source_arn with an entry for each method of the path {}",
+      route?
+    )),
+    FindingCode::RouteCaseMismatch => {
+      Some("Use the same casing for the path in OpenAPI and Terraform".to_string())
+    }
+    FindingCode::FunctionNameMismatch => Some(
+      "Point the uri at the function of the lambda with the permission, or move the permission to the lambda the uri names"
+        .to_string(),
+    ),
+    FindingCode::ConflictingRoute => {
+      Some("Keep the permission for the route on only one of the lambdas".to_string())
+    }
+    FindingCode::MissingIntegration => Some(
+      "Add an x-amazon-apigateway-integration with the type aws_proxy, the httpMethod POST and the lambda's ARN placeholder in the uri"
+        .to_string(),
+    ),
+    FindingCode::MissingIntegrationUri => Some(
+      "Set the uri to arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${<lambda>_arn}/invocations"
+        .to_string(),
+    ),
+    FindingCode::LambdaIntegrationType => {
+      Some("Set the type of the integration to aws_proxy".to_string())
+    }
+    FindingCode::TriggerTypeMismatch => Some(
+      "Add an apigateway.amazonaws.com permission for the route to the lambda in lambdas_permissions, or use the ARN placeholder of the lambda that handles the route"
+        .to_string(),
+    ),
+    FindingCode::GatewayTypeMismatch => Some(
+      "Use the execution ARN of the API the route is in for the source_arn, e.g. aws_apigatewayv2_api for an HTTP API or aws_api_gateway_rest_api for a REST API"
+        .to_string(),
+    ),
+    FindingCode::MissingInvokePermission => Some(
+      "Add the lambda's ARN to the resources of the lambda:InvokeFunction statement of the policy"
+        .to_string(),
+    ),
+    FindingCode::BroadInvokePermission => Some(
+      "Replace the * resource with the ARNs of the lambdas the API invokes".to_string(),
+    ),
+    FindingCode::ConflictingOperation => Some(
+      "Define the operation in only one of the documents, the merge combines their definitions"
+        .to_string(),
+    ),
+    FindingCode::ConflictingOpenApiVersion => Some(
+      "Declare the same openapi version, e.g. 3.0.3, in all the documents that are merged"
+        .to_string(),
+    ),
+    FindingCode::OrphanPathParameter => Some(
+      "Add the parameter to the route in braces, or change it to in: query or remove it"
+        .to_string(),
+    ),
+    FindingCode::NonJsonProxyResponse => Some(
+      "Declare application/json for the response, or set the Content-Type header in the lambda's response"
+        .to_string(),
+    ),
+    FindingCode::UndefinedTag => Some("Declare the tag in the tags of the document".to_string()),
+    FindingCode::UntaggedOperation => {
+      Some("Add the tags of the operation, e.g. tags: [users]".to_string())
+    }
+    FindingCode::UndefinedSecurityScheme => {
+      Some("Define the scheme in components.securitySchemes".to_string())
+    }
+    FindingCode::MissingCorsOptions => Some(
+      "Add an OPTIONS method with a mock integration that returns the CORS headers".to_string(),
+    ),
+    FindingCode::StepFunctionApiPermission => Some(
+      "Remove the API gateway permissions of the lambda, or use a separate lambda for the routes"
+        .to_string(),
+    ),
+    FindingCode::MissingStepFunctionPermission => Some(
+      "Add an entry to lambdas_permissions with the principal states.amazonaws.com".to_string(),
+    ),
+    FindingCode::DuplicateLambdaKey | FindingCode::DuplicatePermissionKey => {
+      Some("Remove or rename one of the keys, Terraform only keeps the last one".to_string())
+    }
+    FindingCode::MissingHandler => Some(
+      "Set the handler of the lambda in lambdas, e.g. handler = \"items.lambda_handler\""
+        .to_string(),
+    ),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::HttpMethod;
+
+  #[test]
+  fn test_suggestion_for() {
+    let finding = Finding::error(
+      FindingCode::ArnMismatch,
+      "The 'uri' doesn't contain the ARN placeholder 'users_arn' in the 'x-amazon-apigateway-integration' extension for GET /users for the lambda users",
+    );
+    assert_eq!(
+      suggestion_for(&finding).as_deref(),
+      Some("Use the ARN placeholder in the uri: arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${users_arn}/invocations")
+    );
+    let finding = Finding::error(
+      FindingCode::MethodNotInTerraform,
+      "The POST method is not defined for the path /users/{id} in Terraform, found: GET",
+    )
+    .for_operation(&HttpMethod::Post, "/users/{id}");
+    assert_eq!(
+      suggestion_for(&finding).as_deref(),
+      Some("Add an entry to lambdas_permissions with a source_arn ending in /POST/users/{id}")
+    );
+    let finding = Finding::warning(FindingCode::PostNoBody, "The POST /users has no body");
+    assert_eq!(suggestion_for(&finding), None);
+  }
+}
@@ -0,0 +1,289 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde_json::Value;
+use simplelog::info;
+
+use crate::{
+  error::SvError,
+  findings::{Finding, FindingCode},
+  terraform::Lambda,
+};
+
+/// The action API Gateway needs to invoke a lambda
+const INVOKE_ACTION: &str = "lambda:InvokeFunction";
+
+/// An `Allow` statement of an IAM policy, only the actions and resources are kept
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PolicyStatement {
+  /// The actions, e.g. `lambda:InvokeFunction`
+  pub actions: Vec<String>,
+  /// The resources, ARNs or Terraform references such as `${module.lambda["lambda-1"].lambda_arn}`
+  pub resources: Vec<String>,
+}
+
+/// Reads the `Allow` statements of the IAM policy in the file
+///
+/// A `.json` file is the policy document itself. Otherwise the file is read as Terraform and the
+/// statements are taken from the `policy` attributes, either `jsonencode(...)` or a JSON string,
+/// and from the `statement` blocks of `aws_iam_policy_document` data sources
+pub fn load_policy(path: &Path) -> anyhow::Result<Vec<PolicyStatement>> {
+  let contents = std::fs::read_to_string(path)
+    .map_err(|e| SvError::Io(format!("Failed to read {:?}: {}", path, e)))?;
+  if path
+    .extension()
+    .is_some_and(|extension| extension == "json")
+  {
+    let document: Value =
+      serde_json::from_str(&contents).map_err(|e| anyhow!("Failed to parse {:?}: {}", path, e))?;
+    return Ok(statements_from_json(&document));
+  }
+  let body = hcl::parse(&contents).map_err(|e| anyhow!("Failed to parse {:?}: {}", path, e))?;
+  let mut statements = Vec::new();
+  statements_from_body(&body, &mut statements);
+  Ok(statements)
+}
+
+/// Collects the statements of the `policy` attributes and `statement` blocks in the body and its
+/// nested blocks
+fn statements_from_body(body: &hcl::Body, statements: &mut Vec<PolicyStatement>) {
+  for attribute in body.attributes() {
+    if attribute.key.to_string() == "policy" {
+      if let Some(document) = policy_document(&attribute.expr) {
+        statements.extend(statements_from_json(&document));
+      }
+    }
+  }
+  for block in body.blocks() {
+    if block.identifier.to_string() == "statement" {
+      if let Some(statement) = statement_from_block(&block.body) {
+        statements.push(statement);
+      }
+    } else {
+      statements_from_body(&block.body, statements);
+    }
+  }
+}
+
+/// The policy document of a `policy` attribute, `None` when it isn't `jsonencode(...)` or a JSON
+/// string, e.g. a reference to a data source
+fn policy_document(expr: &hcl::Expression) -> Option<Value> {
+  match expr {
+    hcl::Expression::FuncCall(call) if call.name.to_string() == "jsonencode" => {
+      let value = hcl::Value::from(call.args.first()?.clone());
+      serde_json::to_value(value).ok()
+    }
+    hcl::Expression::TemplateExpr(template) => serde_json::from_str(&template.to_string()).ok(),
+    hcl::Expression::String(value) => serde_json::from_str(value).ok(),
+    _ => None,
+  }
+}
+
+/// The statement of a `statement` block, `None` for a `Deny` statement
+fn statement_from_block(body: &hcl::Body) -> Option<PolicyStatement> {
+  let attribute = |key: &str| {
+    body
+      .attributes()
+      .find(|attribute| attribute.key.to_string() == key)
+      .and_then(|attribute| serde_json::to_value(hcl::Value::from(attribute.expr.clone())).ok())
+  };
+  if let Some(effect) = attribute("effect") {
+    if effect.as_str() != Some("Allow") {
+      return None;
+    }
+  }
+  Some(PolicyStatement {
+    actions: strings(attribute("actions").as_ref()),
+    resources: strings(attribute("resources").as_ref()),
+  })
+}
+
+/// The `Allow` statements of a policy document, the `Statement` can be a single statement or a
+/// list of them
+fn statements_from_json(document: &Value) -> Vec<PolicyStatement> {
+  let statements = match &document["Statement"] {
+    Value::Array(statements) => statements.iter().collect(),
+    statement @ Value::Object(_) => vec![statement],
+    _ => Vec::new(),
+  };
+  statements
+    .into_iter()
+    .filter(|statement| statement["Effect"].as_str().unwrap_or("Allow") == "Allow")
+    .map(|statement| PolicyStatement {
+      actions: strings(statement.get("Action")),
+      resources: strings(statement.get("Resource")),
+    })
+    .collect()
+}
+
+/// The strings of a value that is a string or a list of strings
+fn strings(value: Option<&Value>) -> Vec<String> {
+  match value {
+    Some(Value::String(value)) => vec![value.clone()],
+    Some(Value::Array(values)) => values
+      .iter()
+      .filter_map(|value| value.as_str().map(str::to_string))
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// Whether the action allows invoking a lambda, actions are case insensitive and can end with a
+/// wildcard, e.g. `lambda:*`
+fn allows_invoke(action: &str) -> bool {
+  let action = action.to_lowercase();
+  let invoke = INVOKE_ACTION.to_lowercase();
+  match action.strip_suffix('*') {
+    Some(prefix) => invoke.starts_with(prefix),
+    None => action == invoke,
+  }
+}
+
+/// Whether the resource matches every lambda, e.g. `*` or `arn:aws:lambda:*:*:function:*`
+fn is_broad(resource: &str) -> bool {
+  resource == "*" || resource.ends_with("function:*")
+}
+
+/// Whether the resource refers to the lambda, either as the `for_each` key of the lambda module,
+/// e.g. `module.lambda["lambda-1"].lambda_arn`, or as the function name in an ARN, which can be
+/// prefixed, e.g. `dev-lambda-1`, and qualified, e.g. `lambda-1:*`
+fn refers_to(resource: &str, key: &str) -> bool {
+  resource.contains(&format!("[\"{}\"]", key))
+    || resource
+      .split(':')
+      .any(|part| part == key || part.ends_with(&format!("-{}", key)))
+}
+
+/// Checks that the policy lets each lambda with API permissions be invoked, reporting the lambdas
+/// that aren't listed with `lambda:InvokeFunction` as errors and the statements that allow
+/// invoking every lambda as warnings
+pub fn check_invoke_policy(
+  lambdas: &[Lambda],
+  statements: &[PolicyStatement],
+  file: &Path,
+) -> Vec<Finding> {
+  info!("Checking the invoke permissions in {:?}", file);
+  let invoke: Vec<&PolicyStatement> = statements
+    .iter()
+    .filter(|statement| statement.actions.iter().any(|action| allows_invoke(action)))
+    .collect();
+  let mut findings = Vec::new();
+  let mut broad = false;
+  for resource in invoke
+    .iter()
+    .flat_map(|statement| &statement.resources)
+    .filter(|resource| is_broad(resource))
+  {
+    broad = true;
+    findings.push(
+      Finding::warning(
+        FindingCode::BroadInvokePermission,
+        format!(
+          "The policy allows {} on every lambda with the resource {}, list the ARNs of the API lambdas instead",
+          INVOKE_ACTION, resource
+        ),
+      )
+      .in_file(file),
+    );
+  }
+  if broad {
+    return findings;
+  }
+  for lambda in lambdas.iter().filter(|lambda| !lambda.apis.is_empty()) {
+    let listed = invoke
+      .iter()
+      .flat_map(|statement| &statement.resources)
+      .any(|resource| refers_to(resource, &lambda.key));
+    if !listed {
+      findings.push(
+        Finding::error(
+          FindingCode::MissingInvokePermission,
+          format!(
+            "The lambda {} has API permissions but the policy doesn't allow {} on it",
+            lambda.key, INVOKE_ACTION
+          ),
+        )
+        .in_file(file),
+      );
+    }
+  }
+  findings
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{terraform::APIPath, util::HttpMethod};
+
+  fn lambda(key: &str) -> Lambda {
+    Lambda {
+      key: key.to_string(),
+      apis: vec![APIPath {
+        method: HttpMethod::Get,
+        route: format!("/{}", key),
+        gateway: None,
+      }],
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_check_invoke_policy() {
+    let body = hcl::parse(
+      r#"
+resource "aws_iam_role_policy" "invoke" {
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [{
+      Effect   = "Allow"
+      Action   = "lambda:InvokeFunction"
+      Resource = [module.lambda["get-user"].lambda_arn]
+    }]
+  })
+}
+
+data "aws_iam_policy_document" "invoke" {
+  statement {
+    actions   = ["lambda:*"]
+    resources = ["arn:aws:lambda:eu-west-1:123456789012:function:dev-delete-user:*"]
+  }
+}
+"#,
+    )
+    .unwrap();
+    let mut statements = Vec::new();
+    statements_from_body(&body, &mut statements);
+    assert_eq!(statements.len(), 2);
+    let lambdas = vec![
+      lambda("get-user"),
+      lambda("delete-user"),
+      lambda("update-user"),
+      Lambda {
+        key: "publish".to_string(),
+        ..Default::default()
+      },
+    ];
+    let findings = check_invoke_policy(&lambdas, &statements, Path::new("iam.tf"));
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::MissingInvokePermission);
+    assert_eq!(
+      findings[0].message,
+      "The lambda update-user has API permissions but the policy doesn't allow lambda:InvokeFunction on it"
+    );
+  }
+
+  #[test]
+  fn test_check_invoke_policy_broad_resource() {
+    let document = serde_json::json!({
+      "Statement": {
+        "Effect": "Allow",
+        "Action": ["lambda:InvokeFunction"],
+        "Resource": "*"
+      }
+    });
+    let statements = statements_from_json(&document);
+    let findings = check_invoke_policy(&[lambda("get-user")], &statements, Path::new("iam.json"));
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::BroadInvokePermission);
+  }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+use crate::{
+  terraform::{Lambda, LambdaTriggerType},
+  util::format_table,
+};
+
+/// What a lambda is and what it's wired to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LambdaSummary {
+  /// Terraform lambda key
+  pub key: String,
+  /// The lambda handler
+  pub handler: String,
+  /// What triggers the lambda, `-` when nothing does
+  pub trigger: String,
+  /// It's referenced in API gateway or has API permissions
+  pub api: bool,
+  /// It's used by a step function
+  pub step_function: bool,
+}
+
+impl From<&Lambda> for LambdaSummary {
+  fn from(lambda: &Lambda) -> Self {
+    let api = lambda.arn_template_key.is_some() || !lambda.apis.is_empty();
+    // The trigger type defaults to API gateway, so it's only shown when there are routes
+    let trigger = if lambda.step_function {
+      LambdaTriggerType::StepFunction.to_string()
+    } else if lambda.lambda_type == LambdaTriggerType::ApiGateway && !api {
+      "-".to_string()
+    } else {
+      lambda.lambda_type.to_string()
+    };
+    LambdaSummary {
+      key: lambda.key.clone(),
+      handler: lambda.handler.clone(),
+      trigger,
+      api,
+      step_function: lambda.step_function,
+    }
+  }
+}
+
+/// Lists the lambdas in the order they are declared
+pub fn lambda_inventory(lambdas: &[Lambda]) -> Vec<LambdaSummary> {
+  lambdas.iter().map(LambdaSummary::from).collect()
+}
+
+/// Formats the lambdas as a table with `x` for what each lambda is wired to and `-` otherwise
+pub fn format_lambda_table(lambdas: &[LambdaSummary]) -> String {
+  let wired = |wired: bool| if wired { "x" } else { "-" }.to_string();
+  let rows: Vec<Vec<String>> = std::iter::once(
+    ["Key", "Handler", "Trigger", "API", "Step function"]
+      .map(String::from)
+      .to_vec(),
+  )
+  .chain(lambdas.iter().map(|lambda| {
+    vec![
+      lambda.key.clone(),
+      lambda.handler.clone(),
+      lambda.trigger.clone(),
+      wired(lambda.api),
+      wired(lambda.step_function),
+    ]
+  }))
+  .collect();
+  format_table(&rows)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{terraform::APIPath, util::HttpMethod};
+
+  #[test]
+  fn test_lambda_inventory() {
+    let lambdas = vec![
+      Lambda {
+        key: "get-items".to_string(),
+        handler: "get_items.lambda_handler".to_string(),
+        apis: vec![APIPath {
+          method: HttpMethod::Get,
+          route: "/v1/items".to_string(),
+          gateway: None,
+        }],
+        arn_template_key: Some("get_items_arn".to_string()),
+        ..Default::default()
+      },
+      Lambda {
+        key: "process".to_string(),
+        handler: "process.lambda_handler".to_string(),
+        step_function: true,
+        ..Default::default()
+      },
+      Lambda {
+        key: "nightly".to_string(),
+        handler: "nightly.lambda_handler".to_string(),
+        lambda_type: LambdaTriggerType::EventBridge,
+        ..Default::default()
+      },
+      Lambda {
+        key: "unused".to_string(),
+        handler: "unused.lambda_handler".to_string(),
+        ..Default::default()
+      },
+    ];
+    let inventory = lambda_inventory(&lambdas);
+    assert_eq!(
+      inventory[0],
+      LambdaSummary {
+        key: "get-items".to_string(),
+        handler: "get_items.lambda_handler".to_string(),
+        trigger: "API Gateway".to_string(),
+        api: true,
+        step_function: false,
+      }
+    );
+    assert_eq!(
+      format_lambda_table(&inventory),
+      [
+        "Key        Handler                   Trigger        API  Step function",
+        "get-items  get_items.lambda_handler  API Gateway    x    -",
+        "process    process.lambda_handler    Step Function  -    x",
+        "nightly    nightly.lambda_handler    EventBridge    -    -",
+        "unused     unused.lambda_handler     -              -    -",
+      ]
+      .join("\n")
+    );
+  }
+}
@@ -1,4 +1,23 @@
+pub mod cache;
+pub mod config;
+pub mod coverage;
 pub mod cross_validation;
+pub mod diff;
+pub mod doctor;
+pub mod error;
+pub mod findings;
+pub mod hints;
+pub mod iam;
+pub mod inventory;
+pub mod logger;
 pub mod open_api;
+pub mod pretty;
+pub mod profile;
+pub mod routes;
+pub mod sarif;
+pub mod suppressions;
 pub mod terraform;
+pub mod terraform_plan;
+pub mod trace;
 pub mod util;
+pub mod verify;
@@ -0,0 +1,136 @@
+use std::io::{IsTerminal, Write};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// How the findings and the summary are marked in the log output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Glyphs {
+  /// Plain text without any glyphs
+  #[default]
+  Ascii,
+  /// ✓, ✗ and ⚠ in front of the summary and the findings
+  Unicode,
+}
+
+impl Glyphs {
+  /// Uses the glyphs unless `ascii` is set, `NO_COLOR` is set or stdout isn't a terminal
+  pub fn detect(ascii: bool) -> Self {
+    if ascii || std::env::var_os("NO_COLOR").is_some() || !std::io::stdout().is_terminal() {
+      Glyphs::Ascii
+    } else {
+      Glyphs::Unicode
+    }
+  }
+
+  /// Puts the glyph for passing in front of the message
+  pub fn pass(self, message: &str) -> String {
+    self.mark("✓", message)
+  }
+
+  /// Puts the glyph for an error or failing in front of the message
+  pub fn fail(self, message: &str) -> String {
+    self.mark("✗", message)
+  }
+
+  /// Puts the glyph for a warning in front of the message
+  pub fn warning(self, message: &str) -> String {
+    self.mark("⚠", message)
+  }
+
+  fn mark(self, glyph: &str, message: &str) -> String {
+    match self {
+      Glyphs::Ascii => message.to_string(),
+      Glyphs::Unicode => format!("{} {}", glyph, message),
+    }
+  }
+}
+
+/// A log record written as a single JSON line
+#[derive(Debug, Serialize)]
+struct LogLine<'a> {
+  /// When the record was logged in RFC 3339 format
+  timestamp: String,
+  /// The log level e.g. INFO, WARN
+  level: String,
+  /// The module that logged the record
+  target: &'a str,
+  /// The log message
+  message: String,
+}
+
+/// Logger that writes each record to stdout as a JSON line, without colours, for log collectors
+pub struct NdjsonLogger {
+  /// The most verbose level that is logged
+  level: LevelFilter,
+}
+
+impl NdjsonLogger {
+  /// Sets the NDJSON logger as the global logger
+  pub fn init(level: LevelFilter) -> Result<(), log::SetLoggerError> {
+    log::set_max_level(level);
+    log::set_boxed_logger(Box::new(NdjsonLogger { level }))
+  }
+}
+
+/// Formats the record as a JSON line
+fn to_json_line(record: &Record) -> String {
+  let line = LogLine {
+    timestamp: OffsetDateTime::now_utc()
+      .format(&Rfc3339)
+      .unwrap_or_default(),
+    level: record.level().to_string(),
+    target: record.target(),
+    message: record.args().to_string(),
+  };
+  serde_json::to_string(&line).expect("Failed to serialize log record")
+}
+
+impl Log for NdjsonLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.level
+  }
+
+  fn log(&self, record: &Record) {
+    if self.enabled(record.metadata()) {
+      let mut stdout = std::io::stdout().lock();
+      let _ = writeln!(stdout, "{}", to_json_line(record));
+    }
+  }
+
+  fn flush(&self) {
+    let _ = std::io::stdout().flush();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_to_json_line() {
+    let line = to_json_line(
+      &Record::builder()
+        .args(format_args!("Validating \"lambda.tf\""))
+        .level(log::Level::Warn)
+        .target("sv::terraform")
+        .build(),
+    );
+    let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["level"], "WARN");
+    assert_eq!(value["target"], "sv::terraform");
+    assert_eq!(value["message"], "Validating \"lambda.tf\"");
+    assert!(value["timestamp"].is_string());
+    assert!(!line.contains('\n'));
+  }
+
+  #[test]
+  fn test_glyphs() {
+    assert_eq!(Glyphs::Unicode.fail("Invalid"), "✗ Invalid");
+    assert_eq!(Glyphs::Unicode.pass("Passed"), "✓ Passed");
+    assert_eq!(Glyphs::Ascii.fail("Invalid"), "Invalid");
+    assert_eq!(Glyphs::Ascii.warning("Check"), "Check");
+    assert_eq!(Glyphs::detect(true), Glyphs::Ascii);
+  }
+}
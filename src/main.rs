@@ -1,22 +1,44 @@
 #[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
+#[cfg(feature = "update")]
 use self_update::cargo_crate_version;
+#[cfg(feature = "update")]
+use simplelog::debug;
 use simplelog::{
-  debug, info, warn, Color, ColorChoice, Config, ConfigBuilder, Level, LevelFilter, TermLogger,
+  error, info, warn, Color, ColorChoice, Config, ConfigBuilder, Level, LevelFilter, TermLogger,
   TerminalMode,
 };
-use sv::{self, cross_validation::cross_validation, open_api, terraform};
-
-use clap::{Args, Parser};
+use sv::{
+  self, cache,
+  config::{Config as SvConfig, Environment, Profile},
+  coverage::{format_coverage_table, route_coverage},
+  diff::diff_routes,
+  doctor::{check_environment, format_checklist},
+  error::{exit_code, SvError},
+  findings::{group_by_prefix, Finding, Findings, Severity},
+  inventory::{format_lambda_table, lambda_inventory},
+  logger::{Glyphs, NdjsonLogger},
+  open_api,
+  pretty::format_pretty_report,
+  profile,
+  routes::{format_routes_csv, format_routes_table, route_inventory},
+  terraform,
+  util::validating_path,
+  verify::{self, ReportDiff, Summary, VerifyOptions, VerifyReport},
+};
 
-use open_api::validate_open_api;
+use clap::{Args, Parser, ValueEnum};
 
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 // extern crate pretty_env_logger;
 // #[macro_use]
 // extern crate log;
-use terraform::validate_terraform;
 
+#[cfg(feature = "update")]
 const REPO_OWNER: &str = "ZimboPro";
+#[cfg(feature = "update")]
 const REPO_NAME: &str = "sv";
 
 /// Top level commands
@@ -24,42 +46,816 @@ const REPO_NAME: &str = "sv";
 #[command(author, version, about, long_about = None)]
 enum Commands {
   /// Update the binary to the latest version
+  #[cfg(feature = "update")]
   Update,
   /// Verify the OpenAPI and Terraform files
-  Verify(VerifyArguments),
+  Verify(Box<VerifyArguments>),
+  /// Verify a single OpenAPI document without the Terraform files
+  VerifyApi(VerifyApiArguments),
+  /// Compare the OpenAPI files to an OpenAPI export of the deployed API Gateway
+  Diff(DiffArguments),
+  /// Show which of GET, POST, PUT, PATCH and DELETE are defined for each route in the OpenAPI files
+  Coverage(CoverageArguments),
+  /// List the lambdas in the Terraform files with their handler, trigger and whether they're wired to API gateway or a step function
+  ListLambdas(ListLambdasArguments),
+  /// List the routes in the OpenAPI files with the lambdas in the Terraform files that back them
+  Routes(RoutesArguments),
+  /// Check that the paths exist, the OpenAPI and Terraform files can be found and updates can be
+  /// checked for, to find out why a verification found nothing
+  Doctor(DoctorArguments),
   /// Output the markdown help page
   #[command(hide = true)]
   Markdown,
 }
 
+/// Format of the log output
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum LogFormat {
+  /// Coloured text for the terminal
+  #[default]
+  Text,
+  /// A JSON object per line for log collectors
+  Ndjson,
+}
+
+/// Format the findings are reported in once verifying is done
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OutputFormat {
+  /// Only the log output
+  #[default]
+  Text,
+  /// A report with a section for the OpenAPI documents, the Terraform files and the
+  /// cross-validation, with the number of findings in each
+  Pretty,
+  /// GitHub Actions workflow commands that annotate the files in the pull request
+  Github,
+}
+
 /// Arguments for verifying
-#[derive(Args, Debug, PartialEq, Eq)]
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
 struct VerifyArguments {
+  /// The path to the OpenAPI files
+  #[arg(short, long, required_unless_present_any = ["env", "files", "api_glob"])]
+  api_path: Option<PathBuf>,
+  /// The path to the Terraform files
+  #[arg(short, long, required_unless_present_any = ["env", "terraform_plan", "files", "terraform_glob"])]
+  terraform: Option<PathBuf>,
+  /// The environment in `sv.toml` to use the paths and overrides of, can be repeated with `--summary-only` to verify several services
+  #[arg(long)]
+  env: Vec<String>,
+  /// Verbose mode
+  #[arg(short, long)]
+  verbose: bool,
+  /// Used to continue even if the CyclicRef error occurs
+  #[arg(long)]
+  skip_cyclic: bool,
+  /// Warn when a POST, PUT or PATCH request body isn't marked as required
+  #[arg(long)]
+  strict_bodies: bool,
+  /// Warn about the `passthroughBehavior` and `requestParameters` of the `x-amazon-apigateway-integration` extensions that are known to cause problems
+  #[arg(long)]
+  strict_integration: bool,
+  /// Warn about the 2xx responses of Lambda proxy (`aws_proxy`) integrations that only declare content types other than JSON, e.g. `text/plain`, as the lambda's response is `application/json` unless it sets the `Content-Type` header
+  #[arg(long)]
+  strict_responses: bool,
+  /// Warn about the routes granted for any method (`*`) in lambda_permissions.tf that the OpenAPI documents only define some methods for, which the strict profile also turns on
+  #[arg(long)]
+  strict_methods: bool,
+  /// Warn about the paths without an OPTIONS method when the OpenAPI documents use CORS, either with an `x-amazon-apigateway-cors` extension or an OPTIONS method with a `mock` integration
+  #[arg(long)]
+  check_cors: bool,
+  /// Warn about the operations that have no `security` requirement and the document has none either, set `security: []` on the public operations
+  #[arg(long)]
+  require_security: bool,
+  /// Fail when an operation has no `tags`, OPTIONS operations are skipped
+  #[arg(long)]
+  require_tags: bool,
+  /// Only cross validate and report the routes matching the glob, e.g. `/users/**`, where `*` matches within a segment and `**` any number of segments
+  #[arg(long, value_name = "GLOB")]
+  filter_route: Option<String>,
+  /// Log how the route is cross validated step by step, e.g. `/users/{id}`: the documents that define it, the lambdas with a permission for it, their ARN placeholders, the integration URIs and why each method passes or fails
+  #[arg(long, value_name = "ROUTE")]
+  trace_route: Option<String>,
+  /// Only validate these OpenAPI documents and Terraform files, e.g. the staged files passed by a pre-commit hook, instead of the folders. The files are validated with the folders they're in, `--api-path` and `--terraform` when set, and cross validated when the OpenAPI documents and the lambdas can be read from them. Other files are skipped
+  #[arg(long, num_args = 1.., value_name = "FILE")]
+  files: Vec<PathBuf>,
+  /// Fail when there are any warnings
+  #[arg(long)]
+  fail_on_warning: bool,
+  /// Preset for how strict the validation is, the config and the other options take precedence over it
+  #[arg(long, value_enum)]
+  profile: Option<Profile>,
+  /// Stop reporting findings after this many errors
+  #[arg(long)]
+  max_errors: Option<usize>,
+  /// Stop at the first error, skipping the remaining files and the cross validation
+  #[arg(long)]
+  fail_fast: bool,
+  /// File stem of the OpenAPI document merged into every other document, can be repeated. Defaults to `shared-schemas` and `shared`
+  #[arg(long)]
+  shared_schema_name: Vec<String>,
+  /// Extension of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi` for `service.openapi`, can be repeated
+  #[arg(long, value_name = "EXT")]
+  api_ext: Vec<String>,
+  /// Name of the Terraform module that creates the lambdas, e.g. `functions` for `module.functions["key"]`. Defaults to `lambda`
+  #[arg(long)]
+  lambda_module_name: Option<String>,
+  /// The JSON of `terraform show -json plan.tfplan` to read the lambdas, permissions and API gateway from instead of the Terraform files
+  #[arg(long)]
+  terraform_plan: Option<PathBuf>,
+  /// Only validate the OpenAPI documents matching the glob, e.g. `'apis/**/*.yaml'`, instead of the documents in `--api-path`
+  #[arg(long, value_name = "GLOB", conflicts_with = "files")]
+  api_glob: Option<String>,
+  /// Only validate the Terraform files matching the glob, e.g. `'infra/**/lambda*.tf'`, instead of the files in `--terraform`
+  #[arg(long, value_name = "GLOB", conflicts_with_all = ["files", "terraform_plan"])]
+  terraform_glob: Option<String>,
+  /// A `.tfvars` file with the values to substitute for `${var.name}` references in the Terraform files
+  #[arg(long)]
+  terraform_var_file: Option<PathBuf>,
+  /// The value of `terraform.workspace`, used to pick the lambdas and permissions in conditionals on the workspace, e.g. `terraform.workspace == "prod" ? {...} : {}`
+  #[arg(long)]
+  terraform_workspace: Option<String>,
+  /// Warn about lambda permissions whose principal isn't supported, e.g. `sns.amazonaws.com`, and skip them instead of failing
+  #[arg(long)]
+  allow_unknown_principal: bool,
+  /// Validate the OpenAPI and Terraform files independently, reporting the findings of both, without checking them against each other
+  #[arg(long)]
+  no_cross_validation: bool,
+  /// Validate each OpenAPI document on its own, without merging the documents or merging the shared document into them, and cross validate each document against the permissions for its routes
+  #[arg(long, conflicts_with_all = ["print_merged", "files"])]
+  no_merge: bool,
+  /// Warn when a lambda's ARN placeholder isn't its key, with `-` replaced by `_`, followed by this suffix, e.g. `_arn` for `lambda_1_arn`
+  #[arg(long)]
+  arn_suffix: Option<String>,
+  /// Check that the IAM policy in the file, a JSON policy document or a Terraform file with `policy = jsonencode(...)` or `aws_iam_policy_document` statements, allows `lambda:InvokeFunction` on each lambda with API permissions, instead of the reminder to check the policy by hand
+  #[arg(long, value_name = "FILE")]
+  check_policy: Option<PathBuf>,
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
+  /// Write the merged OpenAPI document to this file, or stdout for `-`, even if validation fails
+  #[arg(long)]
+  print_merged: Option<PathBuf>,
+  /// Number of threads the OpenAPI documents and Terraform files are validated with, defaults to the number of CPUs, `1` validates them one at a time
+  #[arg(long, value_name = "N")]
+  parallel: Option<NonZeroUsize>,
+  /// Cache the results of the OpenAPI documents in `.sv-cache` so unchanged documents aren't validated again
+  #[arg(long)]
+  cache: bool,
+  /// Format the findings are reported in once verifying is done, `pretty` when stdout is a terminal and the log format is text, otherwise `text`
+  #[arg(long, value_enum)]
+  format: Option<OutputFormat>,
+  /// Don't print the suggestions for fixing the findings below them
+  #[arg(long)]
+  no_hints: bool,
+  /// Don't mark the findings and the summary with unicode glyphs, which are only used when stdout is a terminal and `NO_COLOR` isn't set
+  #[arg(long)]
+  ascii: bool,
+  /// Only print the number of errors, warnings, files and documents skipped because of cyclic references as a JSON object
+  #[arg(long)]
+  summary_json: bool,
+  /// Only print the outcome and the number of errors, warnings and files of each environment given with `--env`, or of the paths, followed by the totals
+  #[arg(long, conflicts_with_all = ["format", "group_by_prefix", "compare_report", "report_template", "save_report", "output_dir", "quiet", "summary_json", "stream", "trace_route", "profile_output"])]
+  summary_only: bool,
+  /// Don't print anything when verification passes, and only the findings and the summary when it fails
+  #[arg(short, long)]
+  quiet: bool,
+  /// Save the findings to this file as a JSON report, to compare later runs against with `--compare-report`
+  #[arg(long)]
+  save_report: Option<PathBuf>,
+  /// Only log the findings that are new or fixed compared to a report saved with `--save-report`, labelled `[new]` and `[fixed]`
+  #[arg(long)]
+  compare_report: Option<PathBuf>,
+  /// Write the findings to `report.json`, `report.sarif` and `report.txt` in this folder, creating it if needed
+  #[arg(long, value_name = "DIR")]
+  output_dir: Option<PathBuf>,
+  /// Write how long each stage and each file took to this file as JSON, with the files sorted slowest first, to find the documents that dominate the time of large repos
+  #[arg(long, value_name = "FILE")]
+  profile_output: Option<PathBuf>,
+  /// Print the report rendered with this Tera template instead of logging the findings, e.g. `templates/pr-comment.md` for a Markdown table. The template gets `passed`, `error` and the `findings` with their `code`, `severity`, `message` and `file`
+  #[arg(long, value_name = "FILE", conflicts_with = "format")]
+  report_template: Option<PathBuf>,
+  /// Log the findings once verifying is done, sorted and grouped under the top-level segment of their route, e.g. `/users`
+  #[arg(long)]
+  group_by_prefix: bool,
+  /// Log each finding as soon as it's found, the OpenAPI documents in the order they finish, with the summary last
+  #[arg(long, conflicts_with_all = ["format", "group_by_prefix", "compare_report", "report_template", "quiet", "summary_json"])]
+  stream: bool,
+}
+
+/// Arguments for verifying a single OpenAPI document
+#[derive(Args, Debug, PartialEq, Eq)]
+struct VerifyApiArguments {
+  /// Read the OpenAPI document from stdin
+  #[arg(long, required = true)]
+  stdin: bool,
+  /// Verbose mode
+  #[arg(short, long)]
+  verbose: bool,
+  /// Used to continue even if the CyclicRef error occurs
+  #[arg(long)]
+  skip_cyclic: bool,
+  /// Warn when a POST, PUT or PATCH request body isn't marked as required
+  #[arg(long)]
+  strict_bodies: bool,
+  /// Warn about the `passthroughBehavior` and `requestParameters` of the `x-amazon-apigateway-integration` extensions that are known to cause problems
+  #[arg(long)]
+  strict_integration: bool,
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
+}
+
+/// Arguments for comparing to the deployed API
+#[derive(Args, Debug, PartialEq, Eq)]
+struct DiffArguments {
+  /// The OpenAPI export of the deployed API Gateway, including the API Gateway extensions
+  #[arg(short, long)]
+  deployed: PathBuf,
+  /// The path to the OpenAPI files
+  #[arg(short, long)]
+  api_path: PathBuf,
+  /// Extension of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi` for `service.openapi`, can be repeated
+  #[arg(long, value_name = "EXT")]
+  api_ext: Vec<String>,
+  /// Verbose mode
+  #[arg(short, long)]
+  verbose: bool,
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
+}
+
+/// Format the route coverage is printed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum CoverageFormat {
+  /// A table with a column per method and the missing methods of each route
+  #[default]
+  Text,
+  /// A JSON array with an object per route
+  Json,
+}
+
+/// Arguments for showing the methods of the routes
+#[derive(Args, Debug, PartialEq, Eq)]
+struct CoverageArguments {
   /// The path to the OpenAPI files
   #[arg(short, long)]
   api_path: PathBuf,
+  /// Extension of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi` for `service.openapi`, can be repeated
+  #[arg(long, value_name = "EXT")]
+  api_ext: Vec<String>,
+  /// Format the coverage is printed in
+  #[arg(long, value_enum, default_value_t = CoverageFormat::Text)]
+  format: CoverageFormat,
+  /// Verbose mode
+  #[arg(short, long)]
+  verbose: bool,
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
+}
+
+/// Format the lambdas are printed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum LambdasFormat {
+  /// A table with a row per lambda
+  #[default]
+  Text,
+  /// A JSON array with an object per lambda
+  Json,
+}
+
+/// Arguments for listing the lambdas
+#[derive(Args, Debug, PartialEq, Eq)]
+struct ListLambdasArguments {
   /// The path to the Terraform files
   #[arg(short, long)]
   terraform: PathBuf,
+  /// Name of the Terraform module that creates the lambdas, e.g. `functions` for `module.functions["key"]`. Defaults to `lambda`
+  #[arg(long)]
+  lambda_module_name: Option<String>,
+  /// Format the lambdas are printed in
+  #[arg(long, value_enum, default_value_t = LambdasFormat::Text)]
+  format: LambdasFormat,
   /// Verbose mode
   #[arg(short, long)]
   verbose: bool,
-  /// Used to continue even if the CyclicRef error occurs
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
+}
+
+/// Format the routes are printed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum RoutesFormat {
+  /// A table with a row per route
+  #[default]
+  Text,
+  /// A JSON array with an object per route
+  Json,
+  /// CSV with a header row, for spreadsheets
+  Csv,
+}
+
+/// Arguments for listing the routes
+#[derive(Args, Debug, PartialEq, Eq)]
+struct RoutesArguments {
+  /// The path to the OpenAPI files
+  #[arg(short, long)]
+  api_path: PathBuf,
+  /// Extension of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi` for `service.openapi`, can be repeated
+  #[arg(long, value_name = "EXT")]
+  api_ext: Vec<String>,
+  /// The path to the Terraform files
+  #[arg(short, long)]
+  terraform: PathBuf,
+  /// Name of the Terraform module that creates the lambdas, e.g. `functions` for `module.functions["key"]`. Defaults to `lambda`
   #[arg(long)]
-  skip_cyclic: bool,
+  lambda_module_name: Option<String>,
+  /// Format the routes are printed in
+  #[arg(long, value_enum, default_value_t = RoutesFormat::Text)]
+  format: RoutesFormat,
+  /// Verbose mode
+  #[arg(short, long)]
+  verbose: bool,
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
+}
+
+/// Arguments for checking the environment
+#[derive(Args, Debug, PartialEq, Eq)]
+struct DoctorArguments {
+  /// The path to the OpenAPI files
+  #[arg(short, long)]
+  api_path: PathBuf,
+  /// Extension of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi` for `service.openapi`, can be repeated
+  #[arg(long, value_name = "EXT")]
+  api_ext: Vec<String>,
+  /// The path to the Terraform files
+  #[arg(short, long)]
+  terraform: PathBuf,
+  /// Don't mark the checks with unicode glyphs, which are only used when stdout is a terminal and `NO_COLOR` isn't set
+  #[arg(long)]
+  ascii: bool,
+  /// Verbose mode
+  #[arg(short, long)]
+  verbose: bool,
+  /// Format of the log output
+  #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+  log_format: LogFormat,
 }
 
-/// Check if the path exists and is a folder else return an Error
-fn validating_path(path: &PathBuf) -> anyhow::Result<()> {
-  if !path.exists() {
-    return Err(anyhow::anyhow!("Path {:?} does not exist", path));
-  } else if !path.is_dir() {
-    return Err(anyhow::anyhow!("Path {:?} is not a folder", path));
+/// Builds the options from the arguments, the config and the environment named `env`
+fn verify_options(args: &VerifyArguments, env: Option<&str>) -> anyhow::Result<VerifyOptions> {
+  let mut config = SvConfig::load(&std::env::current_dir()?)?;
+  let environment = match env {
+    Some(name) => config.environment(name)?,
+    None => Environment::default(),
+  };
+  let api_path = match args.api_path.clone().or(environment.api_path) {
+    Some(api_path) => api_path,
+    None if !args.files.is_empty() || args.api_glob.is_some() => PathBuf::new(),
+    None => {
+      return Err(
+        SvError::Config("The environment doesn't set api_path, use --api-path".to_string()).into(),
+      )
+    }
+  };
+  let terraform = match (
+    args.terraform.clone().or(environment.terraform),
+    &args.terraform_plan,
+  ) {
+    (Some(terraform), _) => terraform,
+    (None, Some(_)) => PathBuf::new(),
+    (None, None) if !args.files.is_empty() || args.terraform_glob.is_some() => PathBuf::new(),
+    (None, None) => {
+      return Err(
+        SvError::Config("The environment doesn't set terraform, use --terraform".to_string())
+          .into(),
+      )
+    }
+  };
+  let mut builder = VerifyOptions::builder()
+    .api_path(api_path)
+    .terraform(terraform)
+    .config(config)
+    .skip_cyclic(args.skip_cyclic)
+    .check_cors(args.check_cors)
+    .require_security(args.require_security)
+    .require_tags(args.require_tags)
+    .no_cross_validation(args.no_cross_validation)
+    .no_merge(args.no_merge)
+    .allow_unknown_principal(args.allow_unknown_principal);
+  if let Some(profile) = args.profile {
+    builder = builder.profile(profile);
+  }
+  // The flags only turn the settings on, so the profile's are kept when they aren't given
+  if args.strict_bodies {
+    builder = builder.strict_bodies(true);
+  }
+  if args.strict_integration {
+    builder = builder.strict_integration(true);
+  }
+  if args.strict_responses {
+    builder = builder.strict_responses(true);
+  }
+  if args.strict_methods {
+    builder = builder.strict_methods(true);
+  }
+  if args.fail_on_warning {
+    builder = builder.fail_on_warning(true);
+  }
+  if !args.shared_schema_name.is_empty() {
+    builder = builder.shared_schema_names(args.shared_schema_name.clone());
+  }
+  if !args.api_ext.is_empty() {
+    builder = builder.api_extensions(args.api_ext.clone());
+  }
+  if let Some(lambda_module_name) = &args.lambda_module_name {
+    builder = builder.lambda_module_name(lambda_module_name.clone());
+  }
+  if let Some(filter_route) = &args.filter_route {
+    builder = builder.filter_route(filter_route.clone());
+  }
+  if let Some(trace_route) = &args.trace_route {
+    builder = builder.trace_route(trace_route.clone());
+  }
+  if !args.files.is_empty() {
+    builder = builder.files(args.files.clone());
+  }
+  if let Some(var_file) = &args
+    .terraform_var_file
+    .clone()
+    .or(environment.terraform_var_file)
+  {
+    builder = builder.variables(terraform::load_var_file(var_file)?);
+  }
+  if let Some(terraform_workspace) = &args.terraform_workspace {
+    builder = builder.terraform_workspace(terraform_workspace.clone());
+  }
+  if let Some(terraform_plan) = &args.terraform_plan {
+    builder = builder.terraform_plan(terraform_plan.clone());
   }
+  if let Some(api_glob) = &args.api_glob {
+    builder = builder.api_glob(api_glob.clone());
+  }
+  if let Some(terraform_glob) = &args.terraform_glob {
+    builder = builder.terraform_glob(terraform_glob.clone());
+  }
+  if let Some(arn_suffix) = &args.arn_suffix {
+    builder = builder.arn_suffix(arn_suffix.clone());
+  }
+  if let Some(check_policy) = &args.check_policy {
+    builder = builder.check_policy(check_policy.clone());
+  }
+  if let Some(print_merged) = &args.print_merged {
+    builder = builder.print_merged(print_merged.clone());
+  }
+  if args.cache {
+    builder = builder.cache_dir(cache::DEFAULT_CACHE_DIR);
+  }
+  Ok(builder.build())
+}
+
+/// Validates the OpenAPI and Terraform files, recording the problems in `findings`
+fn verify(args: VerifyArguments, findings: &mut Findings) -> anyhow::Result<()> {
+  let env = match args.env.as_slice() {
+    [] => None,
+    [env] => Some(env.as_str()),
+    _ => {
+      return Err(
+        SvError::Config("Only one --env can be given without --summary-only".to_string()).into(),
+      )
+    }
+  };
+  let options = verify_options(&args, env)?;
+  let baseline = args
+    .compare_report
+    .as_deref()
+    .map(VerifyReport::load)
+    .transpose()?;
+  let pretty = args.format == Some(OutputFormat::Pretty);
+  let github = args.format == Some(OutputFormat::Github);
+  findings.set_silent(
+    baseline.is_some()
+      || args.group_by_prefix
+      || pretty
+      || github
+      || args.report_template.is_some(),
+  );
+  if args.profile_output.is_some() {
+    profile::start();
+  }
+  let result = verify::verify(&options, findings);
+  if let Some(path) = &args.profile_output {
+    if let Some(profile) = profile::finish() {
+      profile.save(path)?;
+      info!("Wrote the profile to {:?}", path);
+    }
+  }
+  if pretty && baseline.is_none() && !args.quiet {
+    println!(
+      "{}",
+      format_pretty_report(findings.iter(), findings.glyphs(), findings.hints())
+    );
+  } else if args.group_by_prefix && baseline.is_none() {
+    log_findings(findings.iter(), findings.glyphs(), true, findings.hints());
+  }
+  if baseline.is_some()
+    || args.save_report.is_some()
+    || args.output_dir.is_some()
+    || args.report_template.is_some()
+  {
+    let report = VerifyReport::new(&result, findings);
+    if let Some(path) = &args.save_report {
+      report.save(path)?;
+    }
+    if let Some(template) = &args.report_template {
+      print!("{}", report.render(template)?);
+    }
+    if let Some(dir) = &args.output_dir {
+      report.write_to_dir(dir, findings.hints())?;
+      info!(
+        "Wrote report.json, report.sarif and report.txt to {:?}",
+        dir
+      );
+    }
+    if let Some(baseline) = &baseline {
+      log_report_diff(&report.compare(baseline), findings.glyphs());
+    }
+  }
+  if args.summary_json {
+    println!(
+      "{}",
+      serde_json::to_string(&Summary::new(&options, findings))?
+    );
+  }
+  result
+}
+
+/// Verifies each environment given with `--env`, or the paths, and prints only the outcome and
+/// the totals of each one followed by the totals of all of them
+///
+/// Fails when any of them fails
+fn verify_summaries(args: VerifyArguments) -> anyhow::Result<()> {
+  let environments: Vec<Option<&str>> = if args.env.is_empty() {
+    vec![None]
+  } else {
+    args.env.iter().map(|env| Some(env.as_str())).collect()
+  };
+  let mut total = Summary::default();
+  let mut failed = 0;
+  for env in &environments {
+    let mut findings = Findings::new(args.max_errors);
+    findings.set_fail_fast(args.fail_fast);
+    findings.set_silent(true);
+    let (name, result, summary) = match verify_options(&args, *env) {
+      Ok(options) => {
+        let result = verify::verify(&options, &mut findings);
+        let name = env
+          .map(|env| env.to_string())
+          .unwrap_or_else(|| options.api_path.display().to_string());
+        (name, result, Summary::new(&options, &findings))
+      }
+      Err(e) => (
+        env.unwrap_or_default().to_string(),
+        Err(e),
+        Summary::default(),
+      ),
+    };
+    let outcome = match &result {
+      Ok(()) => "passed".to_string(),
+      Err(e) => {
+        failed += 1;
+        format!("failed ({})", e)
+      }
+    };
+    println!(
+      "{}: {}, errors: {}, warnings: {}, files: {}",
+      name, outcome, summary.errors, summary.warnings, summary.files
+    );
+    total.errors += summary.errors;
+    total.warnings += summary.warnings;
+    total.files += summary.files;
+    total.skipped_cyclic += summary.skipped_cyclic;
+  }
+  println!(
+    "Total: {} of {} passed, errors: {}, warnings: {}, files: {}",
+    environments.len() - failed,
+    environments.len(),
+    total.errors,
+    total.warnings,
+    total.files
+  );
+  if failed > 0 {
+    return Err(anyhow::anyhow!(
+      "Verification failed for {} of {}",
+      failed,
+      environments.len()
+    ));
+  }
+  Ok(())
+}
+
+/// Logs the findings, under a header for the prefix of their route when `by_prefix` is set, with
+/// the suggestions for fixing them when `hints` is set
+fn log_findings<'a>(
+  findings: impl IntoIterator<Item = &'a Finding>,
+  glyphs: Glyphs,
+  by_prefix: bool,
+  hints: bool,
+) {
+  if !by_prefix {
+    findings
+      .into_iter()
+      .for_each(|finding| finding.log(glyphs, hints));
+    return;
+  }
+  for (prefix, findings) in group_by_prefix(findings) {
+    info!("{}", prefix.as_deref().unwrap_or("Other findings"));
+    findings
+      .iter()
+      .for_each(|finding| finding.log(glyphs, hints));
+  }
+}
+
+/// Logs the findings that are new or fixed compared to the baseline report
+fn log_report_diff(diff: &ReportDiff, glyphs: Glyphs) {
+  for finding in &diff.new {
+    match finding.severity {
+      Severity::Warning => warn!("[new] {}", glyphs.warning(&finding.message)),
+      Severity::Error => error!("[new] {}", glyphs.fail(&finding.message)),
+    }
+  }
+  for finding in &diff.fixed {
+    info!("[fixed] {}", glyphs.pass(&finding.message));
+  }
+  info!(
+    "{} new and {} fixed findings compared to the baseline report",
+    diff.new.len(),
+    diff.fixed.len()
+  );
+}
+
+/// Validates the OpenAPI document piped to stdin
+fn verify_api(args: VerifyApiArguments) -> anyhow::Result<()> {
+  let mut content = String::new();
+  std::io::stdin()
+    .read_to_string(&mut content)
+    .map_err(|e| SvError::Io(format!("Failed to read stdin: {}", e)))?;
+  let mut findings = Findings::default();
+  open_api::validate_open_api_document(
+    content,
+    Path::new("stdin"),
+    args.skip_cyclic,
+    args.strict_bodies,
+    args.strict_integration,
+    &mut findings,
+  )?;
+  info!("The OpenAPI document is valid");
   Ok(())
 }
 
+/// Reports the routes that differ between the OpenAPI files and the deployed API
+fn diff(args: DiffArguments) -> anyhow::Result<()> {
+  validating_path(&args.api_path)?;
+  if !args.deployed.is_file() {
+    return Err(SvError::Path(format!("File {:?} does not exist", args.deployed)).into());
+  }
+  let mut findings = Findings::default();
+  let source = open_api::read_api_data(&args.api_path, &args.api_ext, &mut findings)?;
+  let deployed = open_api::read_api_data(&args.deployed, &args.api_ext, &mut findings)?;
+  let diff = diff_routes(&source, &deployed);
+  if diff.is_empty() {
+    info!("The deployed API matches the OpenAPI documents");
+    return Ok(());
+  }
+  for api in &diff.added {
+    warn!("Not deployed: {} {}", api.method, api.route);
+  }
+  for api in &diff.removed {
+    warn!(
+      "Deployed but not in the OpenAPI documents: {} {}",
+      api.method, api.route
+    );
+  }
+  Err(anyhow::anyhow!(
+    "The deployed API differs from the OpenAPI documents: {} added, {} removed",
+    diff.added.len(),
+    diff.removed.len()
+  ))
+}
+
+/// Prints which of the common methods are defined for each route
+fn coverage(args: CoverageArguments) -> anyhow::Result<()> {
+  validating_path(&args.api_path)?;
+  let mut findings = Findings::default();
+  let data = open_api::read_api_data(&args.api_path, &args.api_ext, &mut findings)?;
+  let coverage = route_coverage(&data);
+  match args.format {
+    CoverageFormat::Text => println!("{}", format_coverage_table(&coverage)),
+    CoverageFormat::Json => println!("{}", serde_json::to_string_pretty(&coverage)?),
+  }
+  Ok(())
+}
+
+/// Prints the lambdas in the Terraform files and what they're wired to
+fn list_lambdas(args: ListLambdasArguments) -> anyhow::Result<()> {
+  validating_path(&args.terraform)?;
+  let mut findings = Findings::default();
+  let lambdas = terraform::validate_terraform(
+    args.terraform,
+    None,
+    args
+      .lambda_module_name
+      .as_deref()
+      .unwrap_or(terraform::DEFAULT_LAMBDA_MODULE_NAME),
+    &terraform::TerraformVariables::new(),
+    None,
+    true,
+    &mut findings,
+  )?;
+  let inventory = lambda_inventory(&lambdas);
+  match args.format {
+    LambdasFormat::Text => println!("{}", format_lambda_table(&inventory)),
+    LambdasFormat::Json => println!("{}", serde_json::to_string_pretty(&inventory)?),
+  }
+  Ok(())
+}
+
+/// Prints the routes in the OpenAPI files and the lambdas that back them
+fn routes(args: RoutesArguments) -> anyhow::Result<()> {
+  validating_path(&args.api_path)?;
+  validating_path(&args.terraform)?;
+  let mut findings = Findings::default();
+  let data = open_api::read_api_data(&args.api_path, &args.api_ext, &mut findings)?;
+  let lambdas = terraform::validate_terraform(
+    args.terraform,
+    None,
+    args
+      .lambda_module_name
+      .as_deref()
+      .unwrap_or(terraform::DEFAULT_LAMBDA_MODULE_NAME),
+    &terraform::TerraformVariables::new(),
+    None,
+    true,
+    &mut findings,
+  )?;
+  let routes = route_inventory(&data, &lambdas);
+  match args.format {
+    RoutesFormat::Text => println!("{}", format_routes_table(&routes)),
+    RoutesFormat::Json => println!("{}", serde_json::to_string_pretty(&routes)?),
+    RoutesFormat::Csv => println!("{}", format_routes_csv(&routes)),
+  }
+  Ok(())
+}
+
+/// Prints a checklist of what `sv verify` needs, failing when any of it is missing
+fn doctor(args: DoctorArguments) -> anyhow::Result<()> {
+  #[allow(unused_mut)]
+  let mut checks = check_environment(&args.api_path, &args.terraform, &args.api_ext);
+  #[cfg(feature = "update")]
+  checks.push(check_update_access());
+  println!("{}", format_checklist(&checks, Glyphs::detect(args.ascii)));
+  let problems = checks
+    .iter()
+    .filter(|check| check.problem.is_some())
+    .count();
+  if problems > 0 {
+    return Err(anyhow::anyhow!(
+      "Found {} problem{}",
+      problems,
+      if problems == 1 { "" } else { "s" }
+    ));
+  }
+  Ok(())
+}
+
+/// Checks that the releases can be fetched from GitHub to check for updates
+#[cfg(feature = "update")]
+fn check_update_access() -> sv::doctor::Check {
+  let name = "The releases can be fetched to check for updates";
+  let releases = self_update::backends::github::ReleaseList::configure()
+    .repo_owner(REPO_OWNER)
+    .repo_name(REPO_NAME)
+    .build()
+    .and_then(|list| list.fetch());
+  match releases {
+    Ok(_) => sv::doctor::Check::ok(name),
+    Err(e) => sv::doctor::Check::problem(
+      name,
+      format!(
+        "{}, check the network and proxy settings, verify still works without updates",
+        e
+      ),
+    ),
+  }
+}
+
 /// Check if there is an update available
+#[cfg(feature = "update")]
 fn check_if_update_is_available() -> anyhow::Result<()> {
   debug!("Checking for updates");
   let mut rel_builder = self_update::backends::github::ReleaseList::configure();
@@ -96,7 +892,18 @@ fn check_if_update_is_available() -> anyhow::Result<()> {
   Ok(())
 }
 
+/// Sets up the global logger for the log format
+fn init_logger(level: LevelFilter, config: Config, log_format: LogFormat) {
+  match log_format {
+    LogFormat::Text => {
+      TermLogger::init(level, config, TerminalMode::Stdout, ColorChoice::Auto).unwrap()
+    }
+    LogFormat::Ndjson => NdjsonLogger::init(level).unwrap(),
+  }
+}
+
 /// Updates the binary to the latest version
+#[cfg(feature = "update")]
 fn update_binary(config: Config) -> anyhow::Result<()> {
   TermLogger::init(
     LevelFilter::Info,
@@ -125,7 +932,19 @@ fn update_binary(config: Config) -> anyhow::Result<()> {
   Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
+/// Runs the command, exiting with 1 when the files are invalid and 2 when sv couldn't check them
+fn main() -> ExitCode {
+  match run() {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("Error: {:?}", e);
+      ExitCode::from(exit_code(&e))
+    }
+  }
+}
+
+/// Parses the arguments and runs the command
+fn run() -> anyhow::Result<()> {
   // pretty_env_logger::formatted_builder()
   //     .filter_level(log::LevelFilter::Info)
   //     .format_timestamp(None)
@@ -140,29 +959,182 @@ fn main() -> anyhow::Result<()> {
     .build();
   let args = Commands::parse();
   match args {
+    #[cfg(feature = "update")]
     Commands::Update => update_binary(config),
-    Commands::Verify(args) => {
-      let level = if args.verbose {
+    Commands::Verify(mut args) => {
+      let summary_json = args.summary_json;
+      let level = if summary_json || args.summary_only {
+        LevelFilter::Off
+      } else if args.verbose {
         LevelFilter::Debug
       } else {
         LevelFilter::Info
       };
-      TermLogger::init(level, config, TerminalMode::Stdout, ColorChoice::Auto).unwrap();
-      if check_if_update_is_available().is_err() {
+      let log_format = args.log_format;
+      init_logger(level, config, log_format);
+      if args.summary_only {
+        return verify_summaries(*args);
+      }
+      let quiet = args.quiet;
+      let by_prefix = args.group_by_prefix;
+      let hints = !args.no_hints;
+      let filter_route = args.filter_route.clone();
+      let policy_checked = args.check_policy.is_some();
+      #[cfg(feature = "update")]
+      if !summary_json && !quiet && check_if_update_is_available().is_err() {
         warn!("Failed to check for updates");
       }
 
-      let api_path = args.api_path;
-      validating_path(&api_path)?;
-      validating_path(&args.terraform)?;
-      let open_api_config = validate_open_api(api_path, args.skip_cyclic)?;
-      let lambda_data = validate_terraform(args.terraform)?;
-      cross_validation(lambda_data, open_api_config)?;
-      println!();
-      warn!("Make sure to check the JSON policy in either api_gateway.tf or the resources for the attached policy.");
+      if let Some(parallel) = args.parallel {
+        rayon::ThreadPoolBuilder::new()
+          .num_threads(parallel.get())
+          .build_global()?;
+      }
+      let format = args.format.unwrap_or(
+        if log_format == LogFormat::Text
+          && !summary_json
+          && !args.stream
+          && args.report_template.is_none()
+          && std::io::stdout().is_terminal()
+        {
+          OutputFormat::Pretty
+        } else {
+          OutputFormat::Text
+        },
+      );
+      args.format = Some(format);
+      let glyphs = Glyphs::detect(args.ascii || log_format == LogFormat::Ndjson);
+      let mut findings = Findings::new(args.max_errors);
+      findings.set_fail_fast(args.fail_fast);
+      findings.set_glyphs(glyphs);
+      findings.set_hints(hints);
+      findings.set_stream(args.stream);
+      if quiet {
+        findings.set_silent(true);
+        log::set_max_level(LevelFilter::Off);
+      }
+      let result = verify(*args, &mut findings);
+      if quiet {
+        if result.is_ok() {
+          return Ok(());
+        }
+        log::set_max_level(level);
+        match format {
+          OutputFormat::Pretty => {
+            println!("{}", format_pretty_report(findings.iter(), glyphs, hints))
+          }
+          OutputFormat::Github => {}
+          OutputFormat::Text => log_findings(findings.iter(), glyphs, by_prefix, hints),
+        }
+      }
+      if format == OutputFormat::Github {
+        findings
+          .iter()
+          .for_each(|finding| println!("{}", finding.to_github_annotation()));
+      }
+      if findings.suppressed() > 0 {
+        error!("... and {} more; fix these first", findings.suppressed());
+      }
+      if let Some(glob) = &filter_route {
+        warn!(
+          "Only the routes matching {} were checked, {} findings about other routes weren't reported",
+          glob,
+          findings.filtered()
+        );
+      }
+      if findings.ignored() > 0 {
+        info!(
+          "{} findings weren't reported because of sv:ignore comments",
+          findings.ignored()
+        );
+      }
+      match &result {
+        Ok(()) => info!(
+          "{}",
+          glyphs.pass(&format!(
+            "Verification passed, warnings: {}",
+            findings.warning_count()
+          ))
+        ),
+        Err(_) => error!(
+          "{}",
+          glyphs.fail(&format!(
+            "Verification failed, errors: {}, warnings: {}",
+            findings.error_count(),
+            findings.warning_count()
+          ))
+        ),
+      }
+      result?;
+      if log_format == LogFormat::Text && !summary_json {
+        println!();
+      }
+      if !policy_checked {
+        warn!("Make sure to check the JSON policy in either api_gateway.tf or the resources for the attached policy.");
+      }
       warn!("NOTE: This tool only checks for common errors. It does not check for all errors.");
       Ok(())
     }
+    Commands::VerifyApi(args) => {
+      let level = if args.verbose {
+        LevelFilter::Debug
+      } else {
+        LevelFilter::Info
+      };
+      init_logger(level, config, args.log_format);
+      verify_api(args)
+    }
+    Commands::Diff(args) => {
+      let level = if args.verbose {
+        LevelFilter::Debug
+      } else {
+        LevelFilter::Info
+      };
+      init_logger(level, config, args.log_format);
+      diff(args)
+    }
+    Commands::Coverage(args) => {
+      let level = if args.format == CoverageFormat::Json {
+        LevelFilter::Off
+      } else if args.verbose {
+        LevelFilter::Debug
+      } else {
+        LevelFilter::Info
+      };
+      init_logger(level, config, args.log_format);
+      coverage(args)
+    }
+    Commands::ListLambdas(args) => {
+      let level = if args.format == LambdasFormat::Json {
+        LevelFilter::Off
+      } else if args.verbose {
+        LevelFilter::Debug
+      } else {
+        LevelFilter::Info
+      };
+      init_logger(level, config, args.log_format);
+      list_lambdas(args)
+    }
+    Commands::Routes(args) => {
+      let level = if args.format != RoutesFormat::Text {
+        LevelFilter::Off
+      } else if args.verbose {
+        LevelFilter::Debug
+      } else {
+        LevelFilter::Info
+      };
+      init_logger(level, config, args.log_format);
+      routes(args)
+    }
+    Commands::Doctor(args) => {
+      let level = if args.verbose {
+        LevelFilter::Debug
+      } else {
+        LevelFilter::Info
+      };
+      init_logger(level, config, args.log_format);
+      doctor(args)
+    }
     Commands::Markdown => {
       clap_markdown::print_help_markdown::<Commands>();
       Ok(())
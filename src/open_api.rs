@@ -2,14 +2,27 @@ use anyhow::anyhow;
 
 use merge_yaml_hash::MergeYamlHash;
 use oapi::{OApi, OApiTag};
-use simplelog::{debug, error, info, warn};
+use rayon::prelude::*;
+use simplelog::{debug, info};
 use sppparse::{SparseError, SparseRoot};
 
-use std::{ffi::OsStr, io::Read, path::PathBuf};
+use std::{
+  collections::{BTreeMap, HashMap},
+  ffi::OsStr,
+  io::Read,
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicBool, Ordering},
+};
 
 use core::fmt::Display;
 
-use crate::util::HttpMethod;
+use crate::{
+  cache::ValidationCache,
+  error::SvError,
+  findings::{Finding, FindingCode, Findings},
+  profile,
+  util::{expand_glob, walk_dir, GatewayType, HttpMethod},
+};
 
 /// OpenAPI data that is extracted
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,6 +35,11 @@ pub struct OpenAPIData {
   pub uri: String,
   /// The Execution type
   pub execution_type: ExecutionType,
+  /// The operation is marked as `deprecated`
+  pub deprecated: bool,
+  /// The type of API the operation is for, an HTTP API when its integration has a
+  /// `payloadFormatVersion`
+  pub gateway: GatewayType,
 }
 
 /// The API Execution type
@@ -45,197 +63,753 @@ impl Display for ExecutionType {
   }
 }
 
+/// Extensions of the OpenAPI documents that are always looked for
+pub const DEFAULT_API_EXTENSIONS: [&str; 2] = ["yml", "yaml"];
+
+/// File stems of the shared OpenAPI document when none are configured
+pub const DEFAULT_SHARED_SCHEMA_NAMES: [&str; 2] = ["shared-schemas", "shared"];
+
+/// How the OpenAPI documents are found and which of the optional checks are run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenApiOptions<'a> {
+  /// Only the documents matching this glob are validated, instead of the documents in the API path
+  pub api_glob: Option<&'a str>,
+  /// Continue even if the CyclicRef error occurs
+  pub skip_cyclic: bool,
+  /// Warn when a POST, PUT or PATCH request body isn't marked as required
+  pub strict_bodies: bool,
+  /// Warn about the `passthroughBehavior` and `requestParameters` of the integrations
+  pub strict_integration: bool,
+  /// Warn about the 2xx responses of Lambda proxy integrations that only declare content types
+  /// other than JSON
+  pub strict_responses: bool,
+  /// Warn about the paths without an OPTIONS method when the documents use CORS
+  pub check_cors: bool,
+  /// Warn about the operations without a security requirement
+  pub require_security: bool,
+  /// Fail when an operation has no tags
+  pub require_tags: bool,
+  /// File stems of the OpenAPI documents merged into every other document
+  pub shared_schema_names: &'a [String],
+  /// Extensions of the OpenAPI documents besides `yml` and `yaml`
+  pub api_extensions: &'a [String],
+  /// Write the merged OpenAPI document to this file, or stdout for `-`
+  pub print_merged: Option<&'a Path>,
+  /// Cache the results of the documents, not cached when it isn't set
+  pub cache: Option<&'a ValidationCache>,
+}
+
 /// Validates individual OpenAPI documents and docs merged together
 ///
-/// If a `shared-schema` or `shared` file exists, it is merge with each file before being validated
-pub fn validate_open_api(api_path: PathBuf, skip_cyclic: bool) -> anyhow::Result<Vec<OpenAPIData>> {
+/// If a file with one of the `shared_schema_names` stems exists, e.g. `shared-schemas` or `shared`,
+/// it is merge with each file before being validated
+///
+/// The merged document is written to `print_merged` before anything is validated, `-` writes it
+/// to stdout
+///
+/// Each document's results are read from and written to `cache`, if set, so unchanged documents
+/// aren't validated again
+pub fn validate_open_api(
+  api_path: PathBuf,
+  options: &OpenApiOptions,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<OpenAPIData>> {
   info!("Validating OpenAPI documents");
+  let OpenApiOptions {
+    api_glob,
+    skip_cyclic,
+    strict_bodies,
+    strict_integration,
+    strict_responses,
+    check_cors,
+    require_security,
+    require_tags,
+    shared_schema_names,
+    api_extensions,
+    print_merged,
+    cache,
+  } = *options;
 
-  let mut files = find_files(api_path.as_path(), OsStr::new("yml"));
-  files.append(&mut find_files(api_path.as_path(), OsStr::new("yaml")));
+  let files = api_files(&api_path, api_glob, api_extensions)?;
   let mut tags = Vec::new();
-  let mut valid = true;
-  let shared = files.iter().find(|file| {
-    let file_name = file
-      .file_stem()
-      .expect("Failed to get file name")
-      .to_str()
-      .expect("Failed to convert file name to string");
-    file_name == "shared-schemas" || file_name == "shared"
-  });
-  for file in &files {
-    debug!(
-      "Validating OpenAPI document {:?}",
-      file.file_name().expect("Failed to get file name")
+  let failures = findings.failures();
+  let shared = find_shared_file(&files, shared_schema_names);
+  if let (Some(shared), 1) = (shared, files.len()) {
+    return Err(anyhow!(
+      "Only the shared OpenAPI document {:?} was found under {:?}",
+      shared,
+      api_path
+    ));
+  }
+  if let Some(print_merged) = print_merged {
+    let merged_content = merge(
+      files
+        .iter()
+        .map(|file| open_file(file.to_path_buf()))
+        .collect::<anyhow::Result<_>>()?,
     );
-    if let Some(shared) = shared {
-      if file == shared {
-        continue;
+    write_merged(&merged_content, print_merged)?;
+  }
+  let validate = |file: &PathBuf| {
+    let mut reported = Vec::new();
+    let mut sink = |finding| reported.push(finding);
+    let file_tags = validate_document(
+      file,
+      shared,
+      skip_cyclic,
+      cache,
+      &mut Findings::with_sink(&mut sink),
+    )
+    .map(|file_tags| {
+      file_tags
+        .into_iter()
+        .map(|tag| (file.clone(), tag))
+        .collect()
+    });
+    (reported, file_tags)
+  };
+  let streaming = findings.streaming();
+  let mut report = |(reported, file_tags): (Vec<Finding>, Option<Vec<(PathBuf, OApiTag)>>)| {
+    reported
+      .into_iter()
+      .for_each(|finding| findings.push(finding));
+    if let Some(mut file_tags) = file_tags {
+      tags.append(&mut file_tags);
+    }
+    !findings.should_stop() && !findings.is_limit_reached()
+  };
+  let validated: Vec<&PathBuf> = files.iter().filter(|file| Some(*file) != shared).collect();
+  if rayon::current_num_threads() == 1 {
+    // With a single thread the documents are validated one at a time so none are validated after
+    // validation should stop
+    for file in validated {
+      if !report(validate(file)) {
+        break;
       }
-      let shared_contents = open_file(shared.to_path_buf());
-      let file_contents = open_file(file.to_path_buf());
-      let merged_content = merge(vec![shared_contents, file_contents]);
-      let merged_file = temp_file::with_contents(merged_content.as_bytes());
-      validate_file(
-        merged_file.path().to_path_buf(),
-        file.to_path_buf(),
-        &mut tags,
-        &mut valid,
-        skip_cyclic,
-      );
-    } else {
-      validate_file(
-        PathBuf::from_iter([
-          std::env::current_dir().expect("Failed to get current directory"),
-          file.to_path_buf(),
-        ]),
-        file.to_path_buf(),
-        &mut tags,
-        &mut valid,
-        skip_cyclic,
-      );
-    };
+    }
+  } else {
+    // The documents are validated concurrently and the documents that haven't started once
+    // validation should stop are skipped. Their findings are reported as soon as they're validated
+    // when streaming, in the order the documents finish, or else in the order of the files so the
+    // output doesn't depend on the threads
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+      scope.spawn(|| {
+        validated
+          .par_iter()
+          .enumerate()
+          .for_each_with(sender, |sender, (index, file)| {
+            if !stop.load(Ordering::Relaxed) {
+              let _ = sender.send((index, validate(file)));
+            }
+          })
+      });
+      let mut pending = BTreeMap::new();
+      let mut next = 0;
+      for (index, result) in receiver {
+        let proceed = if streaming {
+          report(result)
+        } else {
+          pending.insert(index, result);
+          let mut proceed = true;
+          while let Some(result) = pending.remove(&next) {
+            next += 1;
+            proceed = report(result);
+            if !proceed {
+              break;
+            }
+          }
+          proceed
+        };
+        if !proceed {
+          stop.store(true, Ordering::Relaxed);
+          break;
+        }
+      }
+    });
   }
 
-  if !valid {
+  let documents: Vec<(&Path, String)> = files
+    .iter()
+    .filter(|file| Some(*file) != shared)
+    .filter_map(|file| Some((file.as_path(), open_file(file.to_path_buf()).ok()?)))
+    .collect();
+  for finding in find_info_version_issues(&documents) {
+    findings.push(finding);
+  }
+  for finding in find_conflicting_titles(&documents) {
+    findings.push(finding);
+  }
+  // Path keys that only differ by a trailing slash or parameter name are checked before merging so
+  // they're reported in the document that has them
+  for finding in find_documents_duplicate_routes(&documents) {
+    findings.push(finding);
+  }
+  // The shared document is merged into every other document so its version has to match too
+  let versioned: Vec<(&Path, String)> = shared
+    .and_then(|shared| Some((shared.as_path(), open_file(shared.to_path_buf()).ok()?)))
+    .into_iter()
+    .chain(documents.iter().cloned())
+    .collect();
+  for finding in find_conflicting_openapi_versions(&versioned) {
+    findings.push(finding);
+  }
+  find_conflicting_operations(&documents)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+
+  if findings.failures() > failures {
     return Err(anyhow!("Invalid OpenAPI documents"));
   }
 
   debug!("Validating tags");
-  if tags.len() > 1 {
-    let mut index = 0;
-    while index < tags.len() - 1 {
-      let tag = tags.get(index).expect("Failed to get tag");
-      let mut j = index + 1;
-      while j < tags.len() {
-        let t = tags.get(j).expect("Failed to get tag");
-        if tag.name() == t.name() && tag.description() == t.description() {
-          valid = false;
-          error!(
-            "Duplicate tags: Name: {}\nDescription: {:?}",
-            t.name(),
-            t.description()
-          );
+  find_duplicate_tags(&tags)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  if findings.failures() > failures {
+    return Err(anyhow!("Duplicate tags"));
+  }
+
+  let (merged_content, merged_file) = if files.len() > 1 {
+    info!("Validating combined OpenAPI documents");
+    let mut files_content = Vec::new();
+    for file in &files {
+      files_content.push(open_file(file.to_path_buf())?);
+    }
+    let merged_content = merge(files_content.clone());
+    if let Some(finding) = check_merged_paths(&files_content, &merged_content) {
+      findings.push(finding);
+    }
+    let merged_file = temp_file::with_contents(merged_content.as_bytes());
+    (merged_content, Some(merged_file))
+  } else {
+    let file = files.first().expect("Failed to get file path");
+    (open_file(file.to_path_buf())?, None)
+  };
+  if check_cors {
+    for finding in find_missing_cors_options(&serde_yaml::from_str(&merged_content)?) {
+      findings.push(finding);
+    }
+  }
+  if require_security {
+    for finding in find_missing_security(&serde_yaml::from_str(&merged_content)?) {
+      findings.push(finding);
+    }
+  }
+  if strict_responses {
+    for finding in find_non_json_responses(&serde_yaml::from_str(&merged_content)?) {
+      findings.push(finding);
+    }
+  }
+  if require_tags {
+    find_untagged_operations(&serde_yaml::from_str(&merged_content)?)
+      .into_iter()
+      .for_each(|finding| findings.push(finding));
+    if findings.failures() > failures {
+      return Err(anyhow!("Untagged operations"));
+    }
+  }
+  // A single document was already checked when it was validated on its own
+  let Some(merged_file) = merged_file else {
+    return extract_api_data(
+      merged_content,
+      &files,
+      strict_bodies,
+      strict_integration,
+      findings,
+    );
+  };
+  match SparseRoot::new_from_file(merged_file.path().to_path_buf()) {
+    Ok(s) => {
+      let doc: OApi = OApi::new(s);
+
+      if let Err(e) = doc.check() {
+        findings.push(Finding::error(
+          FindingCode::InvalidDocument,
+          format!("The combined OpenAPI document is not valid: {}", e),
+        ));
+        if findings.failures() > failures {
+          return Err(anyhow!("Invalid OpenAPI documents"));
+        }
+      }
+      Ok(extract_api_data(
+        merged_content,
+        &files,
+        strict_bodies,
+        strict_integration,
+        findings,
+      )?)
+    }
+    Err(e) => match e {
+      SparseError::CyclicRef => {
+        if skip_cyclic {
+          findings.push(Finding::warning(
+            FindingCode::CyclicRef,
+            format!("Merged API document was not able to be parsed: {}", e),
+          ));
+          Ok(extract_api_data(
+            merged_content,
+            &files,
+            strict_bodies,
+            strict_integration,
+            findings,
+          )?)
+        } else {
+          Err(anyhow!(
+            "Merged API document was not able to be parsed: {}",
+            e
+          ))
         }
-        j += 1;
       }
-      index += 1;
+      _ => Err(anyhow!(
+        "Failed to validate combined OpenAPI documents: {}",
+        e
+      )),
+    },
+  }
+}
+
+/// Validates a single OpenAPI document that isn't in a file, e.g. one piped to stdin, and extracts
+/// the API data
+///
+/// `source` names the document in the findings
+pub fn validate_open_api_document(
+  content: String,
+  source: &std::path::Path,
+  skip_cyclic: bool,
+  strict_bodies: bool,
+  strict_integration: bool,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<OpenAPIData>> {
+  info!("Validating OpenAPI document");
+  let file = temp_file::with_contents(content.as_bytes());
+  let failures = findings.failures();
+  let tags: Vec<(PathBuf, OApiTag)> = validate_file(
+    file.path().to_path_buf(),
+    source.to_path_buf(),
+    skip_cyclic,
+    findings,
+  )
+  .unwrap_or_default()
+  .into_iter()
+  .map(|tag| (source.to_path_buf(), tag))
+  .collect();
+  if findings.failures() > failures {
+    return Err(anyhow!("Invalid OpenAPI document"));
+  }
+  for finding in find_info_version_issues(&[(source, content.clone())]) {
+    findings.push(finding);
+  }
+  for finding in find_documents_duplicate_routes(&[(source, content.clone())]) {
+    findings.push(finding);
+  }
+  find_duplicate_tags(&tags)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  if findings.failures() > failures {
+    return Err(anyhow!("Duplicate tags"));
+  }
+  extract_api_data(
+    content,
+    &[source.to_path_buf()],
+    strict_bodies,
+    strict_integration,
+    findings,
+  )
+}
+
+/// Validates each OpenAPI document on its own and extracts its API data, without merging the
+/// documents or merging a shared document into them, for documents that are self-contained
+///
+/// The optional checks are the same as `validate_open_api`'s but per document, the shared schema
+/// names and `print_merged` are ignored
+pub fn validate_open_api_separately(
+  api_path: &Path,
+  options: &OpenApiOptions,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<(PathBuf, Vec<OpenAPIData>)>> {
+  info!("Validating OpenAPI documents separately");
+  let OpenApiOptions {
+    api_glob,
+    skip_cyclic,
+    api_extensions,
+    cache,
+    ..
+  } = *options;
+  let files = api_files(api_path, api_glob, api_extensions)?;
+  let mut documents = Vec::new();
+  let failures = findings.failures();
+  for file in files {
+    if findings.should_stop() {
+      break;
+    }
+    let document_failures = findings.failures();
+    let tags: Vec<(PathBuf, OApiTag)> =
+      validate_document(&file, None, skip_cyclic, cache, findings)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tag| (file.clone(), tag))
+        .collect();
+    find_duplicate_tags(&tags)
+      .into_iter()
+      .for_each(|finding| findings.push(finding.in_file(&file)));
+    if findings.failures() > document_failures {
+      continue;
+    }
+    let content = match open_file(file.clone()) {
+      Ok(content) => content,
+      Err(e) => {
+        findings
+          .push(Finding::error(FindingCode::UnreadableDocument, e.to_string()).in_file(&file));
+        continue;
+      }
+    };
+    for finding in find_info_version_issues(&[(file.as_path(), content.clone())]) {
+      findings.push(finding);
+    }
+    for finding in find_documents_duplicate_routes(&[(file.as_path(), content.clone())]) {
+      findings.push(finding);
+    }
+    if let Some(data) = check_document(&file, content, options, findings) {
+      documents.push((file, data));
     }
+  }
+  if findings.failures() > failures {
+    return Err(anyhow!("Invalid OpenAPI documents"));
+  }
+  Ok(documents)
+}
 
-    if !valid {
-      return Err(anyhow!("Duplicate tags"));
+/// Validates only the listed OpenAPI documents, each merged with the shared document found under
+/// `root` if there is one, and runs the optional checks of `options` on each of them
+///
+/// Listed shared documents are skipped since they're merged into the other documents rather than
+/// validated on their own
+pub fn validate_open_api_files(
+  files: &[PathBuf],
+  root: &Path,
+  options: &OpenApiOptions,
+  findings: &mut Findings,
+) -> anyhow::Result<()> {
+  info!("Validating {} OpenAPI documents", files.len());
+  let root_files = find_api_files(root, options.api_extensions);
+  let shared = find_shared_file(&root_files, options.shared_schema_names);
+  let mut tags = Vec::new();
+  let mut documents = Vec::new();
+  let failures = findings.failures();
+  for file in files {
+    if find_shared_file(std::slice::from_ref(file), options.shared_schema_names).is_some() {
+      debug!("Skipping the shared OpenAPI document {:?}", file);
+      continue;
+    }
+    if findings.should_stop() {
+      break;
     }
+    let document_failures = findings.failures();
+    if let Some(file_tags) =
+      validate_document(file, shared, options.skip_cyclic, options.cache, findings)
+    {
+      tags.extend(file_tags.into_iter().map(|tag| (file.clone(), tag)));
+    }
+    if findings.failures() > document_failures {
+      continue;
+    }
+    let content = match open_file(file.clone()) {
+      Ok(content) => content,
+      Err(e) => {
+        findings.push(Finding::error(FindingCode::UnreadableDocument, e.to_string()).in_file(file));
+        continue;
+      }
+    };
+    // The shared document is merged in so the references to its components resolve
+    let merged_content = match shared.map(|shared| open_file(shared.to_path_buf())) {
+      Some(Ok(shared_content)) => merge(vec![shared_content, content.clone()]),
+      _ => content.clone(),
+    };
+    check_document(file, merged_content, options, findings);
+    documents.push((file.as_path(), content));
+  }
+  for finding in find_info_version_issues(&documents) {
+    findings.push(finding);
+  }
+  for finding in find_conflicting_titles(&documents) {
+    findings.push(finding);
   }
+  for finding in find_documents_duplicate_routes(&documents) {
+    findings.push(finding);
+  }
+  if findings.failures() > failures {
+    return Err(anyhow!("Invalid OpenAPI documents"));
+  }
+  find_duplicate_tags(&tags)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  if findings.failures() > failures {
+    return Err(anyhow!("Duplicate tags"));
+  }
+  Ok(())
+}
 
-  if files.len() > 1 {
-    info!("Validating combined OpenAPI documents");
-    let mut files_content = Vec::new();
-    for file in files {
-      files_content.push(open_file(file));
+/// Runs the optional checks of `options` on a single document and extracts its API data, or
+/// returns `None` when the operations without tags fail validation or the data can't be extracted
+fn check_document(
+  file: &Path,
+  content: String,
+  options: &OpenApiOptions,
+  findings: &mut Findings,
+) -> Option<Vec<OpenAPIData>> {
+  let failures = findings.failures();
+  let doc: serde_yaml::Value = match serde_yaml::from_str(&content) {
+    Ok(doc) => doc,
+    Err(e) => {
+      findings.push(Finding::error(FindingCode::UnparsableDocument, e.to_string()).in_file(file));
+      return None;
     }
-    let merged_content = merge(files_content);
-    let merged_file = temp_file::with_contents(merged_content.as_bytes());
-    match SparseRoot::new_from_file(merged_file.path().to_path_buf()) {
-      Ok(s) => {
-        let doc: OApi = OApi::new(s);
-
-        doc.check().expect("not to have logic errors");
-        Ok(extract_api_data(merged_content)?)
-      }
-      Err(e) => match e {
-        SparseError::CyclicRef => {
-          if skip_cyclic {
-            warn!("Merged API document was not able to be parsed: {}", e);
-            Ok(extract_api_data(merged_content)?)
-          } else {
-            Err(anyhow!(
-              "Merged API document was not able to be parsed: {}",
-              e
-            ))
-          }
+  };
+  if options.check_cors {
+    for finding in find_missing_cors_options(&doc) {
+      findings.push(finding.in_file(file));
+    }
+  }
+  if options.require_security {
+    for finding in find_missing_security(&doc) {
+      findings.push(finding.in_file(file));
+    }
+  }
+  if options.strict_responses {
+    for finding in find_non_json_responses(&doc) {
+      findings.push(finding.in_file(file));
+    }
+  }
+  if options.require_tags {
+    find_untagged_operations(&doc)
+      .into_iter()
+      .for_each(|finding| findings.push(finding.in_file(file)));
+    if findings.failures() > failures {
+      return None;
+    }
+  }
+  match profile::time("extract_api_data", Some(file), || {
+    extract_api_data(
+      content,
+      &[file.to_path_buf()],
+      options.strict_bodies,
+      options.strict_integration,
+      findings,
+    )
+  }) {
+    Ok(data) => Some(data),
+    Err(e) => {
+      findings.push(Finding::error(FindingCode::InvalidDocument, e.to_string()).in_file(file));
+      None
+    }
+  }
+}
+
+/// Validates the document, merged with the shared document if there is one, and returns its tags,
+/// or `None` if it is invalid
+fn validate_document(
+  file: &Path,
+  shared: Option<&PathBuf>,
+  skip_cyclic: bool,
+  cache: Option<&ValidationCache>,
+  findings: &mut Findings,
+) -> Option<Vec<OApiTag>> {
+  debug!(
+    "Validating OpenAPI document {:?}",
+    file.file_name().expect("Failed to get file name")
+  );
+  if let Some(shared) = shared {
+    let (shared_contents, file_contents) = match open_file(shared.to_path_buf())
+      .and_then(|shared_contents| Ok((shared_contents, open_file(file.to_path_buf())?)))
+    {
+      Ok(contents) => contents,
+      Err(e) => {
+        findings.push(Finding::error(FindingCode::UnreadableDocument, e.to_string()).in_file(file));
+        return None;
+      }
+    };
+    let merged_content = merge(vec![shared_contents, file_contents]);
+    let validate = |findings: &mut Findings| {
+      let merged_file = temp_file::with_contents(merged_content.as_bytes());
+      profile::time("validate_document", Some(file), || {
+        validate_file(
+          merged_file.path().to_path_buf(),
+          file.to_path_buf(),
+          skip_cyclic,
+          findings,
+        )
+      })
+    };
+    match cache {
+      Some(cache) => cache.validate(file, &merged_content, skip_cyclic, findings, validate),
+      None => validate(findings),
+    }
+  } else {
+    let validate = |findings: &mut Findings| {
+      profile::time("validate_document", Some(file), || {
+        validate_file(
+          PathBuf::from_iter([
+            std::env::current_dir().expect("Failed to get current directory"),
+            file.to_path_buf(),
+          ]),
+          file.to_path_buf(),
+          skip_cyclic,
+          findings,
+        )
+      })
+    };
+    match cache {
+      Some(cache) => match open_file(file.to_path_buf()) {
+        Ok(contents) => cache.validate(file, &contents, skip_cyclic, findings, validate),
+        Err(e) => {
+          findings
+            .push(Finding::error(FindingCode::UnreadableDocument, e.to_string()).in_file(file));
+          None
         }
-        _ => Err(anyhow!(
-          "Failed to validate combined OpenAPI documents: {}",
-          e
-        )),
       },
+      None => validate(findings),
     }
-  } else {
-    Ok(extract_api_data(open_file(
-      files
-        .first()
-        .expect("Failed to get file path")
-        .to_path_buf(),
-    ))?)
   }
 }
 
-/// Validates the file
+/// Validates the file and returns its tags, or `None` if the file is invalid
+///
+/// Nothing is shared between calls so files can be validated concurrently and their tags
+/// combined afterwards
 fn validate_file(
   path: PathBuf,
   file: PathBuf,
-  tags: &mut Vec<OApiTag>,
-  valid: &mut bool,
   skip_cyclic: bool,
-) {
+  findings: &mut Findings,
+) -> Option<Vec<OApiTag>> {
   match SparseRoot::new_from_file(path) {
     Ok(open_api_doc) => {
       let doc: OApi = OApi::new(open_api_doc);
       if let Err(e) = doc.check() {
-        *valid = false;
-        error!(
-          "API document {:?} is not valid: {}",
-          file.file_name().expect("Failed to get file name"),
-          e
+        findings.push(
+          Finding::error(
+            FindingCode::InvalidDocument,
+            format!(
+              "API document {:?} is not valid: {}",
+              file.file_name().expect("Failed to get file name"),
+              e
+            ),
+          )
+          .in_file(&file),
         );
+        None
       } else {
         debug!(
           "API document {:?} is valid",
           file.file_name().expect("Failed to get file name")
         );
         let root = doc.root_get().expect("Failed to get OpenAPI root");
-        if let Some(file_tags) = root.tags() {
-          tags.append(&mut file_tags.clone());
-        }
+        Some(root.tags().clone().unwrap_or_default())
       }
     }
     Err(e) => match e {
-      SparseError::CyclicRef => {
-        if skip_cyclic {
-          warn!(
-            "API document {:?} was not able to be parsed: {}",
-            file.file_name().expect("Failed to get file name"),
-            e
-          );
-        } else {
-          *valid = false;
-          error!(
-            "API document {:?} was not able to be parsed: {}",
-            file.file_name().expect("Failed to get file name"),
-            e
-          );
-        }
+      SparseError::CyclicRef if skip_cyclic => {
+        findings.push(
+          Finding::warning(
+            FindingCode::CyclicRef,
+            format!(
+              "API document {:?} was not able to be parsed: {}",
+              file.file_name().expect("Failed to get file name"),
+              e
+            ),
+          )
+          .in_file(&file),
+        );
+        Some(Vec::new())
       }
       _ => {
-        *valid = false;
-        error!(
-          "API document {:?} was not able to be parsed: {}",
-          file.file_name().expect("Failed to get file name"),
-          e
+        findings.push(
+          Finding::error(
+            FindingCode::UnparsableDocument,
+            format!(
+              "API document {:?} was not able to be parsed: {}",
+              file.file_name().expect("Failed to get file name"),
+              e
+            ),
+          )
+          .in_file(&file),
         );
+        None
       }
     },
   }
 }
 
-/// Gets a file's contents
-fn open_file(filename: PathBuf) -> String {
-  let mut file = std::fs::File::open(filename).expect("Couldn't find or open the file");
+/// Finds tags that are declared more than once with the same description
+///
+/// Tags are grouped by name so each duplicate is reported once along with how many times it was
+/// declared and the files it was declared in
+pub fn find_duplicate_tags(tags: &[(PathBuf, OApiTag)]) -> Vec<Finding> {
+  let mut grouped: HashMap<&String, Vec<(&Option<String>, &PathBuf)>> = HashMap::new();
+  for (file, tag) in tags {
+    grouped
+      .entry(tag.name())
+      .or_default()
+      .push((tag.description(), file));
+  }
+  let mut names: Vec<_> = grouped.keys().copied().collect();
+  names.sort();
+  let mut findings = Vec::new();
+  for name in names {
+    let mut counts: Vec<(&Option<String>, usize, Vec<&PathBuf>)> = Vec::new();
+    for (description, file) in &grouped[name] {
+      match counts.iter_mut().find(|(d, _, _)| d == description) {
+        Some((_, count, files)) => {
+          *count += 1;
+          if !files.contains(file) {
+            files.push(file);
+          }
+        }
+        None => counts.push((description, 1, vec![file])),
+      }
+    }
+    for (description, count, files) in counts.into_iter().filter(|(_, count, _)| *count > 1) {
+      let files: Vec<String> = files.iter().map(|file| format!("{:?}", file)).collect();
+      findings.push(Finding::error(
+        FindingCode::DuplicateTag,
+        format!(
+          "Duplicate tags: Name: {} is declared {} times in {} {}: {}\nDescription: {:?}",
+          name,
+          count,
+          files.len(),
+          if files.len() == 1 { "file" } else { "files" },
+          files.join(", "),
+          description
+        ),
+      ));
+    }
+  }
+  findings
+}
+
+/// Gets a file's contents, failing if it can't be read or isn't UTF-8
+fn open_file(filename: PathBuf) -> anyhow::Result<String> {
+  let mut file = std::fs::File::open(&filename).map_err(|e| {
+    SvError::Io(format!(
+      "Couldn't find or open the file {:?}: {}",
+      filename, e
+    ))
+  })?;
   let mut contents = String::new();
   file
     .read_to_string(&mut contents)
-    .expect("Couldn't read the contents of the file");
-  contents
+    .map_err(|e| match e.kind() {
+      std::io::ErrorKind::InvalidData => {
+        SvError::Io(format!("The file {:?} is not valid UTF-8", filename))
+      }
+      _ => SvError::Io(format!(
+        "Couldn't read the contents of the file {:?}: {}",
+        filename, e
+      )),
+    })?;
+  Ok(contents)
 }
 
 fn merge(files: Vec<String>) -> String {
@@ -249,53 +823,307 @@ fn merge(files: Vec<String>) -> String {
   hash.to_string()
 }
 
+/// Writes the merged OpenAPI document to the file, or stdout for `-`
+fn write_merged(content: &str, path: &std::path::Path) -> anyhow::Result<()> {
+  if path == std::path::Path::new("-") {
+    println!("{}", content);
+  } else {
+    info!("Writing the merged OpenAPI document to {:?}", path);
+    std::fs::write(path, content).map_err(|e| {
+      anyhow!(
+        "Failed to write the merged OpenAPI document to {:?}: {}",
+        path,
+        e
+      )
+    })?;
+  }
+  Ok(())
+}
+
+/// Finds the shared OpenAPI document that is merged into the other documents
+fn find_shared_file<'a>(
+  files: &'a [PathBuf],
+  shared_schema_names: &[String],
+) -> Option<&'a PathBuf> {
+  files.iter().find(|file| {
+    let file_name = file
+      .file_stem()
+      .expect("Failed to get file name")
+      .to_str()
+      .expect("Failed to convert file name to string");
+    shared_schema_names.iter().any(|name| name == file_name)
+  })
+}
+
+/// Extracts the API data from an OpenAPI document, or the merged documents in a folder, without
+/// validating them
+pub fn read_api_data(
+  path: &std::path::Path,
+  api_extensions: &[String],
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<OpenAPIData>> {
+  let files = if path.is_dir() {
+    let files = find_api_files(path, api_extensions);
+    if files.is_empty() {
+      return Err(SvError::Path(format!("No OpenAPI documents found in {:?}", path)).into());
+    }
+    files
+  } else {
+    vec![path.to_path_buf()]
+  };
+  for file in &files {
+    check_syntax(file)?;
+  }
+  let content = merge(
+    files
+      .iter()
+      .map(|file| open_file(file.to_path_buf()))
+      .collect::<anyhow::Result<_>>()?,
+  );
+  extract_api_data(content, &files, false, false, findings)
+}
+
+/// Checks that the file is valid YAML, reporting the file and the line and column of the error
+fn check_syntax(file: &std::path::Path) -> anyhow::Result<()> {
+  let contents =
+    std::fs::read_to_string(file).map_err(|e| anyhow!("Failed to read {:?}: {}", file, e))?;
+  serde_yaml::from_str::<serde_yaml::Value>(&contents)
+    .map_err(|e| anyhow!("Failed to parse {:?}: {}", file, e))?;
+  Ok(())
+}
+
+/// Parses the OpenAPI document that was read or merged from `sources`
+///
+/// The line and column of an error in a merged document don't match the documents it came from,
+/// so the error names the source document with the syntax error, or the sources when there are
+/// several and they are all valid YAML
+fn parse_document(content: &str, sources: &[PathBuf]) -> anyhow::Result<openapiv3::OpenAPI> {
+  serde_yaml::from_str(content).map_err(|e| {
+    if let Some(Err(file_error)) = sources
+      .iter()
+      .map(|source| check_syntax(source))
+      .find(|result| result.is_err())
+    {
+      return file_error;
+    }
+    match sources {
+      [] => anyhow!("Failed to parse the OpenAPI document: {}", e),
+      [source] => anyhow!("Failed to parse {:?}: {}", source, e),
+      _ => anyhow!(
+        "Failed to parse the OpenAPI document merged from {:?}: {}",
+        sources,
+        e
+      ),
+    }
+  })
+}
+
+/// Gets the path keys defined in an OpenAPI document
+pub(crate) fn path_keys(content: &str) -> Vec<String> {
+  match serde_yaml::from_str::<serde_yaml::Value>(content) {
+    Ok(doc) => doc
+      .get("paths")
+      .and_then(|paths| paths.as_mapping())
+      .map(|paths| {
+        paths
+          .keys()
+          .filter_map(|key| key.as_str().map(|key| key.to_string()))
+          .collect()
+      })
+      .unwrap_or_default(),
+    Err(_) => Vec::new(),
+  }
+}
+
+/// Checks that the merged document has as many paths as the documents it was merged from
+///
+/// When documents define the same path, the merge combines them into one and the definitions may
+/// overwrite each other
+fn check_merged_paths(files_content: &[String], merged_content: &str) -> Option<Finding> {
+  let mut expected = Vec::new();
+  let mut collisions = Vec::new();
+  for content in files_content {
+    for path in path_keys(content) {
+      if expected.contains(&path) && !collisions.contains(&path) {
+        collisions.push(path.clone());
+      }
+      expected.push(path);
+    }
+  }
+  let merged = path_keys(merged_content).len();
+  if merged == expected.len() {
+    return None;
+  }
+  Some(Finding::warning(
+    FindingCode::MergedPathCollision,
+    format!(
+      "The merged OpenAPI document has {} paths but the documents define {} in total, these paths are defined in more than one document and may have been overwritten: {}",
+      merged,
+      expected.len(),
+      collisions.join(", ")
+    ),
+  ))
+}
+
+/// Finds the OpenAPI documents in the directory recursively, the files with one of the default
+/// extensions or of `extensions`, e.g. `openapi` or `.openapi`
+pub fn find_api_files(path: &Path, extensions: &[String]) -> Vec<PathBuf> {
+  api_file_extensions(extensions)
+    .into_iter()
+    .flat_map(|extension| find_files(path, OsStr::new(extension)))
+    .collect()
+}
+
+/// The OpenAPI documents matching the glob when it's set, otherwise the documents in the folder,
+/// failing when there are none
+pub fn api_files(
+  api_path: &Path,
+  api_glob: Option<&str>,
+  extensions: &[String],
+) -> anyhow::Result<Vec<PathBuf>> {
+  let files = match api_glob {
+    Some(glob) => {
+      let all = api_file_extensions(extensions);
+      let files: Vec<PathBuf> = expand_glob(glob)?
+        .into_iter()
+        .filter(|file| {
+          file
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| all.contains(&extension))
+        })
+        .collect();
+      if files.is_empty() {
+        return Err(SvError::Path(format!("No OpenAPI documents match {}", glob)).into());
+      }
+      files
+    }
+    None => find_api_files(api_path, extensions),
+  };
+  if files.is_empty() {
+    return Err(SvError::Path(format!("No OpenAPI documents found under {:?}", api_path)).into());
+  }
+  Ok(files)
+}
+
+/// The default extensions of the OpenAPI documents followed by `extensions` without their `.`
+fn api_file_extensions(extensions: &[String]) -> Vec<&str> {
+  let mut all: Vec<&str> = DEFAULT_API_EXTENSIONS.to_vec();
+  for extension in extensions.iter().map(|e| e.trim_start_matches('.')) {
+    if !all.contains(&extension) {
+      all.push(extension);
+    }
+  }
+  all
+}
+
 /// Finds all the files with the extension in the directory recursively
-fn find_files(path: &std::path::Path, extension: &OsStr) -> Vec<PathBuf> {
+pub(crate) fn find_files(path: &std::path::Path, extension: &OsStr) -> Vec<PathBuf> {
   debug!("Finding files in {:?}", path);
   let mut files = Vec::new();
-  for entry in path.read_dir().expect("Failed to read directory").flatten() {
+  for entry in walk_dir(path).build().flatten() {
     if entry.path().is_dir() {
       debug!("Found directory {:?}", entry.path());
-      files.append(&mut find_files(&entry.path(), extension));
     } else if entry.path().extension() == Some(extension) {
       debug!("Found file {:?}", entry.path());
-      files.push(entry.path());
+      files.push(entry.into_path());
     }
   }
   files
 }
 
 /// Extracts the API data from the OpenAPI operation
+///
+/// The parameters and `x-amazon-apigateway-integration` extension of the `path_item` apply to the
+/// operations that don't set their own
+#[allow(clippy::too_many_arguments)]
 fn extract_api_data_for_item(
   item: &openapiv3::Operation,
   path: &str,
   method: HttpMethod,
+  strict_bodies: bool,
+  strict_integration: bool,
+  path_item: &openapiv3::PathItem,
+  components: Option<&openapiv3::Components>,
+  findings: &mut Findings,
 ) -> anyhow::Result<OpenAPIData> {
   debug!("Method: {}", method);
   let aws = item
     .extensions
     .get("x-amazon-apigateway-integration")
-    .expect("Expected 'x-amazon-apigateway-integration' extension");
-  let uri = aws
-    .get("uri")
-    .expect("Expected 'uri' in 'x-amazon-apigateway-integration' extension");
+    .or_else(|| path_item.extensions.get("x-amazon-apigateway-integration"))
+    .ok_or_else(|| {
+      anyhow!(
+        "Expected 'x-amazon-apigateway-integration' extension for {} {}",
+        method,
+        path
+      )
+    })?;
+  let uri = aws.get("uri").ok_or_else(|| {
+    anyhow!(
+      "Expected 'uri' in 'x-amazon-apigateway-integration' extension for {} {}",
+      method,
+      path
+    )
+  })?;
   let uri_path = uri.as_str().expect("Failed to convert URI to string");
   debug!("URI: {}", uri_path);
   match method {
     HttpMethod::Get => {}
     HttpMethod::Post | HttpMethod::Put | HttpMethod::Patch => {
-      if item.request_body.is_none() && item.parameters.is_empty() {
-        warn!("The {} method for {} does not have a request body or parameters (queries)", method.to_string(), path);
+      let has_parameters = item
+        .parameters
+        .iter()
+        .chain(&path_item.parameters)
+        .any(|parameter| resolve_parameter(parameter, components).is_some());
+      if item.request_body.is_none() && !has_parameters {
+        findings.push(Finding::warning(
+          FindingCode::PostNoBody,
+          format!("The {} method for {} does not have a request body or parameters (queries)", method, path),
+        ).for_operation(&method, path));
+      }
+      if strict_bodies && is_request_body_optional(item, components) {
+        findings.push(Finding::warning(
+          FindingCode::OptionalRequestBody,
+          format!("The request body for the {} method for {} is not marked as required", method, path),
+        ).for_operation(&method, path));
       }
     },
-    HttpMethod::Delete => {}
-    HttpMethod::Options => warn!("Double check if OPTIONS method for {} should have a request body and/or parameters (queries)", path),
-    x => return Err(anyhow!("Http method should not be used: {}", x.to_string())),
+    HttpMethod::Delete | HttpMethod::Head => {}
+    HttpMethod::Options => findings.push(Finding::warning(
+      FindingCode::OptionsMethod,
+      format!("Double check if OPTIONS method for {} should have a request body and/or parameters (queries)", path),
+    ).for_operation(&method, path)),
+    HttpMethod::Trace | HttpMethod::Connect => {
+      return Err(anyhow!(
+        "The {} method for {} is not supported by API Gateway, remove it from the OpenAPI document",
+        method,
+        path
+      ))
+    }
+    HttpMethod::Any => {
+      return Err(anyhow!(
+        "The {} method for {} is not a valid OpenAPI method",
+        method,
+        path
+      ))
+    }
   }
+  let gateway = integration_gateway(aws);
+  debug!("API Gateway type: {}", gateway);
   let api_type = match uri_path {
     x if x.contains("states:action") => ExecutionType::StepFunction,
     x if x.contains("lambda:path") => ExecutionType::Lambda,
     x if x.contains("sqs:action") => ExecutionType::SQS,
+    // An HTTP API integration invokes a lambda by its ARN, unless it's an AWS service
+    // integration with an `integrationSubtype`, e.g. `SQS-SendMessage`
+    _ if gateway == GatewayType::Http => {
+      match aws.get("integrationSubtype").and_then(|x| x.as_str()) {
+        Some(subtype) if subtype.starts_with("StepFunctions-") => ExecutionType::StepFunction,
+        Some(subtype) if subtype.starts_with("SQS-") => ExecutionType::SQS,
+        _ => ExecutionType::Lambda,
+      }
+    }
     _ => {
       return Err(anyhow!(
         "Unknown execution type for URI: {}",
@@ -304,49 +1132,1149 @@ fn extract_api_data_for_item(
     }
   };
   debug!("API execution type: {}", api_type);
+  if strict_integration {
+    for finding in find_integration_issues(aws, &method, path) {
+      findings.push(finding);
+    }
+  }
+  if api_type == ExecutionType::Lambda {
+    if let Some(integration_type) = aws.get("type").and_then(|x| x.as_str()) {
+      if !integration_type.eq_ignore_ascii_case("aws_proxy") {
+        findings.push(Finding::warning(
+          FindingCode::LambdaIntegrationType,
+          if integration_type.eq_ignore_ascii_case("aws") {
+            format!("The {} method for {} uses a non-proxy 'aws' Lambda integration, the request and response are mapped differently to 'aws_proxy'", method, path)
+          } else {
+            format!("The {} method for {} uses a '{}' integration for a Lambda, expected 'aws_proxy'", method, path, integration_type)
+          },
+        ).for_operation(&method, path));
+      }
+    }
+  }
   Ok(OpenAPIData {
     path: path.to_string(),
     method,
     uri: uri_path.to_string(),
     execution_type: api_type,
+    deprecated: item.deprecated,
+    gateway,
   })
 }
 
-/// Extract the API data from the merged file content
-fn extract_api_data(content: String) -> anyhow::Result<Vec<OpenAPIData>> {
-  let mut data = Vec::new();
-  let doc: openapiv3::OpenAPI = serde_yaml::from_str(&content)?;
-  let paths = doc.paths;
-  for (path, path_item) in paths.paths {
-    debug!("Extracting Path data: {}", path);
-    if let Some(get) = &path_item.as_item().unwrap().get {
-      data.push(extract_api_data_for_item(get, &path, HttpMethod::Get)?);
-    }
-    if let Some(post) = &path_item.as_item().unwrap().post {
-      data.push(extract_api_data_for_item(post, &path, HttpMethod::Post)?);
-    }
-    if let Some(put) = &path_item.as_item().unwrap().put {
-      data.push(extract_api_data_for_item(put, &path, HttpMethod::Put)?);
-    }
-    if let Some(patch) = &path_item.as_item().unwrap().patch {
-      data.push(extract_api_data_for_item(patch, &path, HttpMethod::Patch)?);
-    }
-    if let Some(delete) = &path_item.as_item().unwrap().delete {
-      data.push(extract_api_data_for_item(
-        delete,
-        &path,
-        HttpMethod::Delete,
-      )?);
+/// Whether the OPTIONS operation is routed to a Lambda, the CORS preflight requests are usually
+/// answered by a `mock` integration that has no Lambda or permission in the Terraform
+fn is_lambda_options(item: &openapiv3::Operation, path_item: &openapiv3::PathItem) -> bool {
+  let Some(integration) = item
+    .extensions
+    .get("x-amazon-apigateway-integration")
+    .or_else(|| path_item.extensions.get("x-amazon-apigateway-integration"))
+  else {
+    return false;
+  };
+  let integration_type = integration
+    .get("type")
+    .and_then(|x| x.as_str())
+    .unwrap_or("aws_proxy");
+  let is_aws = ["aws_proxy", "aws"]
+    .iter()
+    .any(|x| integration_type.eq_ignore_ascii_case(x));
+  match integration.get("uri").and_then(|x| x.as_str()) {
+    Some(uri) if is_aws => {
+      uri.contains("lambda:path")
+        || (integration_gateway(integration) == GatewayType::Http
+          && integration.get("integrationSubtype").is_none())
     }
+    _ => false,
   }
-  Ok(data)
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+/// The type of API an `x-amazon-apigateway-integration` is for, only the integrations of HTTP
+/// APIs have a `payloadFormatVersion`
+fn integration_gateway(integration: &serde_json::Value) -> GatewayType {
+  if integration.get("payloadFormatVersion").is_some() {
+    GatewayType::Http
+  } else {
+    GatewayType::Rest
+  }
+}
 
-  #[test]
+/// Warns about the `passthroughBehavior` and `requestParameters` of an integration that are known
+/// to cause requests to be rejected or mapped silently to nothing
+fn find_integration_issues(
+  integration: &serde_json::Value,
+  method: &HttpMethod,
+  path: &str,
+) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  if let Some(passthrough) = integration
+    .get("passthroughBehavior")
+    .and_then(|x| x.as_str())
+  {
+    let is_proxy = integration
+      .get("type")
+      .and_then(|x| x.as_str())
+      .is_some_and(|x| x.to_lowercase().ends_with("_proxy"));
+    match passthrough.to_lowercase().as_str() {
+      "when_no_match" | "when_no_templates" => {}
+      "never" => {
+        if !is_proxy && integration.get("requestTemplates").is_none() {
+          findings.push(Finding::warning(
+            FindingCode::IntegrationPassthrough,
+            format!("The {} method for {} has 'passthroughBehavior: {}' without 'requestTemplates', so every request with a body is rejected", method, path, passthrough),
+          ).for_operation(method, path));
+        }
+      }
+      _ => findings.push(Finding::warning(
+        FindingCode::IntegrationPassthrough,
+        format!("The {} method for {} has an unknown 'passthroughBehavior: {}', expected 'when_no_match', 'when_no_templates' or 'never'", method, path, passthrough),
+      ).for_operation(method, path)),
+    }
+  }
+  let parameters = match integration
+    .get("requestParameters")
+    .and_then(|x| x.as_object())
+  {
+    Some(parameters) => parameters,
+    None => return findings,
+  };
+  let route_parameters = route_parameters(path);
+  for (name, value) in parameters {
+    let valid_name = ["header", "querystring", "path"].iter().any(|location| {
+      name
+        .strip_prefix(&format!("integration.request.{}.", location))
+        .is_some_and(|name| !name.is_empty())
+    });
+    if !valid_name {
+      findings.push(Finding::warning(
+        FindingCode::IntegrationRequestParameter,
+        format!("The {} method for {} has the request parameter '{}', expected 'integration.request.header', 'querystring' or 'path' followed by the name", method, path, name),
+      ).for_operation(method, path));
+    }
+    let value = value.as_str().unwrap_or_default();
+    if let Some(parameter) = value.strip_prefix("method.request.path.") {
+      if !route_parameters.contains(&parameter) {
+        findings.push(Finding::warning(
+          FindingCode::IntegrationRequestParameter,
+          format!("The {} method for {} maps the request parameter '{}' from '{}' which isn't a parameter of the path", method, path, name, value),
+        ).for_operation(method, path));
+      }
+    } else if !["method.request.", "context.", "stageVariables."]
+      .iter()
+      .any(|prefix| value.starts_with(prefix))
+      && !is_static_value(value)
+    {
+      findings.push(Finding::warning(
+        FindingCode::IntegrationRequestParameter,
+        format!("The {} method for {} maps the request parameter '{}' from '{}', expected a 'method.request', 'context' or 'stageVariables' value or a quoted static value", method, path, name, value),
+      ).for_operation(method, path));
+    }
+  }
+  findings
+}
+
+/// Whether the request parameter value is a quoted static value, e.g. `'application/json'`
+fn is_static_value(value: &str) -> bool {
+  value.len() > 1 && value.starts_with('\'') && value.ends_with('\'')
+}
+
+/// Checks if the operation has a request body that isn't marked as `required: true`, following a
+/// reference to `components.requestBodies`
+///
+/// A reference that can't be resolved isn't reported as the body isn't known
+fn is_request_body_optional(
+  item: &openapiv3::Operation,
+  components: Option<&openapiv3::Components>,
+) -> bool {
+  let body = match &item.request_body {
+    Some(openapiv3::ReferenceOr::Item(body)) => Some(body),
+    Some(openapiv3::ReferenceOr::Reference { reference }) => {
+      let resolved = reference
+        .strip_prefix("#/components/requestBodies/")
+        .and_then(|name| components?.request_bodies.get(name))
+        .and_then(|body| body.as_item());
+      if resolved.is_none() {
+        debug!("Unable to resolve the request body {}", reference);
+      }
+      resolved
+    }
+    None => None,
+  };
+  body.is_some_and(|body| !body.required)
+}
+
+/// Extract the API data from the merged file content
+///
+/// `sources` are the files the content was read or merged from, used to report parse errors
+fn extract_api_data(
+  content: String,
+  sources: &[PathBuf],
+  strict_bodies: bool,
+  strict_integration: bool,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<OpenAPIData>> {
+  let mut data = Vec::new();
+  let doc = parse_document(&content, sources)?;
+  for finding in find_undefined_tags(&doc) {
+    findings.push(finding);
+  }
+  let failures = findings.failures();
+  find_undefined_security_schemes(&doc)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  let paths = doc.paths;
+  find_unresolved_refs(&serde_yaml::from_str(&content)?)
+    .into_iter()
+    .for_each(|finding| findings.push(finding));
+  for (path, path_item) in paths.paths {
+    debug!("Extracting Path data: {}", path);
+    let path_item = path_item.as_item().unwrap();
+    find_undeclared_path_parameters(&path, path_item, doc.components.as_ref())
+      .into_iter()
+      .for_each(|finding| findings.push(finding));
+    find_orphan_path_parameters(&path, path_item, doc.components.as_ref())
+      .into_iter()
+      .for_each(|finding| findings.push(finding));
+    if let Some(get) = &path_item.get {
+      data.push(extract_api_data_for_item(
+        get,
+        &path,
+        HttpMethod::Get,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(post) = &path_item.post {
+      data.push(extract_api_data_for_item(
+        post,
+        &path,
+        HttpMethod::Post,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(put) = &path_item.put {
+      data.push(extract_api_data_for_item(
+        put,
+        &path,
+        HttpMethod::Put,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(patch) = &path_item.patch {
+      data.push(extract_api_data_for_item(
+        patch,
+        &path,
+        HttpMethod::Patch,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(delete) = &path_item.delete {
+      data.push(extract_api_data_for_item(
+        delete,
+        &path,
+        HttpMethod::Delete,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(head) = &path_item.head {
+      data.push(extract_api_data_for_item(
+        head,
+        &path,
+        HttpMethod::Head,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(options) = path_item
+      .options
+      .as_ref()
+      .filter(|options| is_lambda_options(options, path_item))
+    {
+      data.push(extract_api_data_for_item(
+        options,
+        &path,
+        HttpMethod::Options,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+    if let Some(trace) = &path_item.trace {
+      data.push(extract_api_data_for_item(
+        trace,
+        &path,
+        HttpMethod::Trace,
+        strict_bodies,
+        strict_integration,
+        path_item,
+        doc.components.as_ref(),
+        findings,
+      )?);
+    }
+  }
+  for finding in find_proxy_shadowed_routes(&data) {
+    findings.push(finding);
+  }
+  if findings.failures() > failures {
+    return Err(anyhow!("Invalid OpenAPI document"));
+  }
+  Ok(data)
+}
+
+/// Finds the `$ref`s in the operations that point to something that doesn't exist in the document
+///
+/// References to other files can't be checked and are skipped
+fn find_unresolved_refs(doc: &serde_yaml::Value) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  let paths = match doc.get("paths").and_then(|paths| paths.as_mapping()) {
+    Some(paths) => paths,
+    None => return findings,
+  };
+  for (path, path_item) in paths {
+    let path = path.as_str().unwrap_or_default();
+    let operations = match path_item.as_mapping() {
+      Some(operations) => operations,
+      None => continue,
+    };
+    for (method, operation) in operations {
+      let method = method.as_str().unwrap_or_default();
+      let pointer = format!("/paths/{}/{}", escape_pointer(path), escape_pointer(method));
+      let label = match method.parse::<HttpMethod>() {
+        Ok(method) => format!("{} {} operation", method, path),
+        Err(_) => format!("{} path", path),
+      };
+      let mut refs = Vec::new();
+      collect_refs(operation, &pointer, &mut refs);
+      for (location, reference) in refs {
+        if resolve_ref(doc, &reference).is_none() {
+          findings.push(
+            Finding::error(
+              FindingCode::UnresolvedRef,
+              format!(
+                "The {} references {} at {} which doesn't exist",
+                label, reference, location
+              ),
+            )
+            .for_route(path),
+          );
+        }
+      }
+    }
+  }
+  findings
+}
+
+/// Collects the internal `$ref`s in the value and the JSON pointer of where they are used
+fn collect_refs(value: &serde_yaml::Value, pointer: &str, refs: &mut Vec<(String, String)>) {
+  match value {
+    serde_yaml::Value::Mapping(mapping) => {
+      for (key, child) in mapping {
+        let key = match key.as_str() {
+          Some(key) => key,
+          None => continue,
+        };
+        match child.as_str() {
+          Some(reference) if key == "$ref" && reference.starts_with('#') => {
+            refs.push((pointer.to_string(), reference.to_string()))
+          }
+          _ => collect_refs(child, &format!("{}/{}", pointer, escape_pointer(key)), refs),
+        }
+      }
+    }
+    serde_yaml::Value::Sequence(sequence) => {
+      for (index, child) in sequence.iter().enumerate() {
+        collect_refs(child, &format!("{}/{}", pointer, index), refs);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Escapes a JSON pointer segment, e.g. `/users` becomes `~1users`
+fn escape_pointer(segment: &str) -> String {
+  segment.replace('~', "~0").replace('/', "~1")
+}
+
+/// Gets the value an internal `$ref`, e.g. `#/components/schemas/User`, points to
+fn resolve_ref<'a>(doc: &'a serde_yaml::Value, reference: &str) -> Option<&'a serde_yaml::Value> {
+  let pointer = reference.strip_prefix('#')?;
+  if pointer.is_empty() {
+    return Some(doc);
+  }
+  pointer
+    .strip_prefix('/')?
+    .split('/')
+    .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+    .try_fold(doc, |value, segment| match value {
+      serde_yaml::Value::Sequence(sequence) => sequence.get(segment.parse::<usize>().ok()?),
+      _ => value.get(segment.as_str()),
+    })
+}
+
+/// Gets the names of the parameters in the route e.g. `id` for `/users/{id}`
+fn route_parameters(path: &str) -> Vec<&str> {
+  path
+    .split('{')
+    .skip(1)
+    .filter_map(|segment| segment.split_once('}'))
+    .map(|(name, _)| name.trim_end_matches('+'))
+    .collect()
+}
+
+/// Resolves a parameter, following a reference to `components.parameters`, or None when the
+/// reference can't be resolved
+fn resolve_parameter<'a>(
+  parameter: &'a openapiv3::ReferenceOr<openapiv3::Parameter>,
+  components: Option<&'a openapiv3::Components>,
+) -> Option<&'a openapiv3::Parameter> {
+  match parameter {
+    openapiv3::ReferenceOr::Item(parameter) => Some(parameter),
+    openapiv3::ReferenceOr::Reference { reference } => {
+      let resolved = reference
+        .strip_prefix("#/components/parameters/")
+        .and_then(|name| components?.parameters.get(name))
+        .and_then(|parameter| parameter.as_item());
+      if resolved.is_none() {
+        debug!("Unable to resolve the parameter {}", reference);
+      }
+      resolved
+    }
+  }
+}
+
+/// Gets the names of the `in: path` parameters, or None when a parameter reference can't be
+/// resolved and the names aren't known
+fn declared_path_parameters(
+  parameters: &[openapiv3::ReferenceOr<openapiv3::Parameter>],
+  components: Option<&openapiv3::Components>,
+) -> Option<Vec<String>> {
+  let mut names = Vec::new();
+  for parameter in parameters {
+    let parameter = resolve_parameter(parameter, components)?;
+    if let openapiv3::Parameter::Path { parameter_data, .. } = parameter {
+      names.push(parameter_data.name.clone());
+    }
+  }
+  Some(names)
+}
+
+/// Finds the route parameters that aren't declared as `in: path` parameters by the operations
+fn find_undeclared_path_parameters(
+  path: &str,
+  path_item: &openapiv3::PathItem,
+  components: Option<&openapiv3::Components>,
+) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  let route_parameters = route_parameters(path);
+  if route_parameters.is_empty() {
+    return findings;
+  }
+  let shared = match declared_path_parameters(&path_item.parameters, components) {
+    Some(shared) => shared,
+    None => return findings,
+  };
+  let operations = [
+    (HttpMethod::Get, &path_item.get),
+    (HttpMethod::Post, &path_item.post),
+    (HttpMethod::Put, &path_item.put),
+    (HttpMethod::Patch, &path_item.patch),
+    (HttpMethod::Delete, &path_item.delete),
+    (HttpMethod::Head, &path_item.head),
+    (HttpMethod::Options, &path_item.options),
+    (HttpMethod::Trace, &path_item.trace),
+  ];
+  for (method, operation) in operations {
+    let operation = match operation {
+      Some(operation) => operation,
+      None => continue,
+    };
+    let declared = match declared_path_parameters(&operation.parameters, components) {
+      Some(declared) => declared,
+      None => continue,
+    };
+    for name in &route_parameters {
+      if !shared.iter().chain(declared.iter()).any(|x| x == name) {
+        findings.push(
+          Finding::error(
+            FindingCode::UndeclaredPathParameter,
+            format!(
+              "The path parameter {} is not declared for the {} method for {}",
+              name, method, path
+            ),
+          )
+          .for_operation(&method, path),
+        );
+      }
+    }
+  }
+  findings
+}
+
+/// Finds the `in: path` parameters declared for the path or its operations that aren't in the
+/// route, which API Gateway rejects
+fn find_orphan_path_parameters(
+  path: &str,
+  path_item: &openapiv3::PathItem,
+  components: Option<&openapiv3::Components>,
+) -> Vec<Finding> {
+  let route_parameters = route_parameters(path);
+  let orphan = |name: &String| !route_parameters.contains(&name.as_str());
+  let mut findings: Vec<Finding> = declared_path_parameters(&path_item.parameters, components)
+    .unwrap_or_default()
+    .iter()
+    .filter(|name| orphan(name))
+    .map(|name| {
+      Finding::error(
+        FindingCode::OrphanPathParameter,
+        format!(
+          "The path parameter {} is declared for {} but isn't in the route",
+          name, path
+        ),
+      )
+      .for_route(path)
+    })
+    .collect();
+  for (method, operation) in path_item.iter() {
+    let declared = declared_path_parameters(&operation.parameters, components).unwrap_or_default();
+    for name in declared.iter().filter(|name| orphan(name)) {
+      findings.push(
+        Finding::error(
+          FindingCode::OrphanPathParameter,
+          format!(
+            "The path parameter {} is declared for the {} method for {} but isn't in the route",
+            name,
+            method.to_uppercase(),
+            path
+          ),
+        )
+        .for_operation(&HttpMethod::from(method), path),
+      );
+    }
+  }
+  findings
+}
+
+/// Warns about the paths that have methods but no OPTIONS method when the document uses CORS, which
+/// is either an `x-amazon-apigateway-cors` extension or an OPTIONS method with a `mock` integration
+///
+/// Paths with an `x-amazon-apigateway-any-method` also handle OPTIONS
+fn find_missing_cors_options(doc: &serde_yaml::Value) -> Vec<Finding> {
+  let paths = match doc.get("paths").and_then(|paths| paths.as_mapping()) {
+    Some(paths) => paths,
+    None => return Vec::new(),
+  };
+  let mock_options = paths.values().any(|path_item| {
+    path_item
+      .get("options")
+      .and_then(|options| options.get("x-amazon-apigateway-integration"))
+      .and_then(|integration| integration.get("type"))
+      .and_then(|integration_type| integration_type.as_str())
+      .is_some_and(|integration_type| integration_type.eq_ignore_ascii_case("mock"))
+  });
+  if doc.get("x-amazon-apigateway-cors").is_none() && !mock_options {
+    return Vec::new();
+  }
+  let mut findings = Vec::new();
+  for (path, path_item) in paths {
+    let (Some(path), Some(operations)) = (path.as_str(), path_item.as_mapping()) else {
+      continue;
+    };
+    let methods: Vec<HttpMethod> = operations
+      .keys()
+      .filter_map(|method| method.as_str()?.parse().ok())
+      .collect();
+    let handles_options = methods.contains(&HttpMethod::Options)
+      || operations.contains_key("x-amazon-apigateway-any-method");
+    if !handles_options && !methods.is_empty() {
+      findings.push(
+        Finding::warning(
+          FindingCode::MissingCorsOptions,
+          format!(
+            "The path {} has no OPTIONS method for the CORS preflight requests",
+            path
+          ),
+        )
+        .for_route(path),
+      );
+    }
+  }
+  findings
+}
+
+/// Warns about the documents whose `info.version` is missing or isn't a semver version, and when
+/// the documents that are merged declare different versions
+fn find_info_version_issues(documents: &[(&Path, String)]) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  let mut versions: Vec<(&Path, String)> = Vec::new();
+  for (file, content) in documents {
+    let doc: serde_yaml::Value = match serde_yaml::from_str(content) {
+      Ok(doc) => doc,
+      Err(_) => continue,
+    };
+    let version = match doc.get("info").and_then(|info| info.get("version")) {
+      Some(serde_yaml::Value::String(version)) => version.trim().to_string(),
+      Some(serde_yaml::Value::Number(version)) => version.to_string(),
+      _ => String::new(),
+    };
+    if version.is_empty() {
+      findings.push(
+        Finding::warning(
+          FindingCode::InfoVersion,
+          format!("The document {:?} doesn't set info.version", file),
+        )
+        .in_file(file),
+      );
+    } else if semver::Version::parse(&version).is_err() {
+      findings.push(
+        Finding::warning(
+          FindingCode::InfoVersion,
+          format!(
+            "The info.version {} of the document {:?} is not a semver version, e.g. 1.0.0",
+            version, file
+          ),
+        )
+        .in_file(file),
+      );
+    } else {
+      versions.push((file, version));
+    }
+  }
+  let mut distinct: Vec<&String> = versions.iter().map(|(_, version)| version).collect();
+  distinct.sort();
+  distinct.dedup();
+  if distinct.len() > 1 {
+    findings.push(Finding::warning(
+      FindingCode::ConflictingInfoVersion,
+      format!(
+        "The merged documents declare different info.versions: {}",
+        versions
+          .iter()
+          .map(|(file, version)| format!("{:?} {}", file, version))
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+    ));
+  }
+  findings
+}
+
+/// Warns when the documents that are merged declare different `info.title`s, as they may be
+/// unrelated documents that were included by accident
+fn find_conflicting_titles(documents: &[(&Path, String)]) -> Vec<Finding> {
+  let titles: Vec<(&Path, String)> = documents
+    .iter()
+    .filter_map(|(file, content)| {
+      let doc: serde_yaml::Value = serde_yaml::from_str(content).ok()?;
+      let title = doc.get("info")?.get("title")?.as_str()?.trim().to_string();
+      Some((*file, title))
+    })
+    .collect();
+  let mut distinct: Vec<&String> = titles.iter().map(|(_, title)| title).collect();
+  distinct.sort();
+  distinct.dedup();
+  if distinct.len() < 2 {
+    return Vec::new();
+  }
+  vec![Finding::warning(
+    FindingCode::ConflictingInfoTitle,
+    format!(
+      "The merged documents declare different info.titles: {}",
+      titles
+        .iter()
+        .map(|(file, title)| format!("{:?} {}", file, title))
+        .collect::<Vec<_>>()
+        .join(", ")
+    ),
+  )]
+}
+
+/// Warns when the documents that are merged declare OpenAPI versions with a different major or
+/// minor version, e.g. `3.0.0` and `3.1.0`, as the merged document mixes incompatible features
+fn find_conflicting_openapi_versions(documents: &[(&Path, String)]) -> Vec<Finding> {
+  let versions: Vec<(&Path, String)> = documents
+    .iter()
+    .filter_map(|(file, content)| {
+      let doc: serde_yaml::Value = serde_yaml::from_str(content).ok()?;
+      let version = match doc.get("openapi")? {
+        serde_yaml::Value::String(version) => version.trim().to_string(),
+        serde_yaml::Value::Number(version) => version.to_string(),
+        _ => return None,
+      };
+      Some((*file, version))
+    })
+    .collect();
+  let minor = |version: &str| version.split('.').take(2).collect::<Vec<_>>().join(".");
+  let mut distinct: Vec<String> = versions.iter().map(|(_, version)| minor(version)).collect();
+  distinct.sort();
+  distinct.dedup();
+  if distinct.len() < 2 {
+    return Vec::new();
+  }
+  vec![Finding::warning(
+    FindingCode::ConflictingOpenApiVersion,
+    format!(
+      "The merged documents declare different OpenAPI versions: {}",
+      versions
+        .iter()
+        .map(|(file, version)| format!("{:?} {}", file, version))
+        .collect::<Vec<_>>()
+        .join(", ")
+    ),
+  )]
+}
+
+/// Finds the operations that more than one document defines differently for the same path and
+/// method, which the merge combines into one definition, reporting the documents and their
+/// integration URIs
+fn find_conflicting_operations(documents: &[(&Path, String)]) -> Vec<Finding> {
+  let mut operations: BTreeMap<(String, HttpMethod), Vec<(&Path, serde_yaml::Value)>> =
+    BTreeMap::new();
+  for (file, content) in documents {
+    let Ok(doc) = serde_yaml::from_str::<serde_yaml::Value>(content) else {
+      continue;
+    };
+    let Some(paths) = doc.get("paths").and_then(|paths| paths.as_mapping()) else {
+      continue;
+    };
+    for (path, path_item) in paths {
+      let (Some(path), Some(path_item)) = (path.as_str(), path_item.as_mapping()) else {
+        continue;
+      };
+      for (method, operation) in path_item {
+        let Some(method) = method
+          .as_str()
+          .filter(|method| *method != "*")
+          .and_then(|method| method.parse::<HttpMethod>().ok())
+        else {
+          continue;
+        };
+        operations
+          .entry((path.to_string(), method))
+          .or_default()
+          .push((*file, operation.clone()));
+      }
+    }
+  }
+  let uri = |operation: &serde_yaml::Value| {
+    operation
+      .get("x-amazon-apigateway-integration")
+      .and_then(|integration| integration.get("uri"))
+      .and_then(|uri| uri.as_str())
+      .map(|uri| uri.to_string())
+      .unwrap_or_else(|| "no integration URI".to_string())
+  };
+  let mut findings = Vec::new();
+  for ((path, method), definitions) in operations {
+    let Some((first_file, first)) = definitions.first() else {
+      continue;
+    };
+    for (file, operation) in definitions.iter().skip(1) {
+      if operation == first {
+        continue;
+      }
+      findings.push(Finding::error(
+        FindingCode::ConflictingOperation,
+        format!(
+          "The {} {} operation is defined differently in {:?} and {:?}, with the integration URIs {} and {}",
+          method,
+          path,
+          first_file,
+          file,
+          uri(first),
+          uri(operation)
+        ),
+      ).for_operation(&method, &path));
+    }
+  }
+  findings
+}
+
+/// Warns about the tags used by operations that aren't declared in the document's `tags`
+fn find_undefined_tags(doc: &openapiv3::OpenAPI) -> Vec<Finding> {
+  let defined: Vec<&String> = doc.tags.iter().map(|tag| &tag.name).collect();
+  let mut findings = Vec::new();
+  for (path, path_item) in &doc.paths.paths {
+    let path_item = match path_item.as_item() {
+      Some(path_item) => path_item,
+      None => continue,
+    };
+    for (method, operation) in path_item.iter() {
+      for tag in operation.tags.iter().filter(|tag| !defined.contains(tag)) {
+        findings.push(
+          Finding::warning(
+            FindingCode::UndefinedTag,
+            format!(
+              "The {} {} operation uses the tag {} which is not defined in the tags",
+              method.to_uppercase(),
+              path,
+              tag
+            ),
+          )
+          .for_operation(&HttpMethod::from(method), path),
+        );
+      }
+    }
+  }
+  findings
+}
+
+/// Reports the security requirements of the document and its operations that use a scheme which
+/// isn't defined in `components.securitySchemes`
+fn find_undefined_security_schemes(doc: &openapiv3::OpenAPI) -> Vec<Finding> {
+  let defined: Vec<&String> = doc
+    .components
+    .iter()
+    .flat_map(|components| components.security_schemes.keys())
+    .collect();
+  let undefined = |security: &Option<Vec<openapiv3::SecurityRequirement>>| -> Vec<String> {
+    security
+      .iter()
+      .flatten()
+      .flat_map(|requirement| requirement.keys())
+      .filter(|scheme| !defined.contains(scheme))
+      .cloned()
+      .collect()
+  };
+  let mut findings = Vec::new();
+  for scheme in undefined(&doc.security) {
+    findings.push(Finding::error(
+      FindingCode::UndefinedSecurityScheme,
+      format!(
+        "The document's security uses the scheme {} which is not defined in components.securitySchemes",
+        scheme
+      ),
+    ));
+  }
+  for (path, path_item) in &doc.paths.paths {
+    let path_item = match path_item.as_item() {
+      Some(path_item) => path_item,
+      None => continue,
+    };
+    for (method, operation) in path_item.iter() {
+      for scheme in undefined(&operation.security) {
+        findings.push(Finding::error(
+          FindingCode::UndefinedSecurityScheme,
+          format!(
+            "The {} {} operation uses the security scheme {} which is not defined in components.securitySchemes",
+            method.to_uppercase(),
+            path,
+            scheme
+          ),
+        ).for_operation(&HttpMethod::from(method), path));
+      }
+    }
+  }
+  findings
+}
+
+/// Warns about the operations without a security requirement, neither their own `security` nor the
+/// document's
+///
+/// An empty `security: []` marks the operation as public, and OPTIONS operations are skipped as the
+/// CORS preflight requests aren't authorized
+fn find_missing_security(doc: &serde_yaml::Value) -> Vec<Finding> {
+  let paths = match doc.get("paths").and_then(|paths| paths.as_mapping()) {
+    Some(paths) => paths,
+    None => return Vec::new(),
+  };
+  let secured = doc
+    .get("security")
+    .and_then(|security| security.as_sequence())
+    .is_some_and(|security| !security.is_empty());
+  let mut findings = Vec::new();
+  for (path, path_item) in paths {
+    let (Some(path), Some(operations)) = (path.as_str(), path_item.as_mapping()) else {
+      continue;
+    };
+    for (method, operation) in operations {
+      let method = match method.as_str().map(|method| method.parse::<HttpMethod>()) {
+        Some(Ok(method)) if method != HttpMethod::Options => method,
+        _ => continue,
+      };
+      if operation.get("security").is_none() && !secured {
+        findings.push(
+          Finding::warning(
+            FindingCode::MissingSecurity,
+            format!(
+              "The {} {} operation has no security requirement, set security: [] if it's public",
+              method, path
+            ),
+          )
+          .for_operation(&method, path),
+        );
+      }
+    }
+  }
+  findings
+}
+
+/// Whether the content type is JSON, e.g. `application/json` or `application/problem+json`, or
+/// matches any type
+fn is_json_content_type(content_type: &str) -> bool {
+  let media_type = content_type
+    .split(';')
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_lowercase();
+  media_type == "application/json" || media_type.ends_with("+json") || media_type == "*/*"
+}
+
+/// Reports the 2xx responses of the Lambda proxy integrations that only declare content types
+/// other than JSON, e.g. `text/plain`, as a proxy integration returns `application/json` unless the
+/// lambda sets the `Content-Type` header
+///
+/// Responses that are a `$ref` to `#/components/responses` are resolved, the integration of the
+/// path applies to the operations without their own
+fn find_non_json_responses(doc: &serde_yaml::Value) -> Vec<Finding> {
+  let paths = match doc.get("paths").and_then(|paths| paths.as_mapping()) {
+    Some(paths) => paths,
+    None => return Vec::new(),
+  };
+  let resolve = |response: &'_ serde_yaml::Value| -> Option<serde_yaml::Value> {
+    match response
+      .get("$ref")
+      .and_then(|reference| reference.as_str())
+    {
+      Some(reference) => doc
+        .get("components")?
+        .get("responses")?
+        .get(reference.strip_prefix("#/components/responses/")?)
+        .cloned(),
+      None => Some(response.clone()),
+    }
+  };
+  let mut findings = Vec::new();
+  for (path, path_item) in paths {
+    let (Some(path), Some(operations)) = (path.as_str(), path_item.as_mapping()) else {
+      continue;
+    };
+    for (method, operation) in operations {
+      let method = match method.as_str().map(|method| method.parse::<HttpMethod>()) {
+        Some(Ok(method)) => method,
+        _ => continue,
+      };
+      let integration_type = operation
+        .get("x-amazon-apigateway-integration")
+        .or_else(|| path_item.get("x-amazon-apigateway-integration"))
+        .and_then(|integration| integration.get("type"))
+        .and_then(|integration_type| integration_type.as_str());
+      if !integration_type
+        .is_some_and(|integration_type| integration_type.eq_ignore_ascii_case("aws_proxy"))
+      {
+        continue;
+      }
+      let Some(responses) = operation
+        .get("responses")
+        .and_then(|responses| responses.as_mapping())
+      else {
+        continue;
+      };
+      for (status, response) in responses {
+        let status = match status {
+          serde_yaml::Value::Number(status) => status.to_string(),
+          status => status.as_str().unwrap_or_default().to_string(),
+        };
+        if !status.starts_with('2') {
+          continue;
+        }
+        let Some(content) = resolve(response).and_then(|response| {
+          response
+            .get("content")
+            .and_then(|content| content.as_mapping())
+            .cloned()
+        }) else {
+          continue;
+        };
+        let content_types: Vec<&str> = content.keys().filter_map(|key| key.as_str()).collect();
+        if content_types.is_empty()
+          || content_types
+            .iter()
+            .any(|content_type| is_json_content_type(content_type))
+        {
+          continue;
+        }
+        findings.push(Finding::warning(
+          FindingCode::NonJsonProxyResponse,
+          format!(
+            "The {} response of {} {} only declares {} but its Lambda proxy integration returns application/json unless the lambda sets the Content-Type header",
+            status,
+            method,
+            path,
+            content_types.join(", ")
+          ),
+        ).for_operation(&method, path));
+      }
+    }
+  }
+  findings
+}
+
+/// Reports the operations without any `tags`, OPTIONS operations are skipped as the CORS preflight
+/// requests aren't documented
+fn find_untagged_operations(doc: &serde_yaml::Value) -> Vec<Finding> {
+  let paths = match doc.get("paths").and_then(|paths| paths.as_mapping()) {
+    Some(paths) => paths,
+    None => return Vec::new(),
+  };
+  let mut findings = Vec::new();
+  for (path, path_item) in paths {
+    let (Some(path), Some(operations)) = (path.as_str(), path_item.as_mapping()) else {
+      continue;
+    };
+    for (method, operation) in operations {
+      let method = match method.as_str().map(|method| method.parse::<HttpMethod>()) {
+        Some(Ok(method)) if method != HttpMethod::Options => method,
+        _ => continue,
+      };
+      let tagged = operation
+        .get("tags")
+        .and_then(|tags| tags.as_sequence())
+        .is_some_and(|tags| !tags.is_empty());
+      if !tagged {
+        findings.push(
+          Finding::error(
+            FindingCode::UntaggedOperation,
+            format!("The {} {} operation has no tags", method, path),
+          )
+          .for_operation(&method, path),
+        );
+      }
+    }
+  }
+  findings
+}
+
+/// Warns about greedy proxy routes, e.g. `GET /api/{proxy+}`, that have explicit sibling routes
+/// with the same method under the same base path, as API Gateway will fail to deploy or route them
+/// unexpectedly
+fn find_proxy_shadowed_routes(data: &[OpenAPIData]) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  for proxy in data.iter().filter(|item| item.path.ends_with("+}")) {
+    let base = match proxy.path.rfind('/') {
+      Some(index) => format!("{}/", normalize_route(&proxy.path[..index])),
+      None => continue,
+    };
+    let proxy_route = normalize_route(&proxy.path);
+    let shadowed: Vec<String> = data
+      .iter()
+      .filter(|item| item.method == proxy.method)
+      .filter(|item| {
+        let route = normalize_route(&item.path);
+        route != proxy_route && route.starts_with(&base)
+      })
+      .map(|item| format!("{} {}", item.method.to_string().to_uppercase(), item.path))
+      .collect();
+    if !shadowed.is_empty() {
+      findings.push(
+        Finding::warning(
+          FindingCode::ProxyShadowsPath,
+          format!(
+            "The proxy route {} {} shadows these explicit routes: {}",
+            proxy.method.to_string().to_uppercase(),
+            proxy.path,
+            shadowed.join(", ")
+          ),
+        )
+        .for_operation(&proxy.method, &proxy.path),
+      );
+    }
+  }
+  findings
+}
+
+/// Normalizes a path so paths API Gateway treats as the same route compare equal, e.g. `/users`
+/// and `/users/`, or `/users/{id}` and `/users/{userId}`
+fn normalize_route(path: &str) -> String {
+  let path = match path.trim_end_matches('/') {
+    "" => "/",
+    path => path,
+  };
+  path
+    .split('/')
+    .map(|segment| {
+      if segment.starts_with('{') && segment.ends_with('}') {
+        "{}"
+      } else {
+        segment
+      }
+    })
+    .collect::<Vec<&str>>()
+    .join("/")
+}
+
+/// Warns about the path keys of each document that are different but are the same route
+fn find_documents_duplicate_routes(documents: &[(&Path, String)]) -> Vec<Finding> {
+  documents
+    .iter()
+    .flat_map(|(file, content)| {
+      let doc: serde_yaml::Value = serde_yaml::from_str(content).unwrap_or_default();
+      let paths: Vec<String> = doc
+        .get("paths")
+        .and_then(|paths| paths.as_mapping())
+        .map(|paths| {
+          paths
+            .keys()
+            .filter_map(|path| path.as_str().map(str::to_string))
+            .collect()
+        })
+        .unwrap_or_default();
+      find_duplicate_routes(&paths.iter().collect::<Vec<_>>())
+        .into_iter()
+        .map(|finding| finding.in_file(file))
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+/// Warns about path keys that are different in the document but are the same route
+fn find_duplicate_routes(paths: &[&String]) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  let mut seen: Vec<(String, &String)> = Vec::new();
+  for path in paths {
+    let route = normalize_route(path);
+    match seen.iter().find(|(seen_route, _)| *seen_route == route) {
+      Some((_, first)) => findings.push(
+        Finding::warning(
+          FindingCode::DuplicateRoute,
+          format!("The paths {} and {} are the same route", first, path),
+        )
+        .for_route(path),
+      ),
+      None => seen.push((route, path)),
+    }
+  }
+  findings
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
   fn test_extract_api_data() {
     let content = r#"
 openapi: 3.0.0
@@ -420,7 +2348,14 @@ paths:
         httpMethod: POST
         type: aws_proxy
 "#;
-    let data = extract_api_data(content.to_string()).expect("Failed to extract API data");
+    let data = extract_api_data(
+      content.to_string(),
+      &[],
+      false,
+      false,
+      &mut Findings::default(),
+    )
+    .expect("Failed to extract API data");
     assert_eq!(data.len(), 5);
     assert_eq!(data[0].path, "/test");
     assert_eq!(data[0].method, HttpMethod::Get);
@@ -524,7 +2459,14 @@ paths:
         httpMethod: POST
         type: aws_proxy
 "#;
-    let data = extract_api_data(content.to_string()).expect("Failed to extract API data");
+    let data = extract_api_data(
+      content.to_string(),
+      &[],
+      false,
+      false,
+      &mut Findings::default(),
+    )
+    .expect("Failed to extract API data");
     assert_eq!(data.len(), 5);
     assert_eq!(data[0].path, "/test");
     assert_eq!(data[0].method, HttpMethod::Get);
@@ -563,6 +2505,1131 @@ paths:
     assert_eq!(data[4].execution_type, ExecutionType::Lambda);
   }
 
+  #[test]
+  fn test_extract_api_data_with_path_item_integration() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+paths:
+  /items:
+    x-amazon-apigateway-integration:
+      uri: arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${items_arn}/invocations
+      httpMethod: POST
+      type: aws_proxy
+    get:
+      responses:
+        '200':
+          description: OK
+    delete:
+      responses:
+        '200':
+          description: OK
+    post:
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        uri: arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${create_item_arn}/invocations
+        httpMethod: POST
+        type: aws_proxy
+"#;
+    let data = extract_api_data(
+      content.to_string(),
+      &[],
+      false,
+      false,
+      &mut Findings::default(),
+    )
+    .expect("Failed to extract API data");
+    let uris: Vec<_> = data
+      .iter()
+      .map(|api| (api.method.clone(), api.uri.as_str()))
+      .collect();
+    assert_eq!(
+      uris,
+      vec![
+        (
+          HttpMethod::Get,
+          "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${items_arn}/invocations"
+        ),
+        (
+          HttpMethod::Post,
+          "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${create_item_arn}/invocations"
+        ),
+        (
+          HttpMethod::Delete,
+          "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${items_arn}/invocations"
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_extract_api_data_with_shared_parameters() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+paths:
+  /addresses:
+    post:
+      parameters:
+        - $ref: '#/components/parameters/Postcode'
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        uri: arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/${addresses_arn}/invocations
+        httpMethod: POST
+        type: aws_proxy
+  /stores:
+    parameters:
+      - $ref: '#/components/parameters/Postcode'
+    put:
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        uri: arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/${stores_arn}/invocations
+        httpMethod: POST
+        type: aws_proxy
+components:
+  parameters:
+    Postcode:
+      name: postcode
+      in: query
+      schema:
+        type: string
+"#;
+    let mut findings = Findings::default();
+    let data = extract_api_data(content.to_string(), &[], false, false, &mut findings)
+      .expect("Failed to extract API data");
+    assert_eq!(data.len(), 2);
+    assert_eq!(findings.count(FindingCode::PostNoBody), 0);
+  }
+
+  #[test]
+  fn test_find_duplicate_tags() {
+    let tags: Vec<OApiTag> = serde_yaml::from_str(
+      r#"
+- name: users
+  description: User endpoints
+- name: users
+  description: User endpoints
+- name: users
+  description: User endpoints
+- name: admin
+  description: Admin endpoints
+- name: admin
+  description: Other admin endpoints
+"#,
+    )
+    .unwrap();
+    let files = [
+      "users.yaml",
+      "users.yaml",
+      "admin.yaml",
+      "admin.yaml",
+      "admin.yaml",
+    ];
+    let tags: Vec<(PathBuf, OApiTag)> = files.into_iter().map(PathBuf::from).zip(tags).collect();
+    let findings = find_duplicate_tags(&tags);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::DuplicateTag);
+    assert_eq!(
+      findings[0].message,
+      "Duplicate tags: Name: users is declared 3 times in 2 files: \"users.yaml\", \"admin.yaml\"\nDescription: Some(\"User endpoints\")"
+    );
+  }
+
+  #[test]
+  fn test_extract_api_data_undeclared_path_parameter() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test API
+  version: 1.0.0
+components:
+  parameters:
+    Id:
+      name: id
+      in: path
+      required: true
+      schema:
+        type: string
+paths:
+  /users/{id}/posts/{postId}:
+    parameters:
+      - $ref: '#/components/parameters/Id'
+    get:
+      parameters:
+        - name: postId
+          in: path
+          required: true
+          schema:
+            type: string
+      x-amazon-apigateway-integration:
+        uri: "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_arn}/invocations"
+      responses:
+        '200':
+          description: OK
+    delete:
+      x-amazon-apigateway-integration:
+        uri: "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_arn}/invocations"
+      responses:
+        '200':
+          description: OK
+"#;
+    let mut findings = Findings::default();
+    assert!(extract_api_data(content.to_string(), &[], false, false, &mut findings).is_err());
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec!["The path parameter postId is not declared for the DELETE method for /users/{id}/posts/{postId}"]
+    );
+  }
+
+  #[test]
+  fn test_extract_api_data_orphan_path_parameter() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test API
+  version: 1.0.0
+paths:
+  /users:
+    get:
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema:
+            type: string
+        - name: limit
+          in: query
+          schema:
+            type: integer
+      x-amazon-apigateway-integration:
+        uri: "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_arn}/invocations"
+      responses:
+        '200':
+          description: OK
+  /users/{id}:
+    get:
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema:
+            type: string
+      x-amazon-apigateway-integration:
+        uri: "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_arn}/invocations"
+      responses:
+        '200':
+          description: OK
+"#;
+    let mut findings = Findings::default();
+    assert!(extract_api_data(content.to_string(), &[], false, false, &mut findings).is_err());
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The path parameter id is declared for the GET method for /users but isn't in the route"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_find_unresolved_refs() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test API
+  version: 1.0.0
+components:
+  schemas:
+    User:
+      type: object
+paths:
+  /users:
+    get:
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                type: array
+                items:
+                  $ref: '#/components/schemas/User'
+        '404':
+          description: Not found
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Error'
+"#;
+    let findings = find_unresolved_refs(&serde_yaml::from_str(content).unwrap());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::UnresolvedRef);
+    assert_eq!(
+      findings[0].message,
+      "The GET /users operation references #/components/schemas/Error at /paths/~1users/get/responses/404/content/application~1json/schema which doesn't exist"
+    );
+  }
+
+  #[test]
+  fn test_route_parameters() {
+    assert_eq!(route_parameters("/users"), Vec::<&str>::new());
+    assert_eq!(
+      route_parameters("/users/{id}/posts/{postId}"),
+      vec!["id", "postId"]
+    );
+    assert_eq!(route_parameters("/api/{proxy+}"), vec!["proxy"]);
+  }
+
+  #[test]
+  fn test_validate_open_api_empty_dir() {
+    let api_path = std::env::temp_dir().join("sv_test_validate_open_api_empty_dir");
+    std::fs::create_dir_all(&api_path).unwrap();
+    let result = validate_open_api(
+      api_path,
+      &OpenApiOptions::default(),
+      &mut Findings::default(),
+    );
+    assert!(result
+      .unwrap_err()
+      .to_string()
+      .starts_with("No OpenAPI documents found under"));
+  }
+
+  #[test]
+  fn test_validate_open_api_only_shared_file() {
+    let result = validate_open_api(
+      PathBuf::from("test_files/shared_only"),
+      &OpenApiOptions {
+        shared_schema_names: &["shared".to_string()],
+        ..Default::default()
+      },
+      &mut Findings::default(),
+    );
+    assert!(result
+      .unwrap_err()
+      .to_string()
+      .starts_with("Only the shared OpenAPI document"));
+  }
+
+  #[test]
+  fn test_validate_open_api_single_invalid_document() {
+    let mut findings = Findings::default();
+    let result = validate_open_api(
+      PathBuf::from("test_files/invalid_document"),
+      &OpenApiOptions::default(),
+      &mut findings,
+    );
+    assert_eq!(result.unwrap_err().to_string(), "Invalid OpenAPI documents");
+    assert_eq!(findings.count(FindingCode::InvalidDocument), 1);
+    assert!(findings
+      .iter()
+      .filter(|finding| finding.code == FindingCode::InvalidDocument)
+      .all(|finding| finding.message.starts_with("API document \"api.yaml\"")));
+  }
+
+  #[test]
+  fn test_validate_open_api_sequential() {
+    // Each validated document writes a cache entry, so the entries show which were validated
+    let cache_dir = std::env::temp_dir().join("sv_test_validate_open_api_sequential");
+    let validated = |fail_fast: bool| {
+      let _ = std::fs::remove_dir_all(&cache_dir);
+      let cache = ValidationCache::new(&cache_dir);
+      let mut findings = Findings::default();
+      findings.set_silent(true);
+      findings.set_fail_fast(fail_fast);
+      let result = validate_open_api(
+        PathBuf::from("test_files/invalid_documents"),
+        &OpenApiOptions {
+          cache: Some(&cache),
+          ..Default::default()
+        },
+        &mut findings,
+      );
+      assert!(result.is_err());
+      assert_eq!(
+        findings.count(FindingCode::InvalidDocument),
+        if fail_fast { 1 } else { 2 }
+      );
+      std::fs::read_dir(&cache_dir).unwrap().count()
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+      .num_threads(1)
+      .build()
+      .unwrap();
+    assert_eq!(pool.install(|| validated(false)), 2);
+    assert_eq!(pool.install(|| validated(true)), 1);
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+  }
+
+  #[test]
+  fn test_validate_open_api_streaming() {
+    let messages = |stream: bool| {
+      let mut findings = Findings::default();
+      findings.set_silent(true);
+      findings.set_stream(stream);
+      let result = validate_open_api(
+        PathBuf::from("test_files/open_api"),
+        &OpenApiOptions::default(),
+        &mut findings,
+      );
+      let mut messages: Vec<String> = findings.iter().map(|f| f.message.clone()).collect();
+      messages.sort();
+      (result.is_ok(), messages)
+    };
+    assert_eq!(messages(true), messages(false));
+  }
+
+  #[test]
+  fn test_parse_document_reports_source_file() {
+    let sources = vec![
+      PathBuf::from("test_files/open_api/valid.yaml"),
+      PathBuf::from("test_files/invalid_syntax/broken.yaml"),
+    ];
+    let content = "paths: [";
+    let error = parse_document(content, &sources).unwrap_err().to_string();
+    assert!(error.starts_with("Failed to parse \"test_files/invalid_syntax/broken.yaml\""));
+    assert!(error.contains("line"));
+  }
+
+  #[test]
+  fn test_write_merged() {
+    let path = std::env::temp_dir().join("sv_test_write_merged.yaml");
+    write_merged("openapi: 3.0.1\n", &path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "openapi: 3.0.1\n");
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_find_shared_file() {
+    let files = vec![
+      PathBuf::from("apis/users.yaml"),
+      PathBuf::from("apis/common.yaml"),
+      PathBuf::from("apis/shared.yaml"),
+    ];
+    let defaults: Vec<String> = DEFAULT_SHARED_SCHEMA_NAMES
+      .iter()
+      .map(|name| name.to_string())
+      .collect();
+    assert_eq!(find_shared_file(&files, &defaults), Some(&files[2]));
+    assert_eq!(
+      find_shared_file(&files, &["common".to_string()]),
+      Some(&files[1])
+    );
+    assert_eq!(find_shared_file(&files, &["_shared".to_string()]), None);
+  }
+
+  #[test]
+  fn test_find_proxy_shadowed_routes() {
+    let route = |method: HttpMethod, path: &str| OpenAPIData {
+      path: path.to_string(),
+      method,
+      uri: "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${arn}/invocations"
+        .to_string(),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    };
+    let data = vec![
+      route(HttpMethod::Get, "/api/{proxy+}"),
+      route(HttpMethod::Get, "/api/health"),
+      route(HttpMethod::Post, "/api/users"),
+      route(HttpMethod::Get, "/api/users/{id}"),
+      route(HttpMethod::Get, "/other"),
+    ];
+    let findings = find_proxy_shadowed_routes(&data);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::ProxyShadowsPath);
+    assert_eq!(
+      findings[0].message,
+      "The proxy route GET /api/{proxy+} shadows these explicit routes: GET /api/health, GET /api/users/{id}"
+    );
+
+    let data = vec![
+      route(HttpMethod::Get, "/api/{proxy+}"),
+      route(HttpMethod::Post, "/api/users"),
+      route(HttpMethod::Get, "/other"),
+    ];
+    assert!(find_proxy_shadowed_routes(&data).is_empty());
+  }
+
+  #[test]
+  fn test_find_missing_cors_options() {
+    let content = r#"
+paths:
+  /users:
+    get: {}
+    options:
+      x-amazon-apigateway-integration:
+        type: mock
+  /users/{id}:
+    get: {}
+    delete: {}
+  /proxy:
+    x-amazon-apigateway-any-method: {}
+"#;
+    let findings = find_missing_cors_options(&serde_yaml::from_str(content).unwrap());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::MissingCorsOptions);
+    assert_eq!(
+      findings[0].message,
+      "The path /users/{id} has no OPTIONS method for the CORS preflight requests"
+    );
+
+    let content = "paths:\n  /users:\n    get: {}\n";
+    assert!(find_missing_cors_options(&serde_yaml::from_str(content).unwrap()).is_empty());
+    let content =
+      "x-amazon-apigateway-cors:\n  allowOrigins: ['*']\npaths:\n  /users:\n    get: {}\n";
+    assert_eq!(
+      find_missing_cors_options(&serde_yaml::from_str(content).unwrap()).len(),
+      1
+    );
+  }
+
+  #[test]
+  fn test_find_security_issues() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test API
+  version: 1.0.0
+security:
+  - ApiKey: []
+components:
+  securitySchemes:
+    Cognito:
+      type: apiKey
+      name: Authorization
+      in: header
+paths:
+  /users:
+    get:
+      security:
+        - Cognito: []
+        - Iam: []
+      responses:
+        '200':
+          description: OK
+"#;
+    let doc: openapiv3::OpenAPI = serde_yaml::from_str(content).unwrap();
+    let messages: Vec<String> = find_undefined_security_schemes(&doc)
+      .into_iter()
+      .map(|finding| finding.message)
+      .collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The document's security uses the scheme ApiKey which is not defined in components.securitySchemes",
+        "The GET /users operation uses the security scheme Iam which is not defined in components.securitySchemes",
+      ]
+    );
+
+    let content = r#"
+paths:
+  /users:
+    get:
+      security:
+        - Cognito: []
+    post: {}
+    options: {}
+  /health:
+    get:
+      security: []
+"#;
+    let findings = find_missing_security(&serde_yaml::from_str(content).unwrap());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::MissingSecurity);
+    assert_eq!(
+      findings[0].message,
+      "The POST /users operation has no security requirement, set security: [] if it's public"
+    );
+    let content = "security:\n  - Cognito: []\npaths:\n  /users:\n    post: {}\n";
+    assert!(find_missing_security(&serde_yaml::from_str(content).unwrap()).is_empty());
+  }
+
+  #[test]
+  fn test_find_api_files() {
+    let path = Path::new("test_files/api_ext");
+    assert!(find_api_files(path, &[]).is_empty());
+    let expected = vec![path.join("service.openapi")];
+    assert_eq!(find_api_files(path, &["openapi".to_string()]), expected);
+    assert_eq!(find_api_files(path, &[".openapi".to_string()]), expected);
+  }
+
+  #[test]
+  fn test_find_untagged_operations() {
+    let content = r#"
+paths:
+  /users:
+    get:
+      tags: [users]
+    post:
+      tags: []
+    options: {}
+  /orders:
+    delete: {}
+"#;
+    let findings = find_untagged_operations(&serde_yaml::from_str(content).unwrap());
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The POST /users operation has no tags",
+        "The DELETE /orders operation has no tags",
+      ]
+    );
+    assert_eq!(findings[0].code, FindingCode::UntaggedOperation);
+  }
+
+  #[test]
+  fn test_find_non_json_responses() {
+    let content = r##"
+components:
+  responses:
+    Csv:
+      description: CSV
+      content:
+        text/csv: {}
+paths:
+  /reports:
+    x-amazon-apigateway-integration:
+      type: aws_proxy
+    get:
+      responses:
+        "200":
+          description: Report
+          content:
+            text/plain: {}
+        "400":
+          description: Bad request
+          content:
+            text/plain: {}
+    post:
+      responses:
+        "201":
+          description: Created
+          content:
+            application/problem+json: {}
+            text/plain: {}
+  /exports:
+    get:
+      responses:
+        "200":
+          $ref: "#/components/responses/Csv"
+      x-amazon-apigateway-integration:
+        type: AWS_PROXY
+  /files:
+    get:
+      responses:
+        "200":
+          description: File
+          content:
+            application/octet-stream: {}
+      x-amazon-apigateway-integration:
+        type: http_proxy
+"##;
+    let findings = find_non_json_responses(&serde_yaml::from_str(content).unwrap());
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The 200 response of GET /reports only declares text/plain but its Lambda proxy integration returns application/json unless the lambda sets the Content-Type header",
+        "The 200 response of GET /exports only declares text/csv but its Lambda proxy integration returns application/json unless the lambda sets the Content-Type header",
+      ]
+    );
+    assert_eq!(findings[0].code, FindingCode::NonJsonProxyResponse);
+  }
+
+  #[test]
+  fn test_validate_open_api_document() {
+    let content = r#"
+openapi: 3.0.1
+info:
+  version: 1.0.0
+  title: Piped
+paths:
+  /v1/piped:
+    get:
+      responses:
+        "200":
+          description: Success
+      x-amazon-apigateway-integration:
+        uri: "arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${piped_arn}/invocations"
+"#;
+    let data = validate_open_api_document(
+      content.to_string(),
+      std::path::Path::new("stdin"),
+      false,
+      false,
+      false,
+      &mut Findings::default(),
+    )
+    .unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].path, "/v1/piped");
+
+    let mut findings = Findings::default();
+    assert!(validate_open_api_document(
+      "openapi: 3.0.1\npaths: {}\n".to_string(),
+      std::path::Path::new("stdin"),
+      false,
+      false,
+      false,
+      &mut findings,
+    )
+    .is_err());
+    assert_eq!(findings.error_count(), 1);
+  }
+
+  #[test]
+  fn test_open_file() {
+    let file = temp_file::with_contents(b"openapi: 3.0.0\n");
+    assert_eq!(
+      open_file(file.path().to_path_buf()).unwrap(),
+      "openapi: 3.0.0\n"
+    );
+
+    let file = temp_file::with_contents(&[0x6f, 0x70, 0xff, 0xfe]);
+    let error = open_file(file.path().to_path_buf()).unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      format!("The file {:?} is not valid UTF-8", file.path())
+    );
+
+    let missing = PathBuf::from("test_files/open_api/missing.yaml");
+    assert!(open_file(missing)
+      .unwrap_err()
+      .to_string()
+      .contains("missing.yaml"));
+  }
+
+  #[test]
+  fn test_find_integration_issues() {
+    let integration = serde_json::json!({
+      "type": "aws",
+      "passthroughBehavior": "NEVER",
+      "requestParameters": {
+        "integration.request.path.id": "method.request.path.userId",
+        "integration.request.header.x-source": "'sv'",
+        "integration.request.body": "method.request.body",
+      },
+    });
+    let findings = find_integration_issues(&integration, &HttpMethod::Post, "/users/{id}");
+    let messages: Vec<&str> = findings.iter().map(|x| x.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The POST method for /users/{id} has 'passthroughBehavior: NEVER' without 'requestTemplates', so every request with a body is rejected",
+        "The POST method for /users/{id} has the request parameter 'integration.request.body', expected 'integration.request.header', 'querystring' or 'path' followed by the name",
+        "The POST method for /users/{id} maps the request parameter 'integration.request.path.id' from 'method.request.path.userId' which isn't a parameter of the path",
+      ]
+    );
+
+    let integration = serde_json::json!({
+      "type": "aws_proxy",
+      "passthroughBehavior": "never",
+      "requestParameters": {
+        "integration.request.path.id": "method.request.path.id",
+      },
+    });
+    assert!(find_integration_issues(&integration, &HttpMethod::Get, "/users/{id}").is_empty());
+  }
+
+  #[test]
+  fn test_find_undefined_tags() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+tags:
+  - name: Users
+paths:
+  /users:
+    get:
+      tags:
+        - Users
+      responses:
+        '200':
+          description: OK
+  /admin:
+    post:
+      tags:
+        - Admin
+      responses:
+        '200':
+          description: OK
+"#;
+    let doc: openapiv3::OpenAPI = serde_yaml::from_str(content).unwrap();
+    let findings = find_undefined_tags(&doc);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::UndefinedTag);
+    assert_eq!(
+      findings[0].message,
+      "The POST /admin operation uses the tag Admin which is not defined in the tags"
+    );
+  }
+
+  #[test]
+  fn test_find_info_version_issues() {
+    let document = |version: &str| {
+      format!(
+        "openapi: 3.0.0\ninfo:\n  title: Test\n{}paths: {{}}\n",
+        version
+      )
+    };
+    let documents = [
+      (Path::new("missing.yaml"), document("")),
+      (Path::new("date.yaml"), document("  version: '2024-01'\n")),
+      (Path::new("users.yaml"), document("  version: 1.0.0\n")),
+      (Path::new("orders.yaml"), document("  version: 1.1.0\n")),
+    ];
+    let findings = find_info_version_issues(&documents);
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The document \"missing.yaml\" doesn't set info.version",
+        "The info.version 2024-01 of the document \"date.yaml\" is not a semver version, e.g. 1.0.0",
+        "The merged documents declare different info.versions: \"users.yaml\" 1.0.0, \"orders.yaml\" 1.1.0",
+      ]
+    );
+    assert_eq!(findings[0].file, Some(PathBuf::from("missing.yaml")));
+    assert!(find_info_version_issues(&documents[2..3]).is_empty());
+  }
+
+  #[test]
+  fn test_find_conflicting_titles() {
+    let document =
+      |title: &str| format!("openapi: 3.0.0\ninfo:\n  title: {}\npaths: {{}}\n", title);
+    let documents = [
+      (Path::new("users.yaml"), document("Shop API")),
+      (Path::new("orders.yaml"), document("Shop API")),
+      (Path::new("billing.yaml"), document("Billing API")),
+    ];
+    let findings = find_conflicting_titles(&documents);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::ConflictingInfoTitle);
+    assert_eq!(
+      findings[0].message,
+      "The merged documents declare different info.titles: \"users.yaml\" Shop API, \"orders.yaml\" Shop API, \"billing.yaml\" Billing API"
+    );
+    assert!(find_conflicting_titles(&documents[..2]).is_empty());
+  }
+
+  #[test]
+  fn test_find_conflicting_openapi_versions() {
+    let document = |version: &str| {
+      format!(
+        "openapi: {}\ninfo:\n  title: Shop API\npaths: {{}}\n",
+        version
+      )
+    };
+    let documents = [
+      (Path::new("users.yaml"), document("3.0.0")),
+      (Path::new("orders.yaml"), document("3.0.3")),
+      (Path::new("billing.yaml"), document("3.1.0")),
+    ];
+    let findings = find_conflicting_openapi_versions(&documents);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::ConflictingOpenApiVersion);
+    assert_eq!(
+      findings[0].message,
+      "The merged documents declare different OpenAPI versions: \"users.yaml\" 3.0.0, \"orders.yaml\" 3.0.3, \"billing.yaml\" 3.1.0"
+    );
+    assert!(find_conflicting_openapi_versions(&documents[..2]).is_empty());
+  }
+
+  #[test]
+  fn test_find_conflicting_operations() {
+    let document = |method: &str, uri: &str| {
+      format!(
+        "openapi: 3.0.0\ninfo:\n  title: Shop API\npaths:\n  /x:\n    {}:\n      x-amazon-apigateway-integration:\n        uri: {}\n",
+        method, uri
+      )
+    };
+    let documents = [
+      (Path::new("a.yaml"), document("post", "a_arn")),
+      (Path::new("b.yaml"), document("post", "b_arn")),
+      (Path::new("c.yaml"), document("get", "c_arn")),
+      (Path::new("d.yaml"), document("post", "a_arn")),
+    ];
+    let findings = find_conflicting_operations(&documents);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, FindingCode::ConflictingOperation);
+    assert_eq!(
+      findings[0].message,
+      "The POST /x operation is defined differently in \"a.yaml\" and \"b.yaml\", with the integration URIs a_arn and b_arn"
+    );
+    assert!(find_conflicting_operations(&[documents[0].clone(), documents[2].clone()]).is_empty());
+  }
+
+  #[test]
+  fn test_find_duplicate_routes() {
+    let paths = [
+      "/users".to_string(),
+      "/users/".to_string(),
+      "/users/{id}".to_string(),
+      "/users/{userId}".to_string(),
+      "/".to_string(),
+    ];
+    let paths: Vec<&String> = paths.iter().collect();
+    let findings = find_duplicate_routes(&paths);
+    assert_eq!(findings.len(), 2);
+    assert_eq!(findings[0].code, FindingCode::DuplicateRoute);
+    assert_eq!(
+      findings[0].message,
+      "The paths /users and /users/ are the same route"
+    );
+    assert_eq!(
+      findings[1].message,
+      "The paths /users/{id} and /users/{userId} are the same route"
+    );
+
+    let documents = [
+      (
+        Path::new("users.yaml"),
+        "paths:\n  /users/{id}: {}\n  /users/{userId}: {}\n".to_string(),
+      ),
+      (
+        Path::new("orders.yaml"),
+        "paths:\n  /orders: {}\n".to_string(),
+      ),
+    ];
+    let findings = find_documents_duplicate_routes(&documents);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].file, Some(PathBuf::from("users.yaml")));
+
+    let paths = ["/users".to_string(), "/user".to_string()];
+    let paths: Vec<&String> = paths.iter().collect();
+    assert!(find_duplicate_routes(&paths).is_empty());
+  }
+
+  #[test]
+  fn test_check_merged_paths() {
+    let first = r#"
+paths:
+  /a:
+    get: {}
+  /b:
+    get: {}
+"#
+    .to_string();
+    let second = r#"
+paths:
+  /c:
+    get: {}
+  /d:
+    get: {}
+"#
+    .to_string();
+    let overlapping = r#"
+paths:
+  /a:
+    post: {}
+"#
+    .to_string();
+    let files = vec![first.clone(), second];
+    let merged = merge(files.clone());
+    assert_eq!(path_keys(&merged).len(), 4);
+    assert!(check_merged_paths(&files, &merged).is_none());
+
+    let files = vec![first, overlapping];
+    let merged = merge(files.clone());
+    let finding = check_merged_paths(&files, &merged).expect("Expected a collision");
+    assert_eq!(finding.code, FindingCode::MergedPathCollision);
+    assert!(finding.message.ends_with(": /a"));
+  }
+
+  #[test]
+  fn test_extract_api_data_trace_is_rejected() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+paths:
+  /test:
+    trace:
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        uri: arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:Test/invocations
+        httpMethod: POST
+        type: aws_proxy
+"#;
+    let data = extract_api_data(
+      content.to_string(),
+      &[],
+      false,
+      false,
+      &mut Findings::default(),
+    );
+    assert!(data.is_err());
+    assert_eq!(
+      data.err().unwrap().to_string(),
+      "The TRACE method for /test is not supported by API Gateway, remove it from the OpenAPI document"
+    );
+  }
+
+  #[test]
+  fn test_extract_api_data_skips_mock_options() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+paths:
+  /test:
+    get:
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        uri: arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:Test/invocations
+        httpMethod: POST
+        type: aws_proxy
+    options:
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        type: mock
+        requestTemplates:
+          application/json: '{"statusCode": 200}'
+"#;
+    let mut findings = Findings::default();
+    let data = extract_api_data(content.to_string(), &[], false, false, &mut findings).unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].method, HttpMethod::Get);
+    assert_eq!(findings.count(FindingCode::OptionsMethod), 0);
+
+    let content = content.replace(
+      "        type: mock\n",
+      "        uri: arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:Cors/invocations\n        httpMethod: POST\n        type: aws_proxy\n",
+    );
+    let data = extract_api_data(content, &[], false, false, &mut Findings::default()).unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[1].method, HttpMethod::Options);
+  }
+
+  #[test]
+  fn test_extract_api_data_non_proxy_lambda_integration() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+paths:
+  /test:
+    get:
+      responses:
+        '200':
+          description: OK
+      x-amazon-apigateway-integration:
+        uri: arn:aws:apigateway:us-east-1:lambda:path/2015-03-31/functions/arn:aws:lambda:us-east-1:123456789012:function:Test/invocations
+        httpMethod: POST
+        type: aws
+"#;
+    let mut findings = Findings::default();
+    assert!(extract_api_data(content.to_string(), &[], false, false, &mut findings).is_ok());
+    let finding = findings.iter().next().expect("Expected a finding");
+    assert_eq!(finding.code, FindingCode::LambdaIntegrationType);
+    assert!(finding
+      .message
+      .starts_with("The GET method for /test uses a non-proxy 'aws' Lambda integration"));
+
+    let mut findings = Findings::default();
+    let content = content.replace("type: aws", "type: aws_proxy");
+    assert!(extract_api_data(content, &[], false, false, &mut findings).is_ok());
+    assert_eq!(findings.warning_count(), 0);
+  }
+
+  #[test]
+  fn test_is_request_body_optional() {
+    let content = r#"
+openapi: 3.0.0
+info:
+  title: Test
+  version: 1.0.0
+paths:
+  /test:
+    post:
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+      responses:
+        '200':
+          description: OK
+    put:
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              type: object
+      responses:
+        '200':
+          description: OK
+    patch:
+      requestBody:
+        $ref: '#/components/requestBodies/Patch'
+      responses:
+        '200':
+          description: OK
+    delete:
+      requestBody:
+        $ref: '#/components/requestBodies/Missing'
+      responses:
+        '200':
+          description: OK
+components:
+  requestBodies:
+    Patch:
+      content:
+        application/json:
+          schema:
+            type: object
+"#;
+    let doc: openapiv3::OpenAPI = serde_yaml::from_str(content).unwrap();
+    let components = doc.components.as_ref();
+    let path_item = doc.paths.paths.get("/test").unwrap().as_item().unwrap();
+    assert!(is_request_body_optional(
+      path_item.post.as_ref().unwrap(),
+      components
+    ));
+    assert!(!is_request_body_optional(
+      path_item.put.as_ref().unwrap(),
+      components
+    ));
+    assert!(is_request_body_optional(
+      path_item.patch.as_ref().unwrap(),
+      components
+    ));
+    assert!(!is_request_body_optional(
+      path_item.delete.as_ref().unwrap(),
+      components
+    ));
+  }
+
   //   #[test]
   //   fn test_extract_api_data_post_with_no_request_body() {
   //     let content = r#"
@@ -581,7 +3648,7 @@ paths:
   //         httpMethod: POST
   //         type: aws_proxy
   // "#;
-  //     let data = extract_api_data(content.to_string());
+  //     let data = extract_api_data(content.to_string(), &[], false, false, &mut Findings::default());
   //     assert!(data.is_err());
   //     assert_eq!(
   //       data.err().unwrap().to_string(),
@@ -607,7 +3674,7 @@ paths:
   //         httpMethod: POST
   //         type: aws_proxy
   // "#;
-  //     let data = extract_api_data(content.to_string());
+  //     let data = extract_api_data(content.to_string(), &[], false, false, &mut Findings::default());
   //     assert!(data.is_err());
   //     assert_eq!(
   //       data.err().unwrap().to_string(),
@@ -633,7 +3700,7 @@ paths:
   //         httpMethod: POST
   //         type: aws_proxy
   // "#;
-  //     let data = extract_api_data(content.to_string());
+  //     let data = extract_api_data(content.to_string(), &[], false, false, &mut Findings::default());
   //     assert!(data.is_err());
   //     assert_eq!(
   //       data.err().unwrap().to_string(),
@@ -0,0 +1,125 @@
+use crate::{
+  findings::{Finding, Section, Severity},
+  hints::suggestion_for,
+  logger::Glyphs,
+};
+
+/// Formats the findings as a report with a section for each part of the validation, the number of
+/// errors and warnings in its header and its findings indented below
+///
+/// The sections are drawn in boxes with the unicode glyphs, and under plain headers otherwise. With
+/// `hints` the suggestion for fixing a finding is below it
+pub fn format_pretty_report<'a>(
+  findings: impl IntoIterator<Item = &'a Finding>,
+  glyphs: Glyphs,
+  hints: bool,
+) -> String {
+  let findings: Vec<&Finding> = findings.into_iter().collect();
+  let mut lines = Vec::new();
+  for section in Section::ALL {
+    let findings: Vec<&Finding> = findings
+      .iter()
+      .filter(|finding| finding.code.section() == section)
+      .copied()
+      .collect();
+    let header = format!("{}: {}", section, format_counts(&findings));
+    let indent = match glyphs {
+      Glyphs::Unicode => {
+        lines.push(format!("┌─ {}", header));
+        "│  "
+      }
+      Glyphs::Ascii => {
+        lines.push(format!("== {} ==", header));
+        "  "
+      }
+    };
+    for finding in findings {
+      let message = match (glyphs, finding.severity) {
+        (Glyphs::Unicode, Severity::Warning) => glyphs.warning(&finding.message),
+        (Glyphs::Unicode, Severity::Error) => glyphs.fail(&finding.message),
+        (Glyphs::Ascii, severity) => format!("{}: {}", severity, finding.message),
+      };
+      lines.extend(message.lines().map(|line| format!("{}{}", indent, line)));
+      if let Some(suggestion) = suggestion_for(finding).filter(|_| hints) {
+        lines.push(format!("{}  hint: {}", indent, suggestion));
+      }
+    }
+    if glyphs == Glyphs::Unicode {
+      lines.push("└─".to_string());
+    }
+  }
+  lines.join("\n")
+}
+
+/// Formats the number of errors and warnings, e.g. `1 error, 2 warnings`
+fn format_counts(findings: &[&Finding]) -> String {
+  let count = |severity: Severity| {
+    findings
+      .iter()
+      .filter(|finding| finding.severity == severity)
+      .count()
+  };
+  let plural = |count: usize, name: &str| match count {
+    1 => format!("1 {}", name),
+    _ => format!("{} {}s", count, name),
+  };
+  match (count(Severity::Error), count(Severity::Warning)) {
+    (0, 0) => "no findings".to_string(),
+    (errors, 0) => plural(errors, "error"),
+    (0, warnings) => plural(warnings, "warning"),
+    (errors, warnings) => format!(
+      "{}, {}",
+      plural(errors, "error"),
+      plural(warnings, "warning")
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::findings::FindingCode;
+
+  #[test]
+  fn test_format_pretty_report() {
+    let findings = vec![
+      Finding::error(
+        FindingCode::PathNotInOpenApi,
+        "The path /users is not defined in the OpenAPI documents",
+      )
+      .for_route("/users"),
+      Finding::warning(FindingCode::UndefinedTag, "The tag users is not defined"),
+      Finding::error(
+        FindingCode::DuplicateTag,
+        "Duplicate tags: Name: users\nDescription: None",
+      ),
+    ];
+    assert_eq!(
+      format_pretty_report(&findings, Glyphs::Unicode, false),
+      [
+        "┌─ OpenAPI: 1 error, 1 warning",
+        "│  ⚠ The tag users is not defined",
+        "│  ✗ Duplicate tags: Name: users",
+        "│  Description: None",
+        "└─",
+        "┌─ Terraform: no findings",
+        "└─",
+        "┌─ Cross-validation: 1 error",
+        "│  ✗ The path /users is not defined in the OpenAPI documents",
+        "└─",
+      ]
+      .join("\n")
+    );
+    assert_eq!(
+      format_pretty_report(&findings[..1], Glyphs::Ascii, true),
+      [
+        "== OpenAPI: no findings ==",
+        "== Terraform: no findings ==",
+        "== Cross-validation: 1 error ==",
+        "  error: The path /users is not defined in the OpenAPI documents",
+        "    hint: Add the path /users to the OpenAPI documents, or remove it from lambdas_permissions",
+      ]
+      .join("\n")
+    );
+  }
+}
@@ -0,0 +1,210 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  sync::{Mutex, OnceLock},
+  time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::error::SvError;
+
+/// The timings recorded since `start` was called, profiling is off until then
+static PROFILER: OnceLock<Profiler> = OnceLock::new();
+
+/// Records how long each stage of verifying took
+struct Profiler {
+  /// When profiling started, the start of every span is relative to it
+  started: Instant,
+  /// The spans in the order they finished
+  spans: Mutex<Vec<Span>>,
+}
+
+/// A stage of verifying, or of handling one file, and how long it took
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Span {
+  /// The stage, e.g. `validate_document` or `parse_terraform`
+  pub stage: String,
+  /// The file the stage handled, `None` for the stages that cover every file
+  pub file: Option<PathBuf>,
+  /// The thread the stage ran on, stages on different threads overlap
+  pub thread: String,
+  /// When the stage started, in milliseconds since profiling started
+  pub start_ms: f64,
+  /// How long the stage took in milliseconds
+  pub duration_ms: f64,
+}
+
+/// The total time of a stage over all its spans
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageTotal {
+  /// The stage
+  pub stage: String,
+  /// The number of times the stage ran
+  pub count: usize,
+  /// The total time in milliseconds
+  pub duration_ms: f64,
+}
+
+/// The total time spent on a file over all its spans
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileTotal {
+  /// The file
+  pub file: PathBuf,
+  /// The total time in milliseconds
+  pub duration_ms: f64,
+  /// The time of each stage that handled the file in milliseconds
+  pub stages: BTreeMap<String, f64>,
+}
+
+/// The timings written by `--profile-output`
+///
+/// The stages and files are sorted by their total time, slowest first, and the spans by their
+/// start so they can be drawn as a timeline per thread
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileReport {
+  /// How long profiling ran in milliseconds
+  pub total_ms: f64,
+  /// The total time of each stage
+  pub stages: Vec<StageTotal>,
+  /// The total time of each file
+  pub files: Vec<FileTotal>,
+  /// Every recorded span
+  pub spans: Vec<Span>,
+}
+
+impl ProfileReport {
+  /// Totals the spans per stage and per file
+  pub fn new(total_ms: f64, mut spans: Vec<Span>) -> Self {
+    spans.sort_by(|a, b| a.start_ms.total_cmp(&b.start_ms));
+    let mut stages: BTreeMap<&str, StageTotal> = BTreeMap::new();
+    let mut files: BTreeMap<&Path, FileTotal> = BTreeMap::new();
+    for span in &spans {
+      let stage = stages
+        .entry(span.stage.as_str())
+        .or_insert_with(|| StageTotal {
+          stage: span.stage.clone(),
+          count: 0,
+          duration_ms: 0.0,
+        });
+      stage.count += 1;
+      stage.duration_ms += span.duration_ms;
+      if let Some(file) = &span.file {
+        let total = files.entry(file.as_path()).or_insert_with(|| FileTotal {
+          file: file.clone(),
+          duration_ms: 0.0,
+          stages: BTreeMap::new(),
+        });
+        total.duration_ms += span.duration_ms;
+        *total.stages.entry(span.stage.clone()).or_default() += span.duration_ms;
+      }
+    }
+    let mut stages: Vec<StageTotal> = stages.into_values().collect();
+    stages.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+    let mut files: Vec<FileTotal> = files.into_values().collect();
+    files.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+    ProfileReport {
+      total_ms,
+      stages,
+      files,
+      spans,
+    }
+  }
+
+  /// Writes the profile as JSON
+  pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(self)?)
+      .map_err(|e| SvError::Io(format!("Couldn't write the profile {:?}: {}", path, e)).into())
+  }
+}
+
+/// Starts recording the time of the stages run with `time`
+pub fn start() {
+  let _ = PROFILER.set(Profiler {
+    started: Instant::now(),
+    spans: Mutex::new(Vec::new()),
+  });
+}
+
+/// Runs `f`, recording how long it took as the stage, for the file when it handles one, if
+/// profiling has started
+pub fn time<T>(stage: &str, file: Option<&Path>, f: impl FnOnce() -> T) -> T {
+  let Some(profiler) = PROFILER.get() else {
+    return f();
+  };
+  let started = Instant::now();
+  let result = f();
+  let span = Span {
+    stage: stage.to_string(),
+    file: file.map(Path::to_path_buf),
+    thread: format!("{:?}", std::thread::current().id()),
+    start_ms: millis(started.duration_since(profiler.started).as_secs_f64()),
+    duration_ms: millis(started.elapsed().as_secs_f64()),
+  };
+  profiler
+    .spans
+    .lock()
+    .expect("Failed to lock the profile")
+    .push(span);
+  result
+}
+
+/// The profile of the stages recorded so far, `None` if profiling hasn't started
+pub fn finish() -> Option<ProfileReport> {
+  let profiler = PROFILER.get()?;
+  let spans = profiler
+    .spans
+    .lock()
+    .expect("Failed to lock the profile")
+    .clone();
+  Some(ProfileReport::new(
+    millis(profiler.started.elapsed().as_secs_f64()),
+    spans,
+  ))
+}
+
+/// Converts seconds to milliseconds
+fn millis(seconds: f64) -> f64 {
+  seconds * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn span(stage: &str, file: Option<&str>, start_ms: f64, duration_ms: f64) -> Span {
+    Span {
+      stage: stage.to_string(),
+      file: file.map(PathBuf::from),
+      thread: "ThreadId(1)".to_string(),
+      start_ms,
+      duration_ms,
+    }
+  }
+
+  #[test]
+  fn test_profile_totals() {
+    let profile = ProfileReport::new(
+      100.0,
+      vec![
+        span("validate_document", Some("users.yaml"), 5.0, 10.0),
+        span("validate_document", Some("orders.yaml"), 0.0, 40.0),
+        span("extract_api_data", Some("users.yaml"), 50.0, 5.0),
+        span("cross_validation", None, 60.0, 20.0),
+      ],
+    );
+    assert_eq!(
+      profile
+        .files
+        .iter()
+        .map(|file| (file.file.to_str().unwrap(), file.duration_ms))
+        .collect::<Vec<_>>(),
+      vec![("orders.yaml", 40.0), ("users.yaml", 15.0)]
+    );
+    assert_eq!(profile.files[1].stages["extract_api_data"], 5.0);
+    assert_eq!(profile.stages[0].stage, "validate_document");
+    assert_eq!(profile.stages[0].count, 2);
+    assert_eq!(profile.stages[0].duration_ms, 50.0);
+    assert_eq!(profile.spans[0].file, Some(PathBuf::from("orders.yaml")));
+  }
+}
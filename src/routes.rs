@@ -0,0 +1,180 @@
+use serde::Serialize;
+
+use crate::{open_api::OpenAPIData, terraform::Lambda, util::format_table};
+
+/// The columns of the routes, in order
+pub const ROUTE_COLUMNS: [&str; 7] = [
+  "route",
+  "method",
+  "lambda_key",
+  "arn_placeholder",
+  "execution_type",
+  "gateway",
+  "step_function",
+];
+
+/// A route of the OpenAPI documents and the lambda that backs it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RouteEntry {
+  /// The route path
+  pub route: String,
+  /// The HTTP method
+  pub method: String,
+  /// The key of the lambda that has a permission for the route, or whose ARN placeholder is in
+  /// the integration URI
+  pub lambda_key: Option<String>,
+  /// The lambda's ARN placeholder
+  pub arn_placeholder: Option<String>,
+  /// What the integration invokes
+  pub execution_type: String,
+  /// The type of API the route is in, a `REST API` or an `HTTP API`
+  pub gateway: String,
+  /// The lambda is also used by a step function
+  pub step_function: bool,
+}
+
+impl RouteEntry {
+  /// The values of the columns, empty when there's no lambda
+  fn cells(&self) -> Vec<String> {
+    vec![
+      self.route.clone(),
+      self.method.clone(),
+      self.lambda_key.clone().unwrap_or_default(),
+      self.arn_placeholder.clone().unwrap_or_default(),
+      self.execution_type.clone(),
+      self.gateway.clone(),
+      self.step_function.to_string(),
+    ]
+  }
+}
+
+/// Lists the routes of the OpenAPI documents with the lambdas that back them
+///
+/// The lambda is the one with a permission for the route, or otherwise the one whose ARN
+/// placeholder is in the integration URI
+pub fn route_inventory(open_api_data: &[OpenAPIData], lambdas: &[Lambda]) -> Vec<RouteEntry> {
+  open_api_data
+    .iter()
+    .map(|api| {
+      let lambda = lambdas
+        .iter()
+        .find(|lambda| {
+          lambda
+            .apis
+            .iter()
+            .any(|route| route.route == api.path && route.method == api.method)
+        })
+        .or_else(|| {
+          lambdas.iter().find(|lambda| {
+            lambda
+              .arn_template_key
+              .as_ref()
+              .is_some_and(|placeholder| api.uri.contains(&format!("${{{}}}", placeholder)))
+          })
+        });
+      RouteEntry {
+        route: api.path.clone(),
+        method: api.method.to_string(),
+        lambda_key: lambda.map(|lambda| lambda.key.clone()),
+        arn_placeholder: lambda.and_then(|lambda| lambda.arn_template_key.clone()),
+        execution_type: api.execution_type.to_string(),
+        gateway: api.gateway.to_string(),
+        step_function: lambda.is_some_and(|lambda| lambda.step_function),
+      }
+    })
+    .collect()
+}
+
+/// Formats the routes as a table
+pub fn format_routes_table(routes: &[RouteEntry]) -> String {
+  let rows: Vec<Vec<String>> = std::iter::once(ROUTE_COLUMNS.map(String::from).to_vec())
+    .chain(routes.iter().map(RouteEntry::cells))
+    .collect();
+  format_table(&rows)
+}
+
+/// Formats the routes as CSV with a header row, quoting the values that contain a comma, a quote
+/// or a new line
+pub fn format_routes_csv(routes: &[RouteEntry]) -> String {
+  std::iter::once(ROUTE_COLUMNS.map(String::from).to_vec())
+    .chain(routes.iter().map(RouteEntry::cells))
+    .map(|row| {
+      row
+        .iter()
+        .map(|cell| csv_field(cell))
+        .collect::<Vec<_>>()
+        .join(",")
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Quotes the value for CSV when it's needed, doubling the quotes in it
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    open_api::ExecutionType,
+    terraform::APIPath,
+    util::{GatewayType, HttpMethod},
+  };
+
+  #[test]
+  fn test_route_inventory() {
+    let api = |method: HttpMethod, path: &str, placeholder: &str| OpenAPIData {
+      path: path.to_string(),
+      method,
+      uri: format!(
+        "arn:aws:apigateway:${{region}}:lambda:path/2015-03-31/functions/${{{}}}/invocations",
+        placeholder
+      ),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    };
+    let open_api_data = vec![
+      api(HttpMethod::Get, "/v1/items", "list_items_arn"),
+      api(HttpMethod::Get, "/v1/items/{id},{version}", "get_item_arn"),
+      api(HttpMethod::Delete, "/v1/items/{id}", "missing_arn"),
+    ];
+    let lambdas = vec![
+      Lambda {
+        key: "list-items".to_string(),
+        apis: vec![APIPath {
+          method: HttpMethod::Get,
+          route: "/v1/items".to_string(),
+          gateway: None,
+        }],
+        arn_template_key: Some("list_items_arn".to_string()),
+        ..Default::default()
+      },
+      Lambda {
+        key: "get-item".to_string(),
+        arn_template_key: Some("get_item_arn".to_string()),
+        step_function: true,
+        ..Default::default()
+      },
+    ];
+    let routes = route_inventory(&open_api_data, &lambdas);
+    assert_eq!(routes[1].lambda_key.as_deref(), Some("get-item"));
+    assert_eq!(routes[2].lambda_key, None);
+    assert_eq!(
+      format_routes_csv(&routes),
+      [
+        "route,method,lambda_key,arn_placeholder,execution_type,gateway,step_function",
+        "/v1/items,GET,list-items,list_items_arn,Lambda,REST API,false",
+        "\"/v1/items/{id},{version}\",GET,get-item,get_item_arn,Lambda,REST API,true",
+        "/v1/items/{id},DELETE,,,Lambda,REST API,false",
+      ]
+      .join("\n")
+    );
+  }
+}
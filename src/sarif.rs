@@ -0,0 +1,104 @@
+use std::collections::BTreeSet;
+
+use serde_json::{json, Value};
+
+use crate::findings::{Finding, Severity};
+
+/// Version of the SARIF format the log is written in
+const SARIF_VERSION: &str = "2.1.0";
+/// Schema of the SARIF log
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Formats the findings as a SARIF log for code scanning, with a rule for each finding code
+///
+/// The findings without a file have no location
+pub fn sarif_log<'a>(findings: impl IntoIterator<Item = &'a Finding>) -> Value {
+  let findings: Vec<&Finding> = findings.into_iter().collect();
+  let rules: BTreeSet<String> = findings
+    .iter()
+    .map(|finding| finding.code.to_string())
+    .collect();
+  let results: Vec<Value> = findings
+    .iter()
+    .map(|finding| sarif_result(finding))
+    .collect();
+  json!({
+    "$schema": SARIF_SCHEMA,
+    "version": SARIF_VERSION,
+    "runs": [{
+      "tool": {
+        "driver": {
+          "name": "sv",
+          "version": env!("CARGO_PKG_VERSION"),
+          "informationUri": "https://github.com/ZimboPro/sv",
+          "rules": rules.iter().map(|rule| json!({ "id": rule })).collect::<Vec<_>>(),
+        }
+      },
+      "results": results,
+    }]
+  })
+}
+
+/// Formats the finding as a SARIF result
+fn sarif_result(finding: &Finding) -> Value {
+  let level = match finding.severity {
+    Severity::Error => "error",
+    Severity::Warning => "warning",
+  };
+  let mut result = json!({
+    "ruleId": finding.code.to_string(),
+    "level": level,
+    "message": { "text": finding.message },
+  });
+  if let Some(file) = &finding.file {
+    let uri = file.display().to_string().replace('\\', "/");
+    let mut location = json!({ "artifactLocation": { "uri": uri } });
+    if let Some(line) = finding.line {
+      location["region"] = json!({ "startLine": line });
+    }
+    result["locations"] = json!([{ "physicalLocation": location }]);
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use super::*;
+  use crate::findings::FindingCode;
+
+  #[test]
+  fn test_sarif_log() {
+    let findings = vec![
+      Finding::error(FindingCode::DuplicateLambdaKey, "Key is duplicated: a")
+        .in_file(Path::new("terraform/lambda.tf")),
+      Finding::warning(FindingCode::UndocumentedRoute, "GET /a"),
+    ];
+    let log = sarif_log(&findings);
+    assert_eq!(log["version"], "2.1.0");
+    let run = &log["runs"][0];
+    assert_eq!(
+      run["tool"]["driver"]["rules"],
+      json!([{ "id": "DUPLICATE_LAMBDA_KEY" }, { "id": "UNDOCUMENTED_ROUTE" }])
+    );
+    assert_eq!(
+      run["results"],
+      json!([
+        {
+          "ruleId": "DUPLICATE_LAMBDA_KEY",
+          "level": "error",
+          "message": { "text": "Key is duplicated: a" },
+          "locations": [{
+            "physicalLocation": { "artifactLocation": { "uri": "terraform/lambda.tf" } }
+          }]
+        },
+        {
+          "ruleId": "UNDOCUMENTED_ROUTE",
+          "level": "warning",
+          "message": { "text": "GET /a" }
+        }
+      ])
+    );
+  }
+}
@@ -0,0 +1,316 @@
+use std::path::{Path, PathBuf};
+
+use simplelog::{debug, warn};
+
+use crate::{
+  findings::{Finding, FindingCode},
+  terraform::parse_source_arn,
+  util::HttpMethod,
+};
+
+/// The marker of a comment that suppresses findings, e.g. `# sv:ignore POST_NO_BODY`
+const IGNORE_MARKER: &str = "sv:ignore";
+
+/// Findings suppressed by a `# sv:ignore CODE` comment, for the line below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suppression {
+  /// The codes of the findings that are suppressed
+  pub codes: Vec<FindingCode>,
+  /// The file the comment is in
+  pub file: PathBuf,
+  /// The route the comment is above or in, for an OpenAPI path or operation or a permission
+  pub route: Option<String>,
+  /// The method the comment is above or in, for an OpenAPI operation or a permission
+  pub method: Option<HttpMethod>,
+  /// The lambda the comment is above, for an entry of the `lambdas` or `lambdas_permissions`
+  /// locals
+  pub lambda: Option<String>,
+}
+
+impl Suppression {
+  /// Whether the finding is suppressed by the comment
+  ///
+  /// A comment for a route suppresses the findings about the route, and the method when it's set
+  /// and the finding is about a method, a comment for a lambda the findings that name the lambda,
+  /// and any other comment the findings in its file
+  ///
+  /// Findings that aren't about a method, e.g. `PATH_NOT_IN_OPEN_API`, are matched on the route
+  /// alone, so a comment above a `source_arn` suppresses them too
+  pub fn matches(&self, finding: &Finding) -> bool {
+    if !self.codes.contains(&finding.code) {
+      return false;
+    }
+    let mentions = |word: &str| {
+      finding
+        .message
+        .split_whitespace()
+        .map(|word| word.trim_matches([',', ':', '\'', '"', '.']))
+        .any(|x| x == word)
+    };
+    match (&self.route, &self.lambda) {
+      (Some(route), _) => {
+        finding.route.as_ref() == Some(route)
+          && (self.method.is_none() || finding.method.is_none() || finding.method == self.method)
+      }
+      (None, Some(lambda)) => mentions(lambda),
+      (None, None) => finding.file.as_deref() == Some(self.file.as_path()),
+    }
+  }
+}
+
+/// Finds the `# sv:ignore` comments in the OpenAPI documents and Terraform files
+pub fn find_suppressions(files: &[PathBuf]) -> Vec<Suppression> {
+  let mut suppressions = Vec::new();
+  for file in files {
+    let Ok(contents) = std::fs::read_to_string(file) else {
+      continue;
+    };
+    if !contents.contains(IGNORE_MARKER) {
+      continue;
+    }
+    let found = if file.extension().is_some_and(|extension| extension == "tf") {
+      terraform_suppressions(file, &contents)
+    } else {
+      open_api_suppressions(file, &contents)
+    };
+    debug!("Found {} sv:ignore comments in {:?}", found.len(), file);
+    suppressions.extend(found);
+  }
+  suppressions
+}
+
+/// The codes of an `sv:ignore` comment, separated by commas or spaces, `None` when the line isn't
+/// one
+fn ignored_codes(file: &Path, line: &str) -> Option<Vec<FindingCode>> {
+  let comment = line
+    .strip_prefix('#')
+    .or_else(|| line.strip_prefix("//"))?
+    .trim_start();
+  let codes = comment.strip_prefix(IGNORE_MARKER)?;
+  Some(
+    codes
+      .split([',', ' '])
+      .filter(|code| !code.is_empty())
+      .filter_map(|code| {
+        let parsed = serde_json::from_value(serde_json::Value::String(code.to_string())).ok();
+        if parsed.is_none() {
+          warn!(
+            "Unknown finding code {} in the sv:ignore comment in {:?}",
+            code, file
+          );
+        }
+        parsed
+      })
+      .collect(),
+  )
+}
+
+/// The `sv:ignore` comments of an OpenAPI document, scoped to the path and operation of the line
+/// below them
+fn open_api_suppressions(file: &Path, contents: &str) -> Vec<Suppression> {
+  let mut suppressions = Vec::new();
+  let mut pending: Vec<FindingCode> = Vec::new();
+  let mut paths_indent = None;
+  let mut route: Option<(usize, String)> = None;
+  let mut method: Option<(usize, HttpMethod)> = None;
+  for line in contents.lines() {
+    let trimmed = line.trim_start();
+    if let Some(codes) = ignored_codes(file, trimmed) {
+      pending.extend(codes);
+      continue;
+    }
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+      continue;
+    }
+    let indent = line.len() - trimmed.len();
+    let key = trimmed
+      .split_once(':')
+      .map(|(key, _)| key.trim().trim_matches(['"', '\'']))
+      .unwrap_or_default();
+    if method.as_ref().is_some_and(|(level, _)| indent <= *level) {
+      method = None;
+    }
+    if route.as_ref().is_some_and(|(level, _)| indent <= *level) {
+      route = None;
+    }
+    if paths_indent.is_some_and(|level| indent <= level) {
+      paths_indent = None;
+    }
+    if key == "paths" && paths_indent.is_none() && route.is_none() {
+      paths_indent = Some(indent);
+    } else if paths_indent.is_some() && route.is_none() && key.starts_with('/') {
+      route = Some((indent, key.to_string()));
+    } else if route.is_some() && method.is_none() {
+      if let Ok(parsed) = key.parse::<HttpMethod>() {
+        method = Some((indent, parsed));
+      }
+    }
+    if !pending.is_empty() {
+      suppressions.push(Suppression {
+        codes: std::mem::take(&mut pending),
+        file: file.to_path_buf(),
+        route: route.as_ref().map(|(_, route)| route.clone()),
+        method: method.as_ref().map(|(_, method)| method.clone()),
+        lambda: None,
+      });
+    }
+  }
+  suppressions
+}
+
+/// The `sv:ignore` comments of a Terraform file, scoped to the permission or lambda of the line
+/// below them
+///
+/// A comment above a `source_arn`, or above the `{` of a permission, is for the route of the
+/// `source_arn` and a comment above `key = {` or `key = [` is for the lambda
+fn terraform_suppressions(file: &Path, contents: &str) -> Vec<Suppression> {
+  let lines: Vec<&str> = contents.lines().map(str::trim).collect();
+  let mut suppressions = Vec::new();
+  let mut pending: Vec<FindingCode> = Vec::new();
+  for (index, line) in lines.iter().enumerate() {
+    if let Some(codes) = ignored_codes(file, line) {
+      pending.extend(codes);
+      continue;
+    }
+    if line.is_empty() || line.starts_with('#') || line.starts_with("//") || pending.is_empty() {
+      continue;
+    }
+    let mut suppression = Suppression {
+      codes: std::mem::take(&mut pending),
+      file: file.to_path_buf(),
+      route: None,
+      method: None,
+      lambda: None,
+    };
+    let source_arn = if line.starts_with('{') {
+      lines[index..]
+        .iter()
+        .take_while(|line| !line.starts_with('}'))
+        .find(|line| line.starts_with("source_arn"))
+    } else {
+      Some(line).filter(|line| line.starts_with("source_arn"))
+    };
+    if let Some((_, arn)) = source_arn.and_then(|line| line.split_once('=')) {
+      if let Ok(api) = parse_source_arn(arn.trim()) {
+        suppression.route = Some(api.route);
+        suppression.method = Some(api.method).filter(|method| *method != HttpMethod::Any);
+      }
+    } else if let Some((key, value)) = line.split_once('=') {
+      if value.trim().starts_with(['{', '[']) {
+        suppression.lambda = Some(key.trim().trim_matches('"').to_string());
+      }
+    }
+    suppressions.push(suppression);
+  }
+  suppressions
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_open_api_suppressions() {
+    let file = Path::new("users.yaml");
+    let contents = r#"openapi: "3.0.1"
+paths:
+  /users:
+    # sv:ignore POST_NO_BODY
+    post:
+      summary: Create a user
+  /orders:
+    post:
+      summary: Create an order
+"#;
+    let suppressions = open_api_suppressions(file, contents);
+    assert_eq!(
+      suppressions,
+      vec![Suppression {
+        codes: vec![FindingCode::PostNoBody],
+        file: file.to_path_buf(),
+        route: Some("/users".to_string()),
+        method: Some(HttpMethod::Post),
+        lambda: None,
+      }]
+    );
+    let finding = |route: &str| {
+      Finding::warning(
+        FindingCode::PostNoBody,
+        format!("The POST method for {} doesn't have a request body", route),
+      )
+      .for_operation(&HttpMethod::Post, route)
+    };
+    assert!(suppressions[0].matches(&finding("/users")));
+    assert!(!suppressions[0].matches(&finding("/orders")));
+  }
+
+  #[test]
+  fn test_terraform_suppressions() {
+    let file = Path::new("lambda_permissions.tf");
+    let contents = r#"locals {
+  lambdas_permissions = {
+    # sv:ignore UNDOCUMENTED_ROUTE
+    lambda-1 = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        # sv:ignore PATH_NOT_IN_OPEN_API, METHOD_NOT_IN_OPEN_API
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/POST/v1/lambda/endpoint1"
+      }
+    ],
+  }
+}
+"#;
+    let suppressions = terraform_suppressions(file, contents);
+    assert_eq!(suppressions.len(), 2);
+    assert_eq!(suppressions[0].lambda.as_deref(), Some("lambda-1"));
+    assert_eq!(
+      suppressions[1].codes,
+      vec![
+        FindingCode::PathNotInOpenApi,
+        FindingCode::MethodNotInOpenApi
+      ]
+    );
+    assert_eq!(
+      suppressions[1].route.as_deref(),
+      Some("/v1/lambda/endpoint1")
+    );
+    assert_eq!(suppressions[1].method, Some(HttpMethod::Post));
+    assert!(suppressions[1].matches(
+      &Finding::error(
+        FindingCode::PathNotInOpenApi,
+        "The path /v1/lambda/endpoint1 is not defined in OpenAPI for the lambda lambda-1"
+      )
+      .for_route("/v1/lambda/endpoint1")
+    ));
+    assert!(suppressions[1].matches(&Finding::error(
+      FindingCode::MethodNotInOpenApi,
+      "The POST method is not defined for the path /v1/lambda/endpoint1 for the lambda lambda-1, \
+       found: GET"
+    )
+    .for_operation(&HttpMethod::Post, "/v1/lambda/endpoint1")));
+    assert!(!suppressions[1].matches(
+      &Finding::error(
+        FindingCode::MethodNotInOpenApi,
+        "The GET method is not defined for the path /v1/lambda/endpoint1 for the lambda lambda-1, \
+       found: PUT"
+      )
+      .for_operation(&HttpMethod::Get, "/v1/lambda/endpoint1")
+    ));
+    assert!(!suppressions[1].matches(
+      &Finding::error(
+        FindingCode::MethodNotInOpenApi,
+        "The GET method is not defined for the path /v1/lambda/endpoint1 for the lambda lambda-1, \
+       found: POST"
+      )
+      .for_operation(&HttpMethod::Get, "/v1/lambda/endpoint1")
+    ));
+    assert!(!suppressions[1].matches(
+      &Finding::error(
+        FindingCode::PathNotInOpenApi,
+        "The path /v1/lambda/endpoint2 is not defined in OpenAPI for the lambda lambda-1"
+      )
+      .for_route("/v1/lambda/endpoint2")
+    ));
+  }
+}
@@ -1,18 +1,23 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::anyhow;
 use anyhow::Ok;
+use rayon::prelude::*;
 
 use simplelog::debug;
-use simplelog::error;
 use simplelog::info;
 
-use crate::util::HttpMethod;
+use crate::config::LambdaPolicy;
+use crate::error::SvError;
+use crate::findings::{Finding, FindingCode, Findings};
+use crate::profile;
+use crate::util::{expand_glob, walk_dir, GatewayType, HttpMethod};
 
 /// The Lambda data that gets extracted
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct Lambda {
   /// Terraform lambda key
   pub key: String,
@@ -20,16 +25,22 @@ pub struct Lambda {
   pub handler: String,
   /// Is a step function
   pub step_function: bool,
+  /// Has a permission for Step Functions to invoke it
+  pub step_function_permission: bool,
   /// List of APIs and HTTP methods
   pub apis: Vec<APIPath>,
   /// ARN template key
   pub arn_template_key: Option<String>,
   /// Lambda type
   pub lambda_type: LambdaTriggerType,
+  /// The `timeout` attribute, if set
+  pub timeout: Option<String>,
+  /// The `memory_size` attribute, if set
+  pub memory_size: Option<String>,
 }
 
 /// The Lambda trigger type
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum LambdaTriggerType {
   /// Step Function
   StepFunction,
@@ -42,6 +53,17 @@ pub enum LambdaTriggerType {
   Scheduler,
 }
 
+impl std::fmt::Display for LambdaTriggerType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LambdaTriggerType::StepFunction => write!(f, "Step Function"),
+      LambdaTriggerType::ApiGateway => write!(f, "API Gateway"),
+      LambdaTriggerType::EventBridge => write!(f, "EventBridge"),
+      LambdaTriggerType::Scheduler => write!(f, "Scheduler"),
+    }
+  }
+}
+
 /// API path data
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone)]
 pub struct APIPath {
@@ -49,61 +71,107 @@ pub struct APIPath {
   pub method: HttpMethod,
   /// The route path
   pub route: String,
+  /// The type of API the permission is for, `None` when the `source_arn` doesn't tell, e.g. a
+  /// literal ARN
+  pub gateway: Option<GatewayType>,
 }
 
+/// Name of the Terraform module that creates the lambdas when none is configured
+pub const DEFAULT_LAMBDA_MODULE_NAME: &str = "lambda";
+
+/// Terraform variable values, substituted into `${var.name}` references before the files are parsed
+pub type TerraformVariables = HashMap<String, String>;
+
 /// Validate the Terraform files and extract the data
-pub fn validate_terraform(terraform: PathBuf) -> anyhow::Result<Vec<Lambda>> {
-  validate_terraform_files(&terraform)?;
-  let lambda = terraform.join("lambda.tf");
-  let lambda_permissions = terraform.join("lambda_permissions.tf");
-  let api_gw = terraform.join("api_gateway.tf");
-  let step_fn = terraform.join("step_function.tf");
-  let mut lambda_metadata = if lambda.exists() {
-    validate_lambda(lambda)?
-  } else {
-    return Err(anyhow!("File lambda.tf doesn't exist in {:?}", terraform));
+///
+/// `lambda_module_name` is the name of the module that creates the lambdas, e.g. `lambda` for
+/// `module.lambda["key"]`
+///
+/// `workspace` is the value of `terraform.workspace` used to pick the lambdas and permissions that
+/// are gated on the workspace
+///
+/// Permissions for services that aren't handled yet are errors unless `allow_unknown_principal`
+/// is set, which reports them as warnings and skips them
+///
+/// With `terraform_glob` only the files matching it are parsed instead of the files in the
+/// `terraform` folder, and the lambdas, permissions and API gateway are found in any of them
+pub fn validate_terraform(
+  terraform: PathBuf,
+  terraform_glob: Option<&str>,
+  lambda_module_name: &str,
+  variables: &TerraformVariables,
+  workspace: Option<&str>,
+  allow_unknown_principal: bool,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<Lambda>> {
+  let files = terraform_files(&terraform, terraform_glob)?;
+  validate_terraform_files(&files, variables, findings)?;
+  let sources = match terraform_glob {
+    Some(glob) => discover_sources(&files, Path::new(glob), variables)?,
+    None => read_sources(&terraform, variables)?,
   };
-  if lambda_permissions.exists() {
-    validate_lambda_permissions(lambda_permissions, &mut lambda_metadata)?;
-  } else {
-    return Err(anyhow!(
-      "File lambda_permissions.tf doesn't exist in {:?}",
-      terraform
-    ));
+  let mut lambda_metadata = validate_lambda(&sources.lambda, workspace, findings)?;
+  let files = files
+    .into_par_iter()
+    .map(|file| Ok((parse_file(&file, variables)?.1, file)))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  for finding in check_module_for_each(&files, lambda_module_name, workspace, &lambda_metadata) {
+    findings.push(finding);
   }
-  let mut lambda_data = if api_gw.exists() {
-    extract_api_gw(api_gw, lambda_metadata)?
-  } else {
-    return Err(anyhow!(
-      "File api_gateway.tf doesn't exist in {:?}",
-      terraform
-    ));
-  };
-  if step_fn.exists() {
-    lambda_data = extract_step_function(lambda_data, step_fn)?;
-    let mut valid = true;
+  validate_lambda_permissions(
+    &sources.lambda_permissions,
+    &mut lambda_metadata,
+    workspace,
+    allow_unknown_principal,
+    findings,
+  )?;
+  let mut lambda_data = extract_api_gw(
+    &sources.api_gw,
+    lambda_metadata,
+    lambda_module_name,
+    findings,
+  )?;
+  if let Some(step_fn) = &sources.step_fn {
+    lambda_data = extract_step_function(lambda_data, step_fn, lambda_module_name)?;
+    let failures = findings.failures();
+    for finding in find_step_function_permission_issues(&lambda_data) {
+      findings.push(finding);
+    }
+    // Only the lambdas with permissions that aren't used in API gateway fail, the other unused
+    // lambdas are reported without failing
+    let mut valid = findings.failures() == failures;
     for lambda_item in &lambda_data {
       if lambda_item.arn_template_key.is_none() && !lambda_item.apis.is_empty() {
-        valid = false;
-        error!(
-          "The lambda {} is not used in API gateway but is used in lambda_permissions.tf",
-          lambda_item.key
-        )
+        let failures = findings.failures();
+        findings.push(Finding::error(
+          FindingCode::LambdaNotInApiGateway,
+          format!(
+            "The lambda {} is not used in API gateway but is used in lambda_permissions.tf",
+            lambda_item.key
+          ),
+        ));
+        valid &= findings.failures() == failures;
       }
       if lambda_item.arn_template_key.is_some() && lambda_item.apis.is_empty() {
-        error!(
-          "The lambda arn {} exits in API gateway but not in lambda_permissions.tf",
-          lambda_item.key
-        )
+        findings.push(Finding::error(
+          FindingCode::LambdaNotInPermissions,
+          format!(
+            "The lambda arn {} exits in API gateway but not in lambda_permissions.tf",
+            lambda_item.key
+          ),
+        ));
       }
       if !lambda_item.step_function
         && lambda_item.arn_template_key.is_none()
         && lambda_item.apis.is_empty()
       {
-        error!(
-          "The lambda arn {} exits in lambda.tf but used anywhere else",
-          lambda_item.key
-        )
+        findings.push(Finding::error(
+          FindingCode::UnusedLambda,
+          format!(
+            "The lambda arn {} exits in lambda.tf but used anywhere else",
+            lambda_item.key
+          ),
+        ));
       }
     }
     if !valid {
@@ -111,53 +179,486 @@ pub fn validate_terraform(terraform: PathBuf) -> anyhow::Result<Vec<Lambda>> {
     }
     Ok(lambda_data)
   } else {
-    let mut valid = true;
+    let failures = findings.failures();
     for lambda_item in &lambda_data {
       if lambda_item.arn_template_key.is_none() && !lambda_item.apis.is_empty() {
-        valid = false;
-        error!("The lambda {} is not use in API gateway", lambda_item.key)
+        findings.push(Finding::error(
+          FindingCode::LambdaNotInApiGateway,
+          format!("The lambda {} is not use in API gateway", lambda_item.key),
+        ));
       }
     }
-    if !valid {
+    if findings.failures() > failures {
       return Err(anyhow!("Invalid Terraform configuration"));
     }
     Ok(lambda_data)
   }
 }
 
+/// The Terraform files matching the glob when it's set, otherwise the files in the folder
+pub fn terraform_files(terraform: &Path, glob: Option<&str>) -> anyhow::Result<Vec<PathBuf>> {
+  let Some(glob) = glob else {
+    return Ok(find_files(terraform, OsStr::new("tf")));
+  };
+  let files: Vec<PathBuf> = expand_glob(glob)?
+    .into_iter()
+    .filter(|file| file.extension() == Some(OsStr::new("tf")))
+    .collect();
+  if files.is_empty() {
+    return Err(SvError::Path(format!("No Terraform files match {}", glob)).into());
+  }
+  Ok(files)
+}
+
 /// Finds all the files with the extension in the directory recursively for Terraform files
-fn find_files(path: &std::path::Path, extension: &OsStr) -> Vec<PathBuf> {
-  let mut files = Vec::new();
-  for entry in path.read_dir().expect("Failed to read directory").flatten() {
-    if entry.path().is_dir() && !entry.path().ends_with(".terraform") {
-      files.append(&mut find_files(&entry.path(), extension));
-    } else if entry.path().extension() == Some(extension) {
-      files.push(entry.path());
+pub(crate) fn find_files(path: &std::path::Path, extension: &OsStr) -> Vec<PathBuf> {
+  walk_dir(path)
+    .filter_entry(|entry| entry.file_name() != ".terraform")
+    .build()
+    .flatten()
+    .filter(|entry| entry.path().is_file() && entry.path().extension() == Some(extension))
+    .map(|entry| entry.into_path())
+    .collect()
+}
+
+/// Loads the variables from a `.tfvars` file, only string, number and bool values are loaded
+pub fn load_var_file(path: &Path) -> anyhow::Result<TerraformVariables> {
+  let contents = std::fs::read_to_string(path)
+    .map_err(|e| SvError::Io(format!("Failed to read {:?}: {}", path, e)))?;
+  let body = hcl::parse(&contents)
+    .map_err(|e| SvError::Config(format!("Failed to parse {:?}: {}", path, e)))?;
+  let mut variables = TerraformVariables::new();
+  for attribute in body.attributes() {
+    let value = match &attribute.expr {
+      hcl::Expression::String(value) => value.to_string(),
+      hcl::Expression::Number(value) => value.to_string(),
+      hcl::Expression::Bool(value) => value.to_string(),
+      _ => {
+        debug!(
+          "Skipping the variable {} as it isn't a literal",
+          attribute.key
+        );
+        continue;
+      }
+    };
+    variables.insert(attribute.key.to_string(), value);
+  }
+  Ok(variables)
+}
+
+/// Replaces the `${var.name}` references with the values of the variables
+fn substitute_variables(contents: &str, variables: &TerraformVariables) -> String {
+  variables
+    .iter()
+    .fold(contents.to_string(), |contents, (name, value)| {
+      contents.replace(&format!("${{var.{}}}", name), value)
+    })
+}
+
+/// Gets the names of the `${var.name}` references that weren't substituted
+fn unresolved_variables(contents: &str) -> Vec<&str> {
+  let mut names: Vec<&str> = contents
+    .split("${var.")
+    .skip(1)
+    .filter_map(|rest| rest.split_once('}'))
+    .map(|(name, _)| name)
+    .collect();
+  names.sort();
+  names.dedup();
+  names
+}
+
+/// Reads, substitutes the variables and parses the Terraform file, naming the file in any error
+fn parse_file(path: &Path, variables: &TerraformVariables) -> anyhow::Result<(String, hcl::Body)> {
+  profile::time("parse_terraform", Some(path), || {
+    let contents =
+      std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+    let contents = substitute_variables(&contents, variables);
+    let body = hcl::parse(&contents).map_err(|e| anyhow!("Failed to parse {:?}: {}", path, e))?;
+    Ok((contents, body))
+  })
+}
+
+/// A Terraform file, or the part of one, that a part of the configuration is read from
+struct TerraformSource {
+  /// The file the contents were read from
+  path: PathBuf,
+  /// The contents with the variables substituted
+  contents: String,
+}
+
+impl TerraformSource {
+  /// Reads the file and substitutes the variables
+  fn read(path: &Path, variables: &TerraformVariables) -> anyhow::Result<Self> {
+    let (contents, _) = parse_file(path, variables)?;
+    Ok(TerraformSource {
+      path: path.to_path_buf(),
+      contents,
+    })
+  }
+
+  /// Parses the contents, naming the file in any error
+  fn parse(&self) -> anyhow::Result<hcl::Body> {
+    hcl::parse(&self.contents).map_err(|e| anyhow!("Failed to parse {:?}: {}", self.path, e))
+  }
+
+  /// Gets the local with the name as its own `locals` block
+  fn extract_local(&self, name: &str) -> Option<TerraformSource> {
+    local_value(&self.contents, name).map(|value| TerraformSource {
+      path: self.path.clone(),
+      contents: format!("locals {{\n  {} = {}\n}}\n", name, value),
+    })
+  }
+
+  /// Gets the top level blocks that match, blanking the other lines so the line numbers are kept
+  fn extract_blocks(&self, matches: impl Fn(&str) -> bool) -> Option<TerraformSource> {
+    let lines: Vec<&str> = self.contents.lines().collect();
+    let blocks: Vec<(usize, usize)> = top_level_blocks(&lines)
+      .into_iter()
+      .filter(|(start, end)| matches(&lines[*start..=*end].join("\n")))
+      .collect();
+    if blocks.is_empty() {
+      return None;
+    }
+    let contents = lines
+      .iter()
+      .enumerate()
+      .map(|(index, line)| {
+        if blocks
+          .iter()
+          .any(|(start, end)| (*start..=*end).contains(&index))
+        {
+          *line
+        } else {
+          ""
+        }
+      })
+      .collect::<Vec<&str>>()
+      .join("\n");
+    Some(TerraformSource {
+      path: self.path.clone(),
+      contents,
+    })
+  }
+}
+
+/// The sources of each part of the Terraform configuration
+struct TerraformSources {
+  /// The `lambdas` local
+  lambda: TerraformSource,
+  /// The `lambdas_permissions` local
+  lambda_permissions: TerraformSource,
+  /// The API gateway that references the lambda ARNs
+  api_gw: TerraformSource,
+  /// The step functions, if there are any
+  step_fn: Option<TerraformSource>,
+}
+
+/// Names of the files the configuration is split into, read instead of finding the parts in all
+/// the Terraform files when any of them exists
+pub const SPLIT_FILES: [&str; 3] = ["lambda.tf", "lambda_permissions.tf", "api_gateway.tf"];
+
+/// Reads the split files when any of them exists, and finds the parts of the configuration in all
+/// the Terraform files otherwise
+fn read_sources(
+  terraform: &Path,
+  variables: &TerraformVariables,
+) -> anyhow::Result<TerraformSources> {
+  if SPLIT_FILES.iter().any(|name| terraform.join(name).exists()) {
+    read_split_sources(terraform, variables)
+  } else {
+    discover_sources(
+      &find_files(terraform, OsStr::new("tf")),
+      terraform,
+      variables,
+    )
+  }
+}
+
+/// Checks that the lambdas, the permissions and the API gateway can be found in the Terraform
+/// files, without validating them
+pub fn check_sources(terraform: &Path) -> anyhow::Result<()> {
+  read_sources(terraform, &TerraformVariables::new()).map(|_| ())
+}
+
+/// The file the `lambdas_permissions` local is in, `None` when it can't be found
+pub fn permissions_file(terraform: &Path, terraform_glob: Option<&str>) -> Option<PathBuf> {
+  let variables = TerraformVariables::new();
+  let sources = match terraform_glob {
+    Some(glob) => discover_sources(
+      &terraform_files(terraform, Some(glob)).ok()?,
+      Path::new(glob),
+      &variables,
+    ),
+    None => read_sources(terraform, &variables),
+  };
+  sources.ok().map(|sources| sources.lambda_permissions.path)
+}
+
+/// Reads lambda.tf, lambda_permissions.tf, api_gateway.tf and the optional step_function.tf
+fn read_split_sources(
+  terraform: &Path,
+  variables: &TerraformVariables,
+) -> anyhow::Result<TerraformSources> {
+  let read = |name: &str| {
+    let path = terraform.join(name);
+    if !path.exists() {
+      return Err(anyhow!("File {} doesn't exist in {:?}", name, terraform));
     }
+    TerraformSource::read(&path, variables)
+  };
+  let step_fn = terraform.join("step_function.tf");
+  Ok(TerraformSources {
+    lambda: read("lambda.tf")?,
+    lambda_permissions: read("lambda_permissions.tf")?,
+    api_gw: read("api_gateway.tf")?,
+    step_fn: if step_fn.exists() {
+      Some(TerraformSource::read(&step_fn, variables)?)
+    } else {
+      None
+    },
+  })
+}
+
+/// Finds the parts of the configuration in any of the Terraform files, e.g. a single `main.tf`,
+/// `terraform` is the folder or glob the files are from
+///
+/// The API gateway is the blocks with a `templatefile` of a YAML file and the step functions are
+/// the blocks with a `templatefile` of a JSON file
+fn discover_sources(
+  files: &[PathBuf],
+  terraform: &Path,
+  variables: &TerraformVariables,
+) -> anyhow::Result<TerraformSources> {
+  info!("Finding the lambdas, permissions and API gateway in the Terraform files");
+  let mut lambda = None;
+  let mut lambda_permissions = None;
+  let mut api_gw = None;
+  let mut step_fn = None;
+  for file in files {
+    let source = TerraformSource::read(file, variables)?;
+    lambda = lambda.or_else(|| source.extract_local("lambdas"));
+    lambda_permissions = lambda_permissions.or_else(|| source.extract_local("lambdas_permissions"));
+    api_gw = api_gw.or_else(|| {
+      source.extract_blocks(|block| {
+        block.contains("templatefile(") && (block.contains(".yaml") || block.contains(".yml"))
+      })
+    });
+    step_fn = step_fn.or_else(|| {
+      source.extract_blocks(|block| block.contains("templatefile(") && block.contains(".json"))
+    });
   }
-  files
+  Ok(TerraformSources {
+    lambda: lambda.ok_or_else(|| anyhow!("The lambdas local isn't defined in {:?}", terraform))?,
+    lambda_permissions: lambda_permissions.ok_or_else(|| {
+      anyhow!(
+        "The lambdas_permissions local isn't defined in {:?}",
+        terraform
+      )
+    })?,
+    api_gw: api_gw.ok_or_else(|| {
+      anyhow!(
+        "No API gateway with a templatefile of the OpenAPI document in {:?}",
+        terraform
+      )
+    })?,
+    step_fn,
+  })
+}
+
+/// Gets the object assigned to the local, e.g. `{ ... }` for `lambdas = { ... }`
+fn local_value<'a>(contents: &'a str, name: &str) -> Option<&'a str> {
+  let mut offset = 0;
+  while let Some(index) = contents[offset..].find(name) {
+    let start = offset + index;
+    offset = start + name.len();
+    if contents[..start]
+      .chars()
+      .last()
+      .is_some_and(|c| !c.is_whitespace())
+    {
+      continue;
+    }
+    let value = match contents[offset..].trim_start().strip_prefix('=') {
+      Some(rest) => rest.trim_start(),
+      None => continue,
+    };
+    if !value.starts_with('{') {
+      continue;
+    }
+    let mut depth = 0;
+    for (index, c) in value.char_indices() {
+      match c {
+        '{' => depth += 1,
+        '}' => {
+          depth -= 1;
+          if depth == 0 {
+            return Some(&value[..=index]);
+          }
+        }
+        _ => {}
+      }
+    }
+    return None;
+  }
+  None
+}
+
+/// Finds the keys that are repeated in the objects assigned to the local, e.g. `a` for
+/// `lambdas = { a = {...}, a = {...} }`
+///
+/// Only the outermost objects of the expression are checked, e.g. each object of a `merge`. The
+/// parser keeps one item for the repeated keys, so the items it dropped are parsed again from the
+/// text between the items it kept
+fn duplicate_local_keys(contents: &str, name: &str) -> Vec<String> {
+  let Some(body) = hcl_edit::parser::parse_body(contents).ok() else {
+    return Vec::new();
+  };
+  let mut duplicates = Vec::new();
+  body
+    .iter()
+    .filter_map(hcl_edit::structure::Structure::as_block)
+    .filter(|block| block.ident.as_str() == "locals")
+    .flat_map(|block| block.body.iter())
+    .filter_map(hcl_edit::structure::Structure::as_attribute)
+    .filter(|attribute| attribute.key.as_str() == name)
+    .for_each(|attribute| collect_duplicate_keys(contents, &attribute.value, &mut duplicates));
+  duplicates
+}
+
+/// Collects the repeated keys of the outermost objects of the expression
+fn collect_duplicate_keys(
+  contents: &str,
+  expr: &hcl_edit::expr::Expression,
+  duplicates: &mut Vec<String>,
+) {
+  use hcl_edit::{expr::Expression, Span};
+  match expr {
+    Expression::Object(object) => {
+      let Some(span) = object.span() else {
+        return;
+      };
+      let mut items: Vec<_> = object
+        .iter()
+        .filter_map(|(key, value)| Some((key, key.span()?, value.expr().span()?)))
+        .collect();
+      items.sort_by_key(|(_, key_span, _)| key_span.start);
+      let mut keys = Vec::new();
+      let mut end = span.start + 1;
+      for (key, key_span, value_span) in items {
+        keys.extend(dropped_keys(contents.get(end..key_span.start)));
+        keys.extend(edit_object_key_name(key));
+        end = value_span.end;
+      }
+      keys.extend(dropped_keys(contents.get(end..span.end - 1)));
+      for (position, key) in keys.iter().enumerate() {
+        if keys[..position].contains(key) && !duplicates.contains(key) {
+          duplicates.push(key.clone());
+        }
+      }
+    }
+    Expression::FuncCall(call) => call
+      .args
+      .iter()
+      .for_each(|arg| collect_duplicate_keys(contents, arg, duplicates)),
+    Expression::Conditional(conditional) => {
+      collect_duplicate_keys(contents, &conditional.true_expr, duplicates);
+      collect_duplicate_keys(contents, &conditional.false_expr, duplicates);
+    }
+    Expression::Parenthesis(parenthesis) => {
+      collect_duplicate_keys(contents, parenthesis.inner(), duplicates)
+    }
+    _ => {}
+  }
+}
+
+/// Gets the keys of the items in the text between two items of an object, which is only
+/// separators and comments unless the parser dropped the items for repeating a key
+fn dropped_keys(between: Option<&str>) -> Vec<String> {
+  let Some(between) = between else {
+    return Vec::new();
+  };
+  let items = between.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+  match hcl_edit::parser::parse_expr(&format!("{{{}}}", items)).ok() {
+    Some(hcl_edit::expr::Expression::Object(object)) => object
+      .iter()
+      .filter_map(|(key, _)| edit_object_key_name(key))
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// Gets the name of an object key, either an identifier or a quoted string
+fn edit_object_key_name(key: &hcl_edit::expr::ObjectKey) -> Option<String> {
+  match key {
+    hcl_edit::expr::ObjectKey::Ident(ident) => Some(ident.as_str().to_string()),
+    hcl_edit::expr::ObjectKey::Expression(hcl_edit::expr::Expression::String(name)) => {
+      Some(name.to_string())
+    }
+    _ => None,
+  }
+}
+
+/// Gets the first and last line of each top level block, which ends at a `}` at the start of a line
+fn top_level_blocks(lines: &[&str]) -> Vec<(usize, usize)> {
+  let mut blocks = Vec::new();
+  let mut start = None;
+  for (index, line) in lines.iter().enumerate() {
+    if start.is_none() && !line.starts_with(char::is_whitespace) && line.trim_end().ends_with('{') {
+      start = Some(index);
+    } else if line.trim_end() == "}" {
+      if let Some(start) = start.take() {
+        blocks.push((start, index));
+      }
+    }
+  }
+  blocks
 }
 
 /// Check if all the Terraform files are valid
-fn validate_terraform_files(path: &Path) -> anyhow::Result<()> {
+///
+/// When variables are given, the references to variables that aren't set are reported as warnings
+fn validate_terraform_files(
+  files: &[PathBuf],
+  variables: &TerraformVariables,
+  findings: &mut Findings,
+) -> anyhow::Result<()> {
   info!("Validating Terraform files");
-  let files = find_files(path, OsStr::new("tf"));
-  for file in files {
-    let lambda_contents = std::fs::read_to_string(file)?;
-    let _ = hcl::parse(&lambda_contents)?;
+  let files = files
+    .par_iter()
+    .map(|file| Ok((parse_file(file, variables)?.0, file)))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  for (contents, file) in files {
+    if !variables.is_empty() {
+      for name in unresolved_variables(&contents) {
+        findings.push(
+          Finding::warning(
+            FindingCode::UnresolvedVariable,
+            format!(
+              "The variable {} used in {:?} is not set in the var file",
+              name, file
+            ),
+          )
+          .in_file(file),
+        );
+      }
+    }
   }
   Ok(())
 }
 
 /// Validate and extract from the lambda.tf file
-fn validate_lambda(lambda: PathBuf) -> anyhow::Result<Vec<Lambda>> {
+fn validate_lambda(
+  lambda: &TerraformSource,
+  workspace: Option<&str>,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<Lambda>> {
   info!("Validating lambda.tf config");
   let mut lambda_metadata: Vec<Lambda> = Vec::new();
-  let mut valid = true;
-  debug!("Read Lambda file: {:?}", lambda);
-  let lambda_contents = std::fs::read_to_string(lambda)?;
+  let failures = findings.failures();
+  debug!("Read Lambda file: {:?}", lambda.path);
   debug!("Parsing Lambda file");
-  let body = hcl::parse(&lambda_contents)?;
+  let body = lambda.parse()?;
   let locals = body
     .blocks()
     .find(|x| x.identifier.to_string() == *"locals")
@@ -167,34 +668,29 @@ fn validate_lambda(lambda: PathBuf) -> anyhow::Result<Vec<Lambda>> {
     .attributes()
     .find(|x| x.key.to_string() == *"lambdas")
     .expect("Expected 'lambdas' variable to be set");
-  match &lambdas.expr {
+  match &resolve_workspace(&lambdas.expr, workspace) {
     hcl::Expression::Object(s) => {
       for key in s.keys() {
         let l = s.get_key_value(key).expect("Failed to get key");
 
-        let lambda_key = match l.0 {
-          hcl::ObjectKey::Identifier(s) => s.to_string(),
-          hcl::ObjectKey::Expression(_) => todo!("Unsupported lambda key"),
-          _ => todo!("Should not get here"),
-        };
+        let lambda_key = object_key_name(l.0).expect("Unsupported lambda key");
         match &l.1 {
           hcl::Expression::Object(data) => {
-            let handler = data
-              .iter()
-              .find_map(|data_item| match data_item.0 {
-                hcl::ObjectKey::Identifier(data_key) => {
-                  if data_key.to_string().to_lowercase() == "handler".to_lowercase() {
-                    return Some(data_item.1.to_string().replace('\"', ""));
-                  }
-                  None
-                }
-                hcl::ObjectKey::Expression(_) => None,
-                _ => None,
-              })
-              .expect("Failed to get handler");
+            let handler = find_lambda_attribute(data, "handler").unwrap_or_default();
+            if handler.is_empty() {
+              findings.push(
+                Finding::error(
+                  FindingCode::MissingHandler,
+                  format!("The lambda {} has no handler", lambda_key),
+                )
+                .in_file(&lambda.path),
+              );
+            }
             lambda_metadata.push(Lambda {
               key: lambda_key,
               handler,
+              timeout: find_lambda_attribute(data, "timeout"),
+              memory_size: find_lambda_attribute(data, "memory_size"),
               ..Default::default()
             })
           }
@@ -206,56 +702,355 @@ fn validate_lambda(lambda: PathBuf) -> anyhow::Result<Vec<Lambda>> {
       panic!("Expected Object");
     }
   }
-  if !lambda_metadata.is_empty() {
-    let mut index = 0;
-    let start = lambda_contents
-      .find("lambdas")
-      .expect("Could not find 'lambdas' in file");
-    let (_, end_str) = lambda_contents.split_at(start);
-    let end = lambda_contents
-      .find("\n}")
-      .expect("Could not find closing '}', expecting it to be '\\n}'");
-    let (locals, _) = end_str.split_at(end);
-    while index < lambda_metadata.len() - 1 {
-      let mut j = index + 1;
-      let meta = lambda_metadata
-        .get(index)
-        .expect("Failed to get lambda details");
-      if locals.matches(&meta.key).count() > 1 {
-        valid = false;
-        error!("Key is duplicated: {}", meta.key);
+  for key in duplicate_local_keys(&lambda.contents, "lambdas") {
+    findings.push(
+      Finding::error(
+        FindingCode::DuplicateLambdaKey,
+        format!("Key is duplicated: {}", key),
+      )
+      .in_file(&lambda.path),
+    );
+  }
+  for finding in check_handler_uniqueness(&lambda_metadata) {
+    findings.push(finding.in_file(&lambda.path));
+  }
+  if findings.failures() > failures {
+    return Err(anyhow!("Invalid lambda.tf file"));
+  }
+  Ok(lambda_metadata)
+}
+
+/// Finds the handlers that are used by more than one lambda, reporting all the lambdas that share
+/// a handler together in the order they are declared
+///
+/// Lambdas without a handler are skipped
+fn check_handler_uniqueness(lambdas: &[Lambda]) -> Vec<Finding> {
+  let mut keys: HashMap<&str, Vec<&str>> = HashMap::new();
+  for lambda in lambdas.iter().filter(|lambda| !lambda.handler.is_empty()) {
+    keys
+      .entry(lambda.handler.as_str())
+      .or_default()
+      .push(lambda.key.as_str());
+  }
+  lambdas
+    .iter()
+    .filter_map(|lambda| {
+      let shared = keys.get(lambda.handler.as_str())?;
+      (shared.len() > 1 && shared[0] == lambda.key).then(|| {
+        Finding::error(
+          FindingCode::DuplicateHandler,
+          format!(
+            "The lambda keys {} are using the same handler {}",
+            shared
+              .iter()
+              .map(|key| format!("'{}'", key))
+              .collect::<Vec<_>>()
+              .join(", "),
+            lambda.handler
+          ),
+        )
+      })
+    })
+    .collect()
+}
+
+/// Picks the branches of the conditionals on `terraform.workspace` and combines the objects that
+/// are merged with `merge`, so the lambdas that are gated on the workspace are found
+///
+/// Conditionals that use anything else, e.g. variables, and merges of anything but objects are
+/// left as they are
+fn resolve_workspace(expr: &hcl::Expression, workspace: Option<&str>) -> hcl::Expression {
+  let mut context = hcl::eval::Context::new();
+  if let Some(workspace) = workspace {
+    let mut terraform = hcl::Map::new();
+    terraform.insert("workspace".to_string(), hcl::Value::from(workspace));
+    context.declare_var("terraform", hcl::Value::Object(terraform));
+  }
+  resolve_expression(expr, &context)
+}
+
+/// Resolves the conditionals and merges of the expression with the values in `context`
+fn resolve_expression(expr: &hcl::Expression, context: &hcl::eval::Context) -> hcl::Expression {
+  use hcl::eval::Evaluate;
+  match expr {
+    hcl::Expression::Parenthesis(inner) => resolve_expression(inner, context),
+    hcl::Expression::Conditional(conditional) => match conditional.cond_expr.evaluate(context) {
+      std::result::Result::Ok(hcl::Value::Bool(true)) => {
+        resolve_expression(&conditional.true_expr, context)
       }
-      while j < lambda_metadata.len() {
-        let t = lambda_metadata
-          .get(j)
-          .expect("Failed to get lambda details");
-        if meta.handler == t.handler {
-          valid = false;
-          error!(
-            "Both lambda keys '{}' and '{}' are using the same handler {}",
-            meta.key, t.key, t.handler
-          );
+      std::result::Result::Ok(hcl::Value::Bool(false)) => {
+        resolve_expression(&conditional.false_expr, context)
+      }
+      _ => expr.clone(),
+    },
+    hcl::Expression::FuncCall(call) if call.name.to_string() == "merge" => {
+      let mut merged = hcl::Object::new();
+      for arg in &call.args {
+        match resolve_expression(arg, context) {
+          hcl::Expression::Object(object) => merged.extend(object),
+          _ => return expr.clone(),
         }
-        j += 1;
       }
-      index += 1;
+      hcl::Expression::Object(merged)
     }
+    _ => expr.clone(),
   }
-  if !valid {
-    return Err(anyhow!("Invalid lambda.tf file"));
+}
+
+/// Reports the keys of the lambda module's `for_each` that aren't in the `lambdas` local, e.g. for
+/// `module "lambda" { for_each = merge(local.lambdas, local.extra_lambdas) }`
+///
+/// Nothing is reported when there's no such module or its `for_each` can't be resolved to the keys
+/// of an object, e.g. a `for` expression with a condition
+fn check_module_for_each(
+  files: &[(hcl::Body, PathBuf)],
+  lambda_module_name: &str,
+  workspace: Option<&str>,
+  lambdas: &[Lambda],
+) -> Vec<Finding> {
+  let locals: HashMap<String, &hcl::Expression> = files
+    .iter()
+    .flat_map(|(body, _)| body.blocks())
+    .filter(|block| block.identifier.as_str() == "locals")
+    .flat_map(|block| block.body.attributes())
+    .map(|attribute| (attribute.key.to_string(), &attribute.expr))
+    .collect();
+  let Some((for_each, file)) = files.iter().find_map(|(body, file)| {
+    body
+      .blocks()
+      .filter(|block| block.identifier.as_str() == "module")
+      .filter(|block| {
+        block
+          .labels()
+          .first()
+          .is_some_and(|label| label.as_str() == lambda_module_name)
+      })
+      .find_map(|block| {
+        block
+          .body
+          .attributes()
+          .find(|attribute| attribute.key.as_str() == "for_each")
+      })
+      .map(|attribute| (&attribute.expr, file))
+  }) else {
+    return Vec::new();
+  };
+  let Some(keys) = object_keys(for_each, &locals, workspace, 0) else {
+    debug!("Skipping the lambda module's for_each as its keys can't be resolved");
+    return Vec::new();
+  };
+  keys
+    .into_iter()
+    .filter(|key| !lambdas.iter().any(|lambda| lambda.key == *key))
+    .map(|key| {
+      Finding::warning(
+        FindingCode::UnknownForEachKey,
+        format!(
+          "The for_each of the module {} has the key {} which is not in the lambdas local",
+          lambda_module_name, key
+        ),
+      )
+      .in_file(file)
+    })
+    .collect()
+}
+
+/// Gets the keys of the object the expression resolves to, following the `local.name` references
+/// and resolving the conditionals on the workspace and the merges
+///
+/// A `for` expression keeps the keys of its collection when it has no condition and uses the key
+/// of each element as is
+fn object_keys(
+  expr: &hcl::Expression,
+  locals: &HashMap<String, &hcl::Expression>,
+  workspace: Option<&str>,
+  depth: usize,
+) -> Option<Vec<String>> {
+  // Locals that reference each other in a cycle aren't valid Terraform
+  if depth > locals.len() {
+    return None;
+  }
+  match resolve_workspace(expr, workspace) {
+    hcl::Expression::Object(object) => object.keys().map(object_key_name).collect(),
+    hcl::Expression::Traversal(traversal) => {
+      match (&traversal.expr, traversal.operators.as_slice()) {
+        (hcl::Expression::Variable(variable), [hcl::TraversalOperator::GetAttr(name)])
+          if variable.as_str() == "local" =>
+        {
+          object_keys(locals.get(name.as_str())?, locals, workspace, depth + 1)
+        }
+        _ => None,
+      }
+    }
+    hcl::Expression::FuncCall(call) if call.name.to_string() == "merge" => {
+      let mut keys: Vec<String> = Vec::new();
+      for arg in &call.args {
+        for key in object_keys(arg, locals, workspace, depth + 1)? {
+          if !keys.contains(&key) {
+            keys.push(key);
+          }
+        }
+      }
+      Some(keys)
+    }
+    hcl::Expression::ForExpr(for_expr) => match (&for_expr.key_var, &for_expr.key_expr) {
+      (Some(key_var), Some(hcl::Expression::Variable(key)))
+        if for_expr.cond_expr.is_none() && key.as_str() == key_var.as_str() =>
+      {
+        object_keys(&for_expr.collection_expr, locals, workspace, depth + 1)
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/// Gets the value of an attribute in a lambda's config
+fn find_lambda_attribute(
+  data: &hcl::Object<hcl::ObjectKey, hcl::Expression>,
+  name: &str,
+) -> Option<String> {
+  data.iter().find_map(|data_item| match data_item.0 {
+    hcl::ObjectKey::Identifier(data_key) => {
+      if data_key.to_string().to_lowercase() == name.to_lowercase() {
+        return Some(attribute_value(data_item.1));
+      }
+      None
+    }
+    hcl::ObjectKey::Expression(_) => None,
+    _ => None,
+  })
+}
+
+/// Gets the value of an attribute as it is written, without the quotes of a string
+///
+/// Templates, e.g. `"${var.prefix}.handler"`, are kept verbatim when the variables weren't
+/// substituted from a `.tfvars` file, and other expressions are formatted as HCL
+fn attribute_value(expr: &hcl::Expression) -> String {
+  match expr {
+    hcl::Expression::String(value) => value.clone(),
+    hcl::Expression::TemplateExpr(template) => template.to_string(),
+    expr => expr.to_string(),
+  }
+}
+
+/// Warns about lambdas whose `timeout` or `memory_size` are missing or outside the policy's bounds
+///
+/// Values that aren't numbers, e.g. variables, can't be checked against the bounds
+pub fn check_lambda_policy(lambdas: &[Lambda], policy: &LambdaPolicy, findings: &mut Findings) {
+  info!("Checking lambda policy");
+  for lambda in lambdas {
+    check_lambda_setting(
+      lambda,
+      "timeout",
+      lambda.timeout.as_deref(),
+      (
+        policy.require_timeout,
+        policy.min_timeout,
+        policy.max_timeout,
+      ),
+      FindingCode::LambdaTimeout,
+      findings,
+    );
+    check_lambda_setting(
+      lambda,
+      "memory_size",
+      lambda.memory_size.as_deref(),
+      (
+        policy.require_memory_size,
+        policy.min_memory_size,
+        policy.max_memory_size,
+      ),
+      FindingCode::LambdaMemorySize,
+      findings,
+    );
+  }
+}
+
+/// Warns about lambdas whose ARN placeholder isn't the lambda key with `-` replaced by `_` followed
+/// by `suffix`, e.g. `lambda_1_arn` for `lambda-1` with the suffix `_arn`, as a placeholder that
+/// doesn't follow the convention is often wired to the wrong lambda
+pub fn check_arn_suffix(lambdas: &[Lambda], suffix: &str, findings: &mut Findings) {
+  info!("Checking ARN placeholder names");
+  for lambda in lambdas {
+    let Some(placeholder) = &lambda.arn_template_key else {
+      continue;
+    };
+    let expected = format!("{}{}", lambda.key.replace('-', "_"), suffix);
+    if *placeholder != expected {
+      findings.push(Finding::warning(
+        FindingCode::ArnPlaceholderName,
+        format!(
+          "The lambda {} uses the ARN placeholder {}, expected {}",
+          lambda.key, placeholder, expected
+        ),
+      ));
+    }
+  }
+}
+
+/// Checks a single lambda setting against whether it is required and its minimum and maximum
+fn check_lambda_setting(
+  lambda: &Lambda,
+  name: &str,
+  value: Option<&str>,
+  (required, min, max): (bool, Option<u64>, Option<u64>),
+  code: FindingCode,
+  findings: &mut Findings,
+) {
+  let value = match value {
+    Some(value) => value,
+    None => {
+      if required {
+        findings.push(Finding::warning(
+          code,
+          format!("The lambda {} doesn't set {}", lambda.key, name),
+        ));
+      }
+      return;
+    }
+  };
+  let number = match value.trim().parse::<u64>() {
+    std::result::Result::Ok(number) => number,
+    Err(_) => {
+      debug!(
+        "Skipping {} of {} as it isn't a number: {}",
+        name, lambda.key, value
+      );
+      return;
+    }
+  };
+  if let Some(min) = min.filter(|min| number < *min) {
+    findings.push(Finding::warning(
+      code,
+      format!(
+        "The lambda {} has a {} of {} which is below the minimum of {}",
+        lambda.key, name, number, min
+      ),
+    ));
+  }
+  if let Some(max) = max.filter(|max| number > *max) {
+    findings.push(Finding::warning(
+      code,
+      format!(
+        "The lambda {} has a {} of {} which is above the maximum of {}",
+        lambda.key, name, number, max
+      ),
+    ));
   }
-  Ok(lambda_metadata)
 }
 
 /// Validate and extract data from lambda_permissions.tf
 fn validate_lambda_permissions(
-  lambda_permissions: PathBuf,
+  lambda_permissions: &TerraformSource,
   lambda_metadata: &mut [Lambda],
+  workspace: Option<&str>,
+  allow_unknown_principal: bool,
+  findings: &mut Findings,
 ) -> anyhow::Result<()> {
   info!("Validating lambda_permissions.tf config");
-  let mut valid = true;
-  let lambda_contents = std::fs::read_to_string(lambda_permissions)?;
-  let body = hcl::parse(&lambda_contents)?;
+  let failures = findings.failures();
+  let body = lambda_permissions.parse()?;
   let locals = body
     .blocks()
     .find(|x| x.identifier.to_string() == *"locals")
@@ -265,18 +1060,28 @@ fn validate_lambda_permissions(
     .attributes()
     .find(|x| x.key.to_string() == *"lambdas_permissions")
     .expect("Variable lambdas_permissions doesn't exist in locals");
-  match &lambdas.expr {
+  match &resolve_workspace(&lambdas.expr, workspace) {
     hcl::Expression::Object(permissions) => {
-      let mut lambda_permission_keys = Vec::new();
-      for permission_group in permissions.keys() {
-        let lambda_key = match permission_group {
-          hcl::ObjectKey::Identifier(s) => s.to_string(),
-          hcl::ObjectKey::Expression(_) => todo!("Unsupported lambda key"),
-          _ => todo!("Should not get here"),
-        };
-        lambda_permission_keys.push(lambda_key);
-      }
       for permission_group in permissions {
+        let key = object_key_name(permission_group.0).expect("Unsupported lambda key");
+        let message = match lambda_metadata.iter().find(|x| x.key == key) {
+          None => Some(format!(
+            "lambda_permissions references undefined lambda '{}'",
+            key
+          )),
+          Some(lambda) if lambda.handler.is_empty() => Some(format!(
+            "lambda_permissions references lambda '{}' which has no handler",
+            key
+          )),
+          Some(_) => None,
+        };
+        if let Some(message) = message {
+          findings.push(
+            Finding::error(FindingCode::UndefinedPermissionLambda, message)
+              .in_file(&lambda_permissions.path),
+          );
+          continue;
+        }
         match permission_group.1 {
           hcl::Expression::Array(arr) => {
             for arr_item in arr {
@@ -284,17 +1089,12 @@ fn validate_lambda_permissions(
                 hcl::Expression::Object(route_obj) => {
                   let s = lambda_metadata
                     .iter_mut()
-                    .find(|x| x.key == permission_group.0.to_string())
-                    .unwrap_or_else(|| {
-                      panic!(
-                        "Failed to match permission to key in lambda: {}",
-                        permission_group.0
-                      )
-                    });
+                    .find(|x| x.key == key)
+                    .expect("Failed to match permission to key in lambda");
 
                   let principal = route_obj
                     .iter()
-                    .find(|r| r.0.to_string() == *"principal")
+                    .find(|r| object_key_name(r.0).as_deref() == Some("principal"))
                     .unwrap();
 
                   let service = principal.1.to_string().replace('\"', "");
@@ -308,21 +1108,48 @@ fn validate_lambda_permissions(
                     "scheduler.amazonaws.com" => {
                       s.lambda_type = LambdaTriggerType::EventBridge;
                     }
-                    _ => todo!("Need to cater for {} service", service),
+                    "states.amazonaws.com" => {
+                      s.lambda_type = LambdaTriggerType::StepFunction;
+                      s.step_function_permission = true;
+                    }
+                    _ => {
+                      let message = format!(
+                        "The permission for the lambda {} uses the principal {} which isn't supported yet",
+                        s.key, service
+                      );
+                      if allow_unknown_principal {
+                        findings.push(
+                          Finding::warning(FindingCode::UnknownPrincipal, message)
+                            .in_file(&lambda_permissions.path),
+                        );
+                      } else {
+                        findings.push(
+                          Finding::error(FindingCode::UnknownPrincipal, message)
+                            .in_file(&lambda_permissions.path),
+                        );
+                      }
+                      continue;
+                    }
                   }
 
                   if s.lambda_type == LambdaTriggerType::ApiGateway {
                     let source_arn = route_obj
                       .iter()
-                      .find(|r| r.0.to_string() == *"source_arn")
+                      .find(|r| object_key_name(r.0).as_deref() == Some("source_arn"))
                       .unwrap();
 
-                    let data = handle_api_gateway_lambda(source_arn.1.to_string())?;
-                    debug!("API Gateway Lambda Data: {:?}", data);
-                    s.apis.push(APIPath {
-                      method: data[0].trim().into(),
-                      route: data[1].trim().into(),
-                    });
+                    let api = parse_source_arn(&source_arn.1.to_string())?;
+                    debug!("API Gateway Lambda Data: {:?}", api);
+                    if !api.method.is_supported() {
+                      findings.push(Finding::error(
+                        FindingCode::UnsupportedMethod,
+                        format!(
+                          "The {} method for {} used by the lambda {} is not supported by API Gateway",
+                          api.method, api.route, s.key
+                        ),
+                      ).for_operation(&api.method, &api.route).in_file(&lambda_permissions.path));
+                    }
+                    s.apis.push(api);
                   }
                 }
                 _ => todo!("Terraform expression not supported currently, expecting object"),
@@ -332,102 +1159,350 @@ fn validate_lambda_permissions(
           _ => todo!("Terraform expression not supported currently, expecting array"),
         }
       }
-      for key in lambda_permission_keys {
-        if !lambda_metadata.iter().any(|x| x.key == key) {
-          valid = false;
-          error!("'lambda_permissions' has extra key '{}'", key);
-        }
-        let len = lambda_contents.matches(&key).count();
-        if lambda_contents.matches(&key).count() > 1
-          && lambda_metadata
-            .iter_mut()
-            .find(|x| x.key == key)
-            .expect("Failed to match lambda key")
-            .apis
-            .len()
-            != len
-        {
-          valid = false;
-          error!("Key is duplicated: {}", key);
-        }
+      for key in duplicate_local_keys(&lambda_permissions.contents, "lambdas_permissions") {
+        findings.push(
+          Finding::error(
+            FindingCode::DuplicatePermissionKey,
+            format!("Key is duplicated: {}", key),
+          )
+          .in_file(&lambda_permissions.path),
+        );
       }
     }
     _ => todo!("Terraform expression not supported currently for lambdas_permissions variable"),
   }
-  if !valid {
+  if findings.failures() > failures {
     return Err(anyhow!("Invalid lambda_permissions.tf file"));
   }
+  for lambda in lambda_metadata.iter_mut() {
+    dedup_apis(&mut lambda.apis);
+  }
   Ok(())
 }
 
+/// Removes the repeated routes, e.g. from permissions for the same route in different stages,
+/// keeping the order of the first of each
+pub(crate) fn dedup_apis(apis: &mut Vec<APIPath>) {
+  let mut seen = Vec::new();
+  apis.retain(|api| {
+    if seen.contains(api) {
+      false
+    } else {
+      seen.push(api.clone());
+      true
+    }
+  });
+}
+
 /// Validate and extract data from api_gateway.tf
-fn extract_api_gw(api_gw: PathBuf, mut lambda: Vec<Lambda>) -> anyhow::Result<Vec<Lambda>> {
+///
+/// ARN placeholders that reference a lambda key that isn't in lambda.tf are reported, e.g. a route
+/// still wired to a deleted lambda
+fn extract_api_gw(
+  api_gw: &TerraformSource,
+  mut lambda: Vec<Lambda>,
+  lambda_module_name: &str,
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<Lambda>> {
   info!("Validating api_gateway.tf config");
-  let contents = std::fs::read_to_string(api_gw)?;
-  {
-    let _ = hcl::parse(&contents)?;
-  }
-  let lines = contents.lines();
-  let mut valid = true;
-  for line in lines {
-    for name in &mut lambda {
-      if line.contains(&name.key) && !line.trim().starts_with('#') && !line.trim().starts_with("//")
-      {
-        let parts: Vec<&str> = line.split(':').collect();
-        if name.arn_template_key.is_some() {
-          valid = false;
-          error!("The lambda key '{}' is used more than once", name.key);
-        }
-        name.arn_template_key = Some(parts[0].trim().to_string());
-        break;
-      }
+  let body = api_gw.parse()?;
+  let failures = findings.failures();
+  for reference in lambda_references(&body, lambda_module_name) {
+    let Some(placeholder) = reference.placeholder else {
+      continue;
+    };
+    let Some(name) = lambda.iter_mut().find(|name| name.key == reference.key) else {
+      let module_index = format!("{}[\"{}\"]", lambda_module_name, reference.key);
+      let line = api_gw
+        .contents
+        .lines()
+        .position(|line| line.contains(&module_index))
+        .unwrap_or_default();
+      findings.push(
+        Finding::error(
+          FindingCode::UnknownLambdaKey,
+          format!(
+            "The ARN placeholder {} on line {} references the lambda {} which is not in lambda.tf",
+            placeholder,
+            line + 1,
+            reference.key
+          ),
+        )
+        .in_file(&api_gw.path)
+        .at_line(line + 1),
+      );
+      continue;
+    };
+    if name.arn_template_key.is_some() {
+      findings.push(
+        Finding::error(
+          FindingCode::DuplicateArnKey,
+          format!("The lambda key '{}' is used more than once", name.key),
+        )
+        .in_file(&api_gw.path),
+      );
     }
+    name.arn_template_key = Some(placeholder);
   }
-  if !valid {
+  if findings.failures() > failures {
     return Err(anyhow!("Invalid api_gateway.tf"));
   }
   Ok(lambda)
 }
 
+/// A reference to a lambda of the module, e.g. `module.lambda["lambda-1"].lambda_arn`
+#[derive(Debug, PartialEq, Eq)]
+struct LambdaReference {
+  /// The key of the `templatefile` variables it's assigned to, e.g. `lambda_1_arn`, which is the
+  /// ARN placeholder in the OpenAPI documents, `None` for references outside the variables
+  placeholder: Option<String>,
+  /// The lambda key
+  key: String,
+}
+
+/// Finds the references to the lambdas of the module anywhere in the body, including the
+/// arguments of function calls such as `templatefile` and the interpolations of strings
+fn lambda_references(body: &hcl::Body, lambda_module_name: &str) -> Vec<LambdaReference> {
+  let mut references = Vec::new();
+  collect_body_references(body, lambda_module_name, &mut references);
+  references
+}
+
+/// Collects the references in the attributes and nested blocks of the body
+fn collect_body_references(
+  body: &hcl::Body,
+  lambda_module_name: &str,
+  references: &mut Vec<LambdaReference>,
+) {
+  for structure in body.iter() {
+    match structure {
+      hcl::Structure::Attribute(attribute) => {
+        collect_references(&attribute.expr, None, lambda_module_name, references)
+      }
+      hcl::Structure::Block(block) => {
+        collect_body_references(&block.body, lambda_module_name, references)
+      }
+    }
+  }
+}
+
+/// Collects the references in the expression, `placeholder` is the key of the `templatefile`
+/// variables the expression is assigned to, as only those keys are ARN placeholders
+fn collect_references(
+  expr: &hcl::Expression,
+  placeholder: Option<&str>,
+  lambda_module_name: &str,
+  references: &mut Vec<LambdaReference>,
+) {
+  match expr {
+    hcl::Expression::Traversal(traversal) => match module_index(traversal, lambda_module_name) {
+      Some(key) => references.push(LambdaReference {
+        placeholder: placeholder.map(str::to_string),
+        key,
+      }),
+      None => collect_references(&traversal.expr, None, lambda_module_name, references),
+    },
+    hcl::Expression::Object(object) => object
+      .values()
+      .for_each(|value| collect_references(value, None, lambda_module_name, references)),
+    hcl::Expression::Array(values) => values
+      .iter()
+      .for_each(|value| collect_references(value, None, lambda_module_name, references)),
+    hcl::Expression::FuncCall(call) => {
+      for (index, arg) in call.args.iter().enumerate() {
+        match arg {
+          hcl::Expression::Object(variables)
+            if index == 1 && call.name.to_string() == "templatefile" =>
+          {
+            for (key, value) in variables {
+              let key = object_key_name(key);
+              collect_references(value, key.as_deref(), lambda_module_name, references);
+            }
+          }
+          _ => collect_references(arg, None, lambda_module_name, references),
+        }
+      }
+    }
+    hcl::Expression::Parenthesis(expr) => {
+      collect_references(expr, placeholder, lambda_module_name, references)
+    }
+    hcl::Expression::Conditional(conditional) => {
+      collect_references(
+        &conditional.true_expr,
+        placeholder,
+        lambda_module_name,
+        references,
+      );
+      collect_references(
+        &conditional.false_expr,
+        placeholder,
+        lambda_module_name,
+        references,
+      );
+    }
+    hcl::Expression::TemplateExpr(template) => {
+      let template = hcl::Template::from_expr(template);
+      for element in template.iter().flat_map(|template| template.elements()) {
+        if let hcl::template::Element::Interpolation(interpolation) = element {
+          collect_references(
+            &interpolation.expr,
+            placeholder,
+            lambda_module_name,
+            references,
+          );
+        }
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Gets the lambda key of a traversal such as `module.lambda["lambda-1"].lambda_arn`
+fn module_index(traversal: &hcl::Traversal, lambda_module_name: &str) -> Option<String> {
+  match (&traversal.expr, traversal.operators.as_slice()) {
+    (
+      hcl::Expression::Variable(variable),
+      [hcl::TraversalOperator::GetAttr(module), hcl::TraversalOperator::Index(hcl::Expression::String(key)), ..],
+    ) if variable.as_str() == "module" && module.as_str() == lambda_module_name => {
+      Some(key.to_string())
+    }
+    _ => None,
+  }
+}
+
+/// Gets the name of an object key, either an identifier or a quoted string
+fn object_key_name(key: &hcl::ObjectKey) -> Option<String> {
+  match key {
+    hcl::ObjectKey::Identifier(identifier) => Some(identifier.to_string()),
+    hcl::ObjectKey::Expression(hcl::Expression::String(name)) => Some(name.to_string()),
+    hcl::ObjectKey::Expression(hcl::Expression::Variable(name)) => Some(name.to_string()),
+    _ => None,
+  }
+}
+
 /// Validate and extract data from step_function.tf
 fn extract_step_function(
   mut lambda_data: Vec<Lambda>,
-  step_fn: PathBuf,
+  step_fn: &TerraformSource,
+  lambda_module_name: &str,
 ) -> anyhow::Result<Vec<Lambda>> {
   info!("Validating step_function.tf config");
-  let contents = std::fs::read_to_string(step_fn)?;
-  {
-    let _ = hcl::parse(&contents)?;
-  }
-  let lines = contents.lines();
-  for line in lines {
-    for lambda in &mut lambda_data {
-      if line.contains(&format!("module.lambda[\"{}", lambda.key)) {
-        lambda.step_function = true;
-      }
+  let references = lambda_references(&step_fn.parse()?, lambda_module_name);
+  for lambda in &mut lambda_data {
+    if references
+      .iter()
+      .any(|reference| reference.key == lambda.key)
+    {
+      lambda.step_function = true;
     }
   }
   Ok(lambda_data)
 }
 
-/// Extract the API endpoint and HTTP method
-fn extract_api_and_method(line: &str, method: HttpMethod) -> Option<(String, String)> {
-  if line.contains(method.to_string().to_uppercase().as_str()) {
-    Some((
-      method.to_string(),
-      line.replace(
-        format!("/{}", method.to_string().to_uppercase()).as_str(),
-        "",
-      ),
-    ))
+/// Finds the lambdas used by a step function whose permissions don't match, the ones that also
+/// have API gateway permissions and the ones without a permission for Step Functions
+fn find_step_function_permission_issues(lambdas: &[Lambda]) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  for lambda in lambdas.iter().filter(|lambda| lambda.step_function) {
+    if !lambda.apis.is_empty() {
+      let routes: Vec<String> = lambda
+        .apis
+        .iter()
+        .map(|api| format!("{} {}", api.method, api.route))
+        .collect();
+      issues.push(Finding::error(
+        FindingCode::StepFunctionApiPermission,
+        format!(
+          "The lambda {} is used in a step function but has API gateway permissions for {}",
+          lambda.key,
+          routes.join(", ")
+        ),
+      ));
+    }
+    if !lambda.step_function_permission {
+      issues.push(Finding::warning(
+        FindingCode::MissingStepFunctionPermission,
+        format!(
+          "The lambda {} is used in a step function but has no permission for states.amazonaws.com in lambda_permissions.tf",
+          lambda.key
+        ),
+      ));
+    }
+  }
+  issues
+}
+
+/// Extract the API endpoint and HTTP method, the first segment of the route that is a method in
+/// any casing, e.g. `get` in `/api/get/health`, is the method and the rest is the endpoint
+fn extract_api_and_method(line: &str) -> Option<APIPath> {
+  let segments: Vec<&str> = line.split('/').collect();
+  let (index, method) = segments
+    .iter()
+    .enumerate()
+    .skip(1)
+    .find_map(
+      |(index, segment)| match segment.trim().parse::<HttpMethod>().ok()? {
+        HttpMethod::Any => None,
+        method => Some((index, method)),
+      },
+    )?;
+  let route = segments
+    .iter()
+    .enumerate()
+    .filter(|(i, _)| *i != index)
+    .map(|(_, segment)| *segment)
+    .collect::<Vec<_>>()
+    .join("/");
+  Some(APIPath {
+    method,
+    route: route.trim().to_string(),
+    gateway: None,
+  })
+}
+
+/// Replaces an interpolated stage after the execution ARN, e.g. `/${var.stage}/POST/health`, with
+/// `*` so it is handled the same as a wildcard stage
+fn replace_interpolated_stage(section: &str) -> String {
+  if let Some((arn, rest)) = section.split_once('}') {
+    if let Some((_, rest)) = rest
+      .strip_prefix("/${")
+      .and_then(|stage| stage.split_once('}'))
+    {
+      return format!("{}}}/*{}", arn, rest);
+    }
+  }
+  section.to_string()
+}
+
+/// Parses the `source_arn` of an API Gateway lambda permission into the HTTP method, the route
+/// and the type of API
+///
+/// e.g. `"${module.service_api.rest_api_execution_arn}/*/POST/postcode-validation"` is a `POST` to
+/// `/postcode-validation` of a REST API
+pub fn parse_source_arn(arn: &str) -> anyhow::Result<APIPath> {
+  let mut api = parse_source_arn_route(arn)?;
+  api.gateway = source_arn_gateway(arn);
+  Ok(api)
+}
+
+/// The type of API the `source_arn` is for, from the name of its execution ARN, e.g.
+/// `aws_apigatewayv2_api.orders.execution_arn` or `module.api.http_api_execution_arn` for an HTTP
+/// API and `aws_api_gateway_rest_api.items.execution_arn` or `module.api.rest_api_execution_arn`
+/// for a REST API, `None` when the name doesn't tell
+fn source_arn_gateway(arn: &str) -> Option<GatewayType> {
+  let arn = arn.to_lowercase();
+  if arn.contains("apigatewayv2") || arn.contains("http_api") {
+    Some(GatewayType::Http)
+  } else if arn.contains("rest_api") || arn.contains("aws_api_gateway_") {
+    Some(GatewayType::Rest)
   } else {
     None
   }
 }
 
-/// Extract API endpoint and HTTP method from the ARN
-fn handle_api_gateway_lambda(source_arn: String) -> anyhow::Result<Vec<String>> {
-  let section = source_arn.replace('\"', "");
+/// Parses the HTTP method and route of a `source_arn`
+fn parse_source_arn_route(arn: &str) -> anyhow::Result<APIPath> {
+  let section = replace_interpolated_stage(&arn.replace('\"', ""));
   debug!("Lambda route: {}", section);
   let parts: Vec<String> = section.split('}').map(|x| x.to_string()).collect();
   if section.contains("/*/*/*") {
@@ -440,32 +1515,35 @@ fn handle_api_gateway_lambda(source_arn: String) -> anyhow::Result<Vec<String>>
     debug!("Parts: {:?}", parts);
     let section = parts[1].replacen('/', " ", 2);
     debug!("Section: {}", section);
-    let mut data: Vec<String> = section.trim().split(' ').map(|x| x.to_string()).collect();
+    let data: Vec<String> = section.trim().split(' ').map(|x| x.to_string()).collect();
     debug!("Data: {:?}", data);
-    data[1] = format!("/{}", data[1].trim());
-    Ok(data)
+    if data.len() < 2 {
+      return Err(anyhow!(
+        "Unsupported route: {}. Expected /*/METHOD/the/endpoint",
+        section
+      ));
+    }
+    Ok(APIPath {
+      method: data[0].trim().parse()?,
+      route: format!("/{}", data[1].trim()),
+      gateway: None,
+    })
   } else if section.contains('*') && section.matches('*').count() == 2 && section.contains("/*/*") {
     let parts: Vec<String> = section.split("/*/*").map(|x| x.to_string()).collect();
     debug!("Parts: {:?}", parts);
     let section = parts[1].replacen('/', "", 1);
     debug!("Section: {}", section);
-    let mut data: Vec<String> = section.trim().split(' ').map(|x| x.to_string()).collect();
-    data.insert(0, HttpMethod::Any.to_string());
-    debug!("Data: {:?}", data);
-    data[1] = format!("/{}", data[1].trim());
-    Ok(data)
-  } else if let Some(data) = extract_api_and_method(parts[1].trim(), HttpMethod::Get) {
-    Ok([data.0, data.1].to_vec())
-  } else if let Some(data) = extract_api_and_method(parts[1].trim(), HttpMethod::Post) {
-    Ok([data.0, data.1].into())
-  } else if let Some(data) = extract_api_and_method(parts[1].trim(), HttpMethod::Put) {
-    Ok([data.0, data.1].into())
-  } else if let Some(data) = extract_api_and_method(parts[1].trim(), HttpMethod::Delete) {
-    Ok([data.0, data.1].into())
-  } else if let Some(data) = extract_api_and_method(parts[1].trim(), HttpMethod::Patch) {
-    Ok([data.0, data.1].into())
+    Ok(APIPath {
+      method: HttpMethod::Any,
+      route: format!("/{}", section.trim()),
+      gateway: None,
+    })
   } else {
-    todo!("Need to cater for {}", parts[1].trim());
+    let route = parts
+      .get(1)
+      .ok_or_else(|| anyhow!("Unsupported route: {}. Expected an execution ARN", section))?
+      .trim();
+    extract_api_and_method(route).ok_or_else(|| anyhow!("Need to cater for {}", route))
   }
 }
 
@@ -524,101 +1602,745 @@ mod tests {
   //   assert_eq!(lambda[1].handler, "lambda2.handler");
   // }
 
-  // Tests for handle_api_gateway_lambda
   #[test]
-  fn test_handle_api_gateway_lambda() {
+  fn test_check_lambda_policy() {
+    let lambdas = vec![
+      Lambda {
+        key: "slow".to_string(),
+        timeout: Some("900".to_string()),
+        memory_size: Some("512".to_string()),
+        ..Default::default()
+      },
+      Lambda {
+        key: "unset".to_string(),
+        timeout: Some("var.timeout".to_string()),
+        ..Default::default()
+      },
+    ];
+    let policy = LambdaPolicy {
+      max_timeout: Some(300),
+      require_memory_size: true,
+      ..Default::default()
+    };
+    let mut findings = Findings::default();
+    check_lambda_policy(&lambdas, &policy, &mut findings);
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The lambda slow has a timeout of 900 which is above the maximum of 300",
+        "The lambda unset doesn't set memory_size",
+      ]
+    );
+    assert_eq!(findings.error_count(), 0);
+  }
+
+  #[test]
+  fn test_find_lambda_attribute_template() {
+    let body = hcl::parse(
+      r#"
+lambdas = {
+  orders = {
+    handler = "${var.prefix}-handler"
+    runtime = "${lookup(var.runtimes, "orders")}"
+    timeout = 30
+    memory_size = var.memory_size
+  }
+}
+"#,
+    )
+    .unwrap();
+    let lambdas = body.attributes().next().unwrap();
+    let orders = match &lambdas.expr {
+      hcl::Expression::Object(lambdas) => match lambdas.values().next() {
+        Some(hcl::Expression::Object(orders)) => orders,
+        _ => panic!("Expected the lambda to be an object"),
+      },
+      _ => panic!("Expected the lambdas to be an object"),
+    };
+    assert_eq!(
+      find_lambda_attribute(orders, "handler"),
+      Some("${var.prefix}-handler".to_string())
+    );
+    assert_eq!(
+      find_lambda_attribute(orders, "runtime"),
+      Some("${lookup(var.runtimes, \"orders\")}".to_string())
+    );
+    assert_eq!(
+      find_lambda_attribute(orders, "timeout"),
+      Some("30".to_string())
+    );
+    assert_eq!(
+      find_lambda_attribute(orders, "memory_size"),
+      Some("var.memory_size".to_string())
+    );
+  }
+
+  #[test]
+  fn test_validate_lambda_workspace() {
+    let lambda = TerraformSource::read(
+      Path::new("test_files/workspace/lambda.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap();
+    let keys = |workspace| {
+      validate_lambda(&lambda, Some(workspace), &mut Findings::default())
+        .unwrap()
+        .into_iter()
+        .map(|lambda| lambda.key)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(keys("prod"), vec!["list-items", "export-items"]);
+    assert_eq!(keys("dev"), vec!["list-items"]);
+  }
+
+  #[test]
+  fn test_validate_lambda_missing_handler() {
+    let lambda = TerraformSource {
+      path: PathBuf::from("lambda.tf"),
+      contents: r#"
+locals {
+  lambdas = {
+    list-items = {
+      handler = "list_items.lambda_handler"
+    }
+    get-item = {
+      timeout = 3
+    }
+  }
+}
+"#
+      .to_string(),
+    };
+    let mut findings = Findings::default();
+    assert!(validate_lambda(&lambda, None, &mut findings).is_err());
+    let finding = findings.iter().next().unwrap();
+    assert_eq!(finding.code, FindingCode::MissingHandler);
+    assert_eq!(finding.message, "The lambda get-item has no handler");
+    assert_eq!(finding.file, Some(PathBuf::from("lambda.tf")));
+  }
+
+  #[test]
+  fn test_check_handler_uniqueness() {
+    let lambda = |key: &str, handler: &str| Lambda {
+      key: key.to_string(),
+      handler: handler.to_string(),
+      ..Default::default()
+    };
+    let lambdas = vec![
+      lambda("list-items", "items.lambda_handler"),
+      lambda("get-item", "get_item.lambda_handler"),
+      lambda("create-item", "items.lambda_handler"),
+      lambda("no-handler", ""),
+      lambda("other-no-handler", ""),
+      lambda("delete-item", "items.lambda_handler"),
+    ];
+    let findings = check_handler_uniqueness(&lambdas);
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec!["The lambda keys 'list-items', 'create-item', 'delete-item' are using the same handler items.lambda_handler"]
+    );
+    assert_eq!(findings[0].code, FindingCode::DuplicateHandler);
+  }
+
+  #[test]
+  fn test_check_arn_suffix() {
+    let lambdas = vec![
+      Lambda {
+        key: "lambda-1".to_string(),
+        arn_template_key: Some("lambda_1_arn".to_string()),
+        ..Default::default()
+      },
+      Lambda {
+        key: "lambda-2".to_string(),
+        arn_template_key: Some("lambda_1_arn".to_string()),
+        ..Default::default()
+      },
+      Lambda {
+        key: "unwired".to_string(),
+        ..Default::default()
+      },
+    ];
+    let mut findings = Findings::default();
+    check_arn_suffix(&lambdas, "_arn", &mut findings);
+    let messages: Vec<_> = findings.iter().map(|f| f.message.as_str()).collect();
+    assert_eq!(
+      messages,
+      vec!["The lambda lambda-2 uses the ARN placeholder lambda_1_arn, expected lambda_2_arn"]
+    );
+  }
+
+  #[test]
+  fn test_extract_step_function_module_name() {
+    let lambdas = || {
+      vec![Lambda {
+        key: "x".to_string(),
+        ..Default::default()
+      }]
+    };
+    let step_fn = TerraformSource::read(
+      Path::new("test_files/step_function/step_function.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap();
+    let lambda_data = extract_step_function(lambdas(), &step_fn, "functions").unwrap();
+    assert!(lambda_data[0].step_function);
+    let lambda_data =
+      extract_step_function(lambdas(), &step_fn, DEFAULT_LAMBDA_MODULE_NAME).unwrap();
+    assert!(!lambda_data[0].step_function);
+  }
+
+  #[test]
+  fn test_extract_api_gw_unknown_lambda_key() {
+    let lambda = vec![Lambda {
+      key: "lambda-1".to_string(),
+      ..Default::default()
+    }];
+    let mut findings = Findings::default();
+    let api_gw = TerraformSource::read(
+      Path::new("test_files/api_gateway/api_gateway.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap();
+    let result = extract_api_gw(&api_gw, lambda, DEFAULT_LAMBDA_MODULE_NAME, &mut findings);
+    assert!(result.is_err());
+    let finding = findings.iter().next().expect("Expected a finding");
+    assert_eq!(finding.code, FindingCode::UnknownLambdaKey);
+    assert_eq!(
+      finding.message,
+      "The ARN placeholder ghost_arn on line 7 references the lambda ghost which is not in lambda.tf"
+    );
+  }
+
+  #[test]
+  fn test_check_module_for_each() {
+    let file = PathBuf::from("test_files/module_for_each/lambda.tf");
+    let files = vec![(
+      parse_file(&file, &TerraformVariables::new()).unwrap().1,
+      file,
+    )];
+    let lambda = |key: &str| Lambda {
+      key: key.to_string(),
+      ..Default::default()
+    };
+    let lambdas = vec![lambda("lambda-1"), lambda("lambda-2")];
+    // The conditional on the workspace can't be resolved without one
+    assert!(check_module_for_each(&files, "lambda", None, &lambdas).is_empty());
+    let messages: Vec<String> = check_module_for_each(&files, "lambda", Some("dev"), &lambdas)
+      .into_iter()
+      .map(|finding| finding.message)
+      .collect();
+    assert_eq!(
+      messages,
+      vec![
+        "The for_each of the module lambda has the key legacy which is not in the lambdas local"
+      ]
+    );
+    let messages: Vec<String> = check_module_for_each(&files, "lambda", Some("prod"), &lambdas)
+      .into_iter()
+      .map(|finding| finding.message)
+      .collect();
+    assert_eq!(messages.len(), 2);
+    assert!(check_module_for_each(&files, "functions", Some("dev"), &lambdas).is_empty());
+  }
+
+  #[test]
+  fn test_lambda_references() {
+    let body = hcl::parse(
+      r#"
+module "api" {
+  body = templatefile("api.yaml", {
+    region : var.region
+    lambda_1_arn : module.lambda["lambda-1"].lambda_arn,
+    "lambda_2_arn" = module.lambda["lambda-2"].lambda_arn
+    other_arn = module.other["lambda-3"].lambda_arn,
+  })
+  # lambda_4_arn : module.lambda["lambda-4"].lambda_arn
+  uri = "${module.lambda["lambda-5"].invoke_arn}"
+  tags = { lambda_6_arn = module.lambda["lambda-6"].lambda_arn }
+}
+"#,
+    )
+    .unwrap();
+    let reference = |placeholder: &str, key: &str| LambdaReference {
+      placeholder: Some(placeholder.to_string()),
+      key: key.to_string(),
+    };
+    assert_eq!(
+      lambda_references(&body, "lambda"),
+      vec![
+        reference("lambda_1_arn", "lambda-1"),
+        reference("lambda_2_arn", "lambda-2"),
+        LambdaReference {
+          placeholder: None,
+          key: "lambda-5".to_string(),
+        },
+        LambdaReference {
+          placeholder: None,
+          key: "lambda-6".to_string(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_duplicate_local_keys() {
+    let contents = r#"
+locals {
+  lambdas = {
+    "lambda-1" = {
+      handler = "lambda-2.handler",
+    },
+    lambda-2 : { handler = "${var.prefix}.handler" }
+    # lambda-3 = {}
+    lambda-1 = {
+      handler = "lambda_1.handler"
+    }
+  }
+  lambdas_permissions = merge({
+    lambda-1 = []
+  }, terraform.workspace == "prod" ? { lambda-1 = [] } : {})
+  handlers = { a = "a.handler", b = "b.handler", a = "c.handler" }
+  layers = {
+    b = [] # a = []
+    a = [],
+    a = []
+  }
+}
+"#;
+    assert_eq!(duplicate_local_keys(contents, "lambdas"), vec!["lambda-1"]);
+    assert!(duplicate_local_keys(contents, "lambdas_permissions").is_empty());
+    assert_eq!(duplicate_local_keys(contents, "handlers"), vec!["a"]);
+    assert_eq!(duplicate_local_keys(contents, "layers"), vec!["a"]);
+    assert!(duplicate_local_keys(contents, "missing").is_empty());
+  }
+
+  #[test]
+  fn test_validate_lambda_permissions_dedups_apis() {
+    let mut lambda = vec![Lambda {
+      key: "lambda-1".to_string(),
+      handler: "lambda_1.lambda_handler".to_string(),
+      ..Default::default()
+    }];
+    let lambda_permissions = TerraformSource::read(
+      Path::new("test_files/permissions/lambda_permissions.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap();
+    validate_lambda_permissions(
+      &lambda_permissions,
+      &mut lambda,
+      None,
+      false,
+      &mut Findings::default(),
+    )
+    .unwrap();
+    assert_eq!(
+      lambda[0].apis,
+      vec![
+        APIPath {
+          method: HttpMethod::Get,
+          route: "/v1/items".to_string(),
+          gateway: Some(GatewayType::Rest),
+        },
+        APIPath {
+          method: HttpMethod::Post,
+          route: "/v1/items".to_string(),
+          gateway: Some(GatewayType::Rest),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_validate_lambda_permissions_undefined_lambda() {
+    let lambda_permissions = TerraformSource::read(
+      Path::new("test_files/permissions/lambda_permissions.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap();
+    let mut lambda = vec![Lambda {
+      key: "lambda-2".to_string(),
+      handler: "lambda_2.lambda_handler".to_string(),
+      ..Default::default()
+    }];
+    let mut findings = Findings::default();
+    assert!(validate_lambda_permissions(
+      &lambda_permissions,
+      &mut lambda,
+      None,
+      false,
+      &mut findings
+    )
+    .is_err());
+    let finding = findings.iter().next().unwrap();
+    assert_eq!(finding.code, FindingCode::UndefinedPermissionLambda);
+    assert_eq!(
+      finding.message,
+      "lambda_permissions references undefined lambda 'lambda-1'"
+    );
+
+    let mut lambda = vec![Lambda {
+      key: "lambda-1".to_string(),
+      ..Default::default()
+    }];
+    let mut findings = Findings::default();
+    assert!(validate_lambda_permissions(
+      &lambda_permissions,
+      &mut lambda,
+      None,
+      false,
+      &mut findings
+    )
+    .is_err());
+    assert_eq!(
+      findings.iter().next().unwrap().message,
+      "lambda_permissions references lambda 'lambda-1' which has no handler"
+    );
+  }
+
+  #[test]
+  fn test_validate_lambda_permissions_unknown_principal() {
+    let lambda_permissions = TerraformSource::read(
+      Path::new("test_files/permissions/unknown_principal.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap();
+    let lambdas = || {
+      vec![
+        Lambda {
+          key: "lambda-1".to_string(),
+          handler: "lambda_1.lambda_handler".to_string(),
+          ..Default::default()
+        },
+        Lambda {
+          key: "notify".to_string(),
+          handler: "notify.lambda_handler".to_string(),
+          ..Default::default()
+        },
+      ]
+    };
+    let mut lambda = lambdas();
+    let mut findings = Findings::default();
+    assert!(validate_lambda_permissions(
+      &lambda_permissions,
+      &mut lambda,
+      None,
+      false,
+      &mut findings
+    )
+    .is_err());
+    assert_eq!(findings.count(FindingCode::UnknownPrincipal), 1);
+    assert_eq!(findings.error_count(), 1);
+
+    let mut lambda = lambdas();
+    let mut findings = Findings::default();
+    validate_lambda_permissions(&lambda_permissions, &mut lambda, None, true, &mut findings)
+      .unwrap();
+    assert_eq!(
+      findings.iter().next().unwrap().message,
+      "The permission for the lambda notify uses the principal sns.amazonaws.com which isn't supported yet"
+    );
+    assert_eq!(findings.warning_count(), 1);
+    assert_eq!(lambda[0].apis.len(), 1);
+    assert!(lambda[1].apis.is_empty());
+  }
+
+  #[test]
+  fn test_validate_terraform_combined_file() {
+    let mut findings = Findings::default();
+    let lambda_data = validate_terraform(
+      PathBuf::from("test_files/combined"),
+      None,
+      DEFAULT_LAMBDA_MODULE_NAME,
+      &TerraformVariables::new(),
+      None,
+      false,
+      &mut findings,
+    )
+    .unwrap();
+    assert_eq!(lambda_data.len(), 3);
+    let list_items = &lambda_data[0];
+    assert_eq!(list_items.key, "list-items");
+    assert_eq!(
+      list_items.arn_template_key,
+      Some("list_items_arn".to_string())
+    );
+    assert_eq!(
+      list_items.apis,
+      vec![APIPath {
+        method: HttpMethod::Get,
+        route: "/v1/items".to_string(),
+        gateway: Some(GatewayType::Rest),
+      }]
+    );
+    assert!(lambda_data[2].step_function);
+    assert!(lambda_data[2].step_function_permission);
+    assert_eq!(findings.iter().count(), 0);
+  }
+
+  #[test]
+  fn test_find_step_function_permission_issues() {
+    let lambdas = vec![
+      Lambda {
+        key: "process".to_string(),
+        step_function: true,
+        step_function_permission: true,
+        apis: vec![APIPath {
+          method: HttpMethod::Post,
+          route: "/process".to_string(),
+          gateway: None,
+        }],
+        ..Default::default()
+      },
+      Lambda {
+        key: "notify".to_string(),
+        step_function: true,
+        ..Default::default()
+      },
+      Lambda {
+        key: "users".to_string(),
+        apis: vec![APIPath {
+          method: HttpMethod::Get,
+          route: "/users".to_string(),
+          gateway: None,
+        }],
+        ..Default::default()
+      },
+    ];
+    let issues = find_step_function_permission_issues(&lambdas);
+    assert_eq!(
+      issues,
+      vec![
+        Finding::error(
+          FindingCode::StepFunctionApiPermission,
+          "The lambda process is used in a step function but has API gateway permissions for POST /process",
+        ),
+        Finding::warning(
+          FindingCode::MissingStepFunctionPermission,
+          "The lambda notify is used in a step function but has no permission for states.amazonaws.com in lambda_permissions.tf",
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_local_value() {
+    let contents = r#"
+locals {
+  lambdas_permissions = { a = [] }
+  lambdas = {
+    a = { handler = "${var.prefix}.handler" }
+  }
+}
+
+module "lambda" {
+  for_each = local.lambdas
+}
+"#;
+    assert_eq!(
+      local_value(contents, "lambdas"),
+      Some("{\n    a = { handler = \"${var.prefix}.handler\" }\n  }")
+    );
+    assert_eq!(
+      local_value(contents, "lambdas_permissions"),
+      Some("{ a = [] }")
+    );
+    assert_eq!(local_value(contents, "missing"), None);
+  }
+
+  #[test]
+  fn test_substitute_variables() {
+    let variables = load_var_file(Path::new("test_files/tfvars/terraform.tfvars")).unwrap();
+    assert_eq!(variables.get("api_prefix"), Some(&"v1".to_string()));
+    assert_eq!(variables.get("timeout"), Some(&"30".to_string()));
+    let source_arn = substitute_variables(
+      "\"${module.service_api.rest_api_execution_arn}/*/POST/${var.api_prefix}/health/${var.unknown}\"",
+      &variables,
+    );
+    assert_eq!(unresolved_variables(&source_arn), vec!["unknown"]);
+    let api = parse_source_arn(&source_arn.replace("/${var.unknown}", "")).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(api.route, "/v1/health");
+  }
+
+  #[test]
+  fn test_parse_file_reports_file() {
+    let error = parse_file(
+      Path::new("test_files/invalid_syntax/broken.tf"),
+      &TerraformVariables::new(),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(error.starts_with("Failed to parse \"test_files/invalid_syntax/broken.tf\""));
+  }
+
+  #[test]
+  fn test_find_files_respects_svignore() {
+    let files = find_files(Path::new("test_files/svignore"), OsStr::new("tf"));
+    let names: Vec<_> = files
+      .iter()
+      .map(|file| file.file_name().unwrap().to_str().unwrap())
+      .collect();
+    assert_eq!(names, vec!["lambda.tf", "kept.tf"]);
+  }
+
+  // Tests for parse_source_arn
+  #[test]
+  fn test_parse_source_arn() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/GET/health\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "GET");
-    assert_eq!(data[1], "/api/health");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Get);
+    assert_eq!(api.route, "/api/health");
+  }
+
+  #[test]
+  fn test_parse_source_arn_gateway() {
+    let rest = "\"${module.service_api.rest_api_execution_arn}/*/GET/health\"";
+    assert_eq!(
+      parse_source_arn(rest).unwrap().gateway,
+      Some(GatewayType::Rest)
+    );
+    let http = "\"${aws_apigatewayv2_api.orders.execution_arn}/*/GET/v1/orders\"";
+    let api = parse_source_arn(http).unwrap();
+    assert_eq!(api.route, "/v1/orders");
+    assert_eq!(api.gateway, Some(GatewayType::Http));
+    let literal = "\"arn:aws:execute-api:eu-west-1:123456789012:abc123/*/GET/health\"";
+    assert_eq!(parse_source_arn(literal).unwrap().gateway, None);
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_with_wildcard() {
+  fn test_parse_source_arn_with_wildcard() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/POST/postcode-validation\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "POST");
-    assert_eq!(data[1], "/postcode-validation");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(api.route, "/postcode-validation");
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_with_wildcard_and_path() {
+  fn test_parse_source_arn_with_wildcard_and_path() {
     let source_arn =
       "\"${module.service_api.rest_api_execution_arn}/*/POST/postcode-validation/validate\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "POST");
-    assert_eq!(data[1], "/postcode-validation/validate");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(api.route, "/postcode-validation/validate");
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_with_wildcard_and_path_and_query() {
+  fn test_parse_source_arn_with_wildcard_and_path_and_query() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/POST/postcode-validation/validate?postcode={postcode}\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "POST");
-    assert_eq!(data[1], "/postcode-validation/validate?postcode={postcode}");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(
+      api.route,
+      "/postcode-validation/validate?postcode={postcode}"
+    );
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_with_wildcard_and_path_and_query_and_hash() {
+  fn test_parse_source_arn_with_wildcard_and_path_and_query_and_hash() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/POST/postcode-validation/validate?postcode={postcode}#test\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "POST");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
     assert_eq!(
-      data[1],
+      api.route,
       "/postcode-validation/validate?postcode={postcode}#test"
     );
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_with_wildcard_and_path_and_query_and_hash_and_slash() {
+  fn test_parse_source_arn_with_wildcard_and_path_and_query_and_hash_and_slash() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/POST/postcode-validation/validate?postcode={postcode}#test/\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "POST");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
     assert_eq!(
-      data[1],
+      api.route,
       "/postcode-validation/validate?postcode={postcode}#test/"
     );
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_post() {
+  fn test_parse_source_arn_post() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/POST/health\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "POST");
-    assert_eq!(data[1], "/api/health");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(api.route, "/api/health");
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_put() {
+  fn test_parse_source_arn_put() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/PUT/health\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "PUT");
-    assert_eq!(data[1], "/api/health");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Put);
+    assert_eq!(api.route, "/api/health");
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_delete() {
+  fn test_parse_source_arn_delete() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/DELETE/health\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "DELETE");
-    assert_eq!(data[1], "/api/health");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Delete);
+    assert_eq!(api.route, "/api/health");
   }
 
   #[test]
-  fn test_handle_api_gateway_lambda_patch() {
+  fn test_parse_source_arn_patch() {
     let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/PATCH/health\"";
-    let data = handle_api_gateway_lambda(source_arn.to_string()).unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(data[0], "PATCH");
-    assert_eq!(data[1], "/api/health");
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Patch);
+    assert_eq!(api.route, "/api/health");
+  }
+
+  #[test]
+  fn test_parse_source_arn_trace() {
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/TRACE/health\"";
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Trace);
+    assert_eq!(api.route, "/api/health");
+    assert!(!api.method.is_supported());
+  }
+
+  #[test]
+  fn test_parse_source_arn_any_method() {
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/*/postcode-validation\"";
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Any);
+    assert_eq!(api.route, "/postcode-validation");
+  }
+
+  #[test]
+  fn test_parse_source_arn_interpolated_stage() {
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/${var.stage}/POST/health\"";
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(api.route, "/health");
+
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/${var.stage}/*/health\"";
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Any);
+    assert_eq!(api.route, "/health");
+  }
+
+  #[test]
+  fn test_parse_source_arn_lowercase_method() {
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/get/health\"";
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Get);
+    assert_eq!(api.route, "/api/health");
+
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/Post/items/get\"";
+    let api = parse_source_arn(source_arn).unwrap();
+    assert_eq!(api.method, HttpMethod::Post);
+    assert_eq!(api.route, "/items/get");
+
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/api/health\"";
+    assert!(parse_source_arn(source_arn).is_err());
+  }
+
+  #[test]
+  fn test_parse_source_arn_rejects_catch_all() {
+    let source_arn = "\"${module.service_api.rest_api_execution_arn}/*/*/*\"";
+    assert!(parse_source_arn(source_arn).is_err());
   }
 }
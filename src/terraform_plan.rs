@@ -0,0 +1,443 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::Value;
+use simplelog::{debug, info};
+
+use crate::{
+  findings::{Finding, FindingCode, Findings},
+  open_api::{ExecutionType, OpenAPIData},
+  terraform::{dedup_apis, parse_source_arn, APIPath, Lambda, LambdaTriggerType},
+  util::HttpMethod,
+};
+
+/// The output of `terraform show -json plan.tfplan`, only the parts that are read
+#[derive(Debug, Deserialize)]
+struct Plan {
+  planned_values: PlannedValues,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlannedValues {
+  root_module: PlanModule,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PlanModule {
+  #[serde(default)]
+  resources: Vec<PlanResource>,
+  #[serde(default)]
+  child_modules: Vec<PlanModule>,
+}
+
+impl PlanModule {
+  /// The resources of the module and all of its child modules
+  fn all_resources(&self) -> Vec<&PlanResource> {
+    let mut resources: Vec<&PlanResource> = self.resources.iter().collect();
+    for module in &self.child_modules {
+      resources.extend(module.all_resources());
+    }
+    resources
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct PlanResource {
+  address: String,
+  #[serde(rename = "type")]
+  resource_type: String,
+  #[serde(default)]
+  index: Option<Value>,
+  #[serde(default)]
+  values: Value,
+}
+
+impl PlanResource {
+  /// Gets a string value, values that are computed during the apply are missing
+  fn value(&self, name: &str) -> Option<&str> {
+    self.values.get(name).and_then(Value::as_str)
+  }
+
+  /// Gets a value that is a number or a string as a string
+  fn setting(&self, name: &str) -> Option<String> {
+    match self.values.get(name) {
+      Some(Value::Number(number)) => Some(number.to_string()),
+      Some(Value::String(value)) => Some(value.clone()),
+      _ => None,
+    }
+  }
+
+  /// The lambda key of the resource, from the `module.<name>["key"]` address of the lambda module,
+  /// or the `for_each` key of the resource itself
+  fn lambda_key(&self, lambda_module_name: &str) -> Option<String> {
+    if let Some((_, key)) = self
+      .address
+      .split_once(&format!("module.{}[\"", lambda_module_name))
+    {
+      return key.split_once('"').map(|(key, _)| key.to_string());
+    }
+    match &self.index {
+      Some(Value::String(key)) => Some(key.clone()),
+      _ => None,
+    }
+  }
+}
+
+/// A lambda read from the plan with the resolved names it can be referenced by
+struct PlanLambda {
+  lambda: Lambda,
+  /// The function name and, when it is known, the ARN
+  names: Vec<String>,
+}
+
+impl PlanLambda {
+  /// Whether the text references the lambda by its function name or ARN
+  fn is_referenced_in(&self, text: &str) -> bool {
+    self.names.iter().any(|name| contains_name(text, name))
+  }
+}
+
+/// Validate the Terraform plan and extract the lambda data from its resolved values
+///
+/// The plan is the JSON output of `terraform show -json plan.tfplan`. The ARN placeholders of the
+/// lambdas are found by matching the integrations of the rendered API gateway body to the
+/// routes in `open_api_data`, which can be empty when the files aren't cross validated
+pub fn validate_terraform_plan(
+  plan_path: &Path,
+  lambda_module_name: &str,
+  open_api_data: &[OpenAPIData],
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<Lambda>> {
+  info!("Validating the Terraform plan");
+  let contents = std::fs::read_to_string(plan_path)
+    .map_err(|e| anyhow!("Failed to read the Terraform plan {:?}: {}", plan_path, e))?;
+  let plan: Plan = serde_json::from_str(&contents)
+    .map_err(|e| anyhow!("Failed to parse the Terraform plan {:?}: {}", plan_path, e))?;
+  let resources = plan.planned_values.root_module.all_resources();
+  let mut lambdas = extract_plan_lambdas(&resources, lambda_module_name);
+  let failures = findings.failures();
+  for permission in resources
+    .iter()
+    .filter(|resource| resource.resource_type == "aws_lambda_permission")
+  {
+    add_plan_permission(
+      permission,
+      &mut lambdas,
+      lambda_module_name,
+      plan_path,
+      findings,
+    )?;
+  }
+  for state_machine in resources
+    .iter()
+    .filter(|resource| resource.resource_type == "aws_sfn_state_machine")
+  {
+    if let Some(definition) = state_machine.value("definition") {
+      for plan_lambda in lambdas.iter_mut() {
+        if plan_lambda.is_referenced_in(definition) {
+          plan_lambda.lambda.step_function = true;
+        }
+      }
+    }
+  }
+  for rest_api in resources
+    .iter()
+    .filter(|resource| resource.resource_type == "aws_api_gateway_rest_api")
+  {
+    if let Some(body) = rest_api.value("body") {
+      assign_arn_template_keys(body, &mut lambdas, open_api_data)?;
+    }
+  }
+  if findings.failures() > failures {
+    return Err(anyhow!("Invalid Terraform plan"));
+  }
+  Ok(
+    lambdas
+      .into_iter()
+      .map(|plan_lambda| {
+        let mut lambda = plan_lambda.lambda;
+        dedup_apis(&mut lambda.apis);
+        lambda
+      })
+      .collect(),
+  )
+}
+
+/// Gets the lambdas from the `aws_lambda_function` resources
+fn extract_plan_lambdas(resources: &[&PlanResource], lambda_module_name: &str) -> Vec<PlanLambda> {
+  resources
+    .iter()
+    .filter(|resource| resource.resource_type == "aws_lambda_function")
+    .filter_map(|resource| {
+      let function_name = resource.value("function_name")?;
+      let key = resource
+        .lambda_key(lambda_module_name)
+        .unwrap_or_else(|| function_name.to_string());
+      debug!("Lambda {} in the plan: {}", key, resource.address);
+      let mut names = vec![function_name.to_string()];
+      if let Some(arn) = resource.value("arn") {
+        names.push(arn.to_string());
+      }
+      Some(PlanLambda {
+        lambda: Lambda {
+          key,
+          handler: resource.value("handler").unwrap_or_default().to_string(),
+          timeout: resource.setting("timeout"),
+          memory_size: resource.setting("memory_size"),
+          ..Default::default()
+        },
+        names,
+      })
+    })
+    .collect()
+}
+
+/// Adds the route of an `aws_lambda_permission` to the lambda it grants, reporting the permissions
+/// that are invalid
+fn add_plan_permission(
+  permission: &PlanResource,
+  lambdas: &mut [PlanLambda],
+  lambda_module_name: &str,
+  plan_path: &Path,
+  findings: &mut Findings,
+) -> anyhow::Result<()> {
+  let function_name = permission.value("function_name").unwrap_or_default();
+  let key = permission.lambda_key(lambda_module_name);
+  let plan_lambda = match lambdas.iter_mut().find(|plan_lambda| {
+    plan_lambda.names.iter().any(|name| name == function_name)
+      || Some(&plan_lambda.lambda.key) == key.as_ref()
+  }) {
+    Some(plan_lambda) => plan_lambda,
+    None => {
+      findings.push(
+        Finding::error(
+          FindingCode::UndefinedPermissionLambda,
+          format!(
+            "The permission {} doesn't grant a lambda in the plan",
+            permission.address
+          ),
+        )
+        .in_file(plan_path),
+      );
+      return Ok(());
+    }
+  };
+  let lambda = &mut plan_lambda.lambda;
+  match permission.value("principal").unwrap_or_default() {
+    "apigateway.amazonaws.com" => lambda.lambda_type = LambdaTriggerType::ApiGateway,
+    "events.amazonaws.com" | "scheduler.amazonaws.com" => {
+      lambda.lambda_type = LambdaTriggerType::EventBridge;
+      return Ok(());
+    }
+    _ => return Ok(()),
+  }
+  let source_arn = match permission.value("source_arn") {
+    Some(source_arn) => source_arn,
+    None => return Ok(()),
+  };
+  let api = parse_source_arn(&wildcard_stage(source_arn))?;
+  debug!("API Gateway Lambda Data: {:?}", api);
+  if !api.method.is_supported() {
+    findings.push(
+      Finding::error(
+        FindingCode::UnsupportedMethod,
+        format!(
+          "The {} method for {} used by the lambda {} is not supported by API Gateway",
+          api.method, api.route, lambda.key
+        ),
+      )
+      .for_operation(&api.method, &api.route)
+      .in_file(plan_path),
+    );
+  }
+  lambda.apis.push(api);
+  Ok(())
+}
+
+/// Replaces the stage of a resolved execution ARN with `*`, e.g.
+/// `arn:aws:execute-api:eu-west-1:123456789012:abc123/dev/GET/health` becomes
+/// `arn:aws:execute-api:eu-west-1:123456789012:abc123/*/GET/health`
+fn wildcard_stage(arn: &str) -> String {
+  match arn.split_once('/') {
+    Some((api, rest)) => match rest.split_once('/') {
+      Some((_, rest)) => format!("{}/*/{}", api, rest),
+      None => arn.to_string(),
+    },
+    None => arn.to_string(),
+  }
+}
+
+/// Sets the ARN placeholder of each lambda integrated in the rendered API gateway body to the
+/// placeholder the OpenAPI documents use for the same route
+fn assign_arn_template_keys(
+  body: &str,
+  lambdas: &mut [PlanLambda],
+  open_api_data: &[OpenAPIData],
+) -> anyhow::Result<()> {
+  let body: serde_yaml::Value = serde_yaml::from_str(body)
+    .map_err(|e| anyhow!("Failed to parse the API gateway body in the plan: {}", e))?;
+  for (api, uri) in rendered_integrations(&body) {
+    let plan_lambda = match lambdas
+      .iter_mut()
+      .find(|plan_lambda| plan_lambda.is_referenced_in(&uri))
+    {
+      Some(plan_lambda) => plan_lambda,
+      None => continue,
+    };
+    let placeholder = open_api_data
+      .iter()
+      .find(|item| {
+        item.execution_type == ExecutionType::Lambda
+          && item.path == api.route
+          && item.method == api.method
+      })
+      .and_then(|item| arn_placeholder(&item.uri));
+    if let Some(placeholder) = placeholder {
+      debug!(
+        "The lambda {} is integrated with {} {} as {}",
+        plan_lambda.lambda.key, api.method, api.route, placeholder
+      );
+      plan_lambda
+        .lambda
+        .arn_template_key
+        .get_or_insert(placeholder.to_string());
+    }
+  }
+  Ok(())
+}
+
+/// Gets the routes and integration URIs of the rendered OpenAPI document
+fn rendered_integrations(body: &serde_yaml::Value) -> Vec<(APIPath, String)> {
+  let mut integrations = Vec::new();
+  let paths = match body.get("paths").and_then(serde_yaml::Value::as_mapping) {
+    Some(paths) => paths,
+    None => return integrations,
+  };
+  for (route, item) in paths {
+    let (route, item) = match (route.as_str(), item.as_mapping()) {
+      (Some(route), Some(item)) => (route, item),
+      _ => continue,
+    };
+    for (method, operation) in item {
+      let method: HttpMethod = match method.as_str().map(str::parse) {
+        Some(Ok(method)) => method,
+        _ => continue,
+      };
+      if let Some(uri) = operation
+        .get("x-amazon-apigateway-integration")
+        .or_else(|| item.get("x-amazon-apigateway-integration"))
+        .and_then(|integration| integration.get("uri"))
+        .and_then(serde_yaml::Value::as_str)
+      {
+        integrations.push((
+          APIPath {
+            method,
+            route: route.to_string(),
+            gateway: None,
+          },
+          uri.to_string(),
+        ));
+      }
+    }
+  }
+  integrations
+}
+
+/// Gets the placeholder of the lambda ARN in an integration URI, e.g. `lambda_1_arn` for
+/// `arn:aws:apigateway:${region}:lambda:path/2015-03-31/functions/${lambda_1_arn}/invocations`
+fn arn_placeholder(uri: &str) -> Option<&str> {
+  let (_, function) = uri.split_once("/functions/${")?;
+  function.split_once('}').map(|(placeholder, _)| placeholder)
+}
+
+/// Whether the name appears in the text without being part of a longer name
+fn contains_name(text: &str, name: &str) -> bool {
+  let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+  text.match_indices(name).any(|(start, _)| {
+    let before = text[..start].chars().next_back();
+    let after = text[start + name.len()..].chars().next();
+    !before.is_some_and(is_name_char) && !after.is_some_and(is_name_char)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::GatewayType;
+
+  fn open_api_item(path: &str, method: HttpMethod, placeholder: &str) -> OpenAPIData {
+    OpenAPIData {
+      path: path.to_string(),
+      method,
+      uri: format!(
+        "arn:aws:apigateway:${{region}}:lambda:path/2015-03-31/functions/${{{}}}/invocations",
+        placeholder
+      ),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    }
+  }
+
+  #[test]
+  fn test_validate_terraform_plan() {
+    let open_api_data = vec![
+      open_api_item("/v1/items", HttpMethod::Get, "get_items_arn"),
+      open_api_item("/v1/items", HttpMethod::Post, "create_item_arn"),
+    ];
+    let mut findings = Findings::default();
+    let lambdas = validate_terraform_plan(
+      Path::new("test_files/plan/plan.json"),
+      "lambda",
+      &open_api_data,
+      &mut findings,
+    )
+    .unwrap();
+    assert_eq!(findings.error_count(), 0);
+    assert_eq!(lambdas.len(), 3);
+    assert_eq!(lambdas[0].key, "get-items");
+    assert_eq!(lambdas[0].handler, "get_items.lambda_handler");
+    assert_eq!(lambdas[0].timeout, Some("30".to_string()));
+    assert_eq!(
+      lambdas[0].apis,
+      vec![APIPath {
+        method: HttpMethod::Get,
+        route: "/v1/items".to_string(),
+        gateway: None,
+      }]
+    );
+    assert_eq!(
+      lambdas[0].arn_template_key,
+      Some("get_items_arn".to_string())
+    );
+    assert_eq!(lambdas[1].key, "create-item");
+    assert_eq!(
+      lambdas[1].arn_template_key,
+      Some("create_item_arn".to_string())
+    );
+    assert_eq!(lambdas[2].key, "process-item");
+    assert!(lambdas[2].step_function);
+    assert!(lambdas[2].apis.is_empty());
+  }
+
+  #[test]
+  fn test_wildcard_stage() {
+    assert_eq!(
+      wildcard_stage("arn:aws:execute-api:eu-west-1:123456789012:abc123/dev/GET/v1/items"),
+      "arn:aws:execute-api:eu-west-1:123456789012:abc123/*/GET/v1/items"
+    );
+  }
+
+  #[test]
+  fn test_contains_name() {
+    assert!(contains_name(
+      "arn:aws:lambda:eu-west-1:123456789012:function:items-get/invocations",
+      "items-get"
+    ));
+    assert!(!contains_name(
+      "arn:aws:lambda:eu-west-1:123456789012:function:items-get-all/invocations",
+      "items-get"
+    ));
+  }
+}
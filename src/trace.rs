@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+
+use crate::{
+  findings::Finding,
+  open_api::{path_keys, ExecutionType, OpenAPIData},
+  terraform::Lambda,
+  util::HttpMethod,
+};
+
+/// Explains how the route is cross validated, step by step: the documents that define it, the
+/// operations and their integration URIs, the lambdas with a permission for it and their ARN
+/// placeholders, why each method passes or fails and the findings about the route
+///
+/// `documents` are the OpenAPI files with their contents
+pub fn trace_route<'a>(
+  route: &str,
+  documents: &[(PathBuf, String)],
+  open_api_data: &[OpenAPIData],
+  lambdas: &[Lambda],
+  findings: impl IntoIterator<Item = &'a Finding>,
+) -> Vec<String> {
+  let mut lines = vec![format!("Tracing the route {}", route)];
+  let files: Vec<String> = documents
+    .iter()
+    .filter(|(_, content)| path_keys(content).iter().any(|path| path == route))
+    .map(|(file, _)| format!("{:?}", file))
+    .collect();
+  if files.is_empty() {
+    lines.push(format!(
+      "OpenAPI: {} isn't defined in any of the {} documents",
+      route,
+      documents.len()
+    ));
+  } else {
+    lines.push(format!(
+      "OpenAPI: {} is defined in {}",
+      route,
+      files.join(", ")
+    ));
+  }
+  let operations: Vec<&OpenAPIData> = open_api_data
+    .iter()
+    .filter(|operation| operation.path == route)
+    .collect();
+  for operation in &operations {
+    lines.push(format!(
+      "OpenAPI: {} is a {} integration with the URI {}",
+      operation.method, operation.execution_type, operation.uri
+    ));
+  }
+  let grants: Vec<(&Lambda, &HttpMethod)> = lambdas
+    .iter()
+    .flat_map(|lambda| {
+      lambda
+        .apis
+        .iter()
+        .filter(|api| api.route == route)
+        .map(move |api| (lambda, &api.method))
+    })
+    .collect();
+  if grants.is_empty() {
+    lines.push(format!(
+      "Terraform: no lambda has a permission for {} in lambda_permissions.tf",
+      route
+    ));
+  }
+  for (lambda, method) in &grants {
+    lines.push(match &lambda.arn_template_key {
+      Some(placeholder) => format!(
+        "Terraform: {} is granted to the lambda {} in lambda_permissions.tf, its ARN placeholder is {}",
+        method, lambda.key, placeholder
+      ),
+      None => format!(
+        "Terraform: {} is granted to the lambda {} in lambda_permissions.tf, it isn't referenced in api_gateway.tf so it has no ARN placeholder",
+        method, lambda.key
+      ),
+    });
+  }
+  let mut methods: Vec<&HttpMethod> = operations
+    .iter()
+    .map(|operation| &operation.method)
+    .chain(grants.iter().map(|(_, method)| *method))
+    .filter(|method| **method != HttpMethod::Any)
+    .collect();
+  methods.sort();
+  methods.dedup();
+  for method in methods {
+    let operation = operations
+      .iter()
+      .find(|operation| operation.method == *method);
+    let granted: Vec<&Lambda> = grants
+      .iter()
+      .filter(|(_, granted)| *granted == method || **granted == HttpMethod::Any)
+      .map(|(lambda, _)| *lambda)
+      .collect();
+    lines.push(format!(
+      "{}: {}",
+      method,
+      explain(operation.copied(), &granted)
+    ));
+  }
+  let reported: Vec<&Finding> = findings
+    .into_iter()
+    .filter(|finding| finding.route.as_deref() == Some(route))
+    .collect();
+  if reported.is_empty() {
+    lines.push("Reported: no findings about the route".to_string());
+  }
+  for finding in reported {
+    lines.push(format!(
+      "Reported: {}: {}",
+      finding.severity, finding.message
+    ));
+  }
+  lines
+}
+
+/// Explains why a method of the route passes or fails, from its operation in OpenAPI and the
+/// lambdas with a permission for it
+fn explain(operation: Option<&OpenAPIData>, granted: &[&Lambda]) -> String {
+  let keys = || {
+    granted
+      .iter()
+      .map(|lambda| lambda.key.as_str())
+      .collect::<Vec<_>>()
+      .join(", ")
+  };
+  let operation = match (operation, granted) {
+    (None, _) => {
+      return format!(
+        "fails, it's granted to the lambda {} but isn't defined in OpenAPI",
+        keys()
+      )
+    }
+    (Some(operation), []) if operation.execution_type != ExecutionType::Lambda => {
+      return format!(
+        "skipped, {} integrations aren't cross validated",
+        operation.execution_type
+      )
+    }
+    (Some(_), []) => {
+      return "fails, it's defined in OpenAPI but no lambda has a permission for it in lambda_permissions.tf".to_string()
+    }
+    (Some(_), [_, _, ..]) => {
+      return format!(
+        "fails, more than one lambda has a permission for it: {}",
+        keys()
+      )
+    }
+    (Some(operation), [_]) => operation,
+  };
+  let lambda = granted[0];
+  match &lambda.arn_template_key {
+    None => format!(
+      "fails, the lambda {} isn't referenced in api_gateway.tf so the URI can't be checked",
+      lambda.key
+    ),
+    Some(_) if operation.execution_type != ExecutionType::Lambda => format!(
+      "fails, it's a {} integration in OpenAPI but a permission of the lambda {} in Terraform",
+      operation.execution_type, lambda.key
+    ),
+    Some(placeholder) if operation.uri.contains(placeholder.as_str()) => format!(
+      "passes, the integration URI contains the ARN placeholder {} of the lambda {}",
+      placeholder, lambda.key
+    ),
+    Some(placeholder) => format!(
+      "fails, the integration URI {} doesn't contain the ARN placeholder {} of the lambda {}",
+      operation.uri, placeholder, lambda.key
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{findings::FindingCode, terraform::APIPath, util::GatewayType};
+
+  #[test]
+  fn test_trace_route() {
+    let documents = vec![(
+      PathBuf::from("users.yaml"),
+      "paths:\n  /users/{id}:\n    get: {}\n    delete: {}\n".to_string(),
+    )];
+    let operation = |method: HttpMethod, uri: &str| OpenAPIData {
+      path: "/users/{id}".to_string(),
+      method,
+      uri: uri.to_string(),
+      execution_type: ExecutionType::Lambda,
+      deprecated: false,
+      gateway: GatewayType::Rest,
+    };
+    let open_api_data = vec![
+      operation(HttpMethod::Get, "functions/${get_user_arn}/invocations"),
+      operation(HttpMethod::Delete, "functions/${get_user_arn}/invocations"),
+    ];
+    let lambda = |key: &str, method: HttpMethod| Lambda {
+      key: key.to_string(),
+      arn_template_key: Some(format!("{}_arn", key.replace('-', "_"))),
+      apis: vec![APIPath {
+        method,
+        route: "/users/{id}".to_string(),
+        gateway: None,
+      }],
+      ..Default::default()
+    };
+    let lambdas = vec![
+      lambda("get-user", HttpMethod::Get),
+      lambda("delete-user", HttpMethod::Delete),
+    ];
+    let findings = vec![Finding::error(
+      FindingCode::ArnMismatch,
+      "The 'uri' doesn't contain the ARN placeholder 'delete_user_arn' in the 'x-amazon-apigateway-integration' extension for DELETE /users/{id} for the lambda delete-user",
+    )
+    .for_operation(&HttpMethod::Delete, "/users/{id}")];
+    assert_eq!(
+      trace_route("/users/{id}", &documents, &open_api_data, &lambdas, &findings),
+      vec![
+        "Tracing the route /users/{id}",
+        "OpenAPI: /users/{id} is defined in \"users.yaml\"",
+        "OpenAPI: GET is a Lambda integration with the URI functions/${get_user_arn}/invocations",
+        "OpenAPI: DELETE is a Lambda integration with the URI functions/${get_user_arn}/invocations",
+        "Terraform: GET is granted to the lambda get-user in lambda_permissions.tf, its ARN placeholder is get_user_arn",
+        "Terraform: DELETE is granted to the lambda delete-user in lambda_permissions.tf, its ARN placeholder is delete_user_arn",
+        "GET: passes, the integration URI contains the ARN placeholder get_user_arn of the lambda get-user",
+        "DELETE: fails, the integration URI functions/${get_user_arn}/invocations doesn't contain the ARN placeholder delete_user_arn of the lambda delete-user",
+        "Reported: error: The 'uri' doesn't contain the ARN placeholder 'delete_user_arn' in the 'x-amazon-apigateway-integration' extension for DELETE /users/{id} for the lambda delete-user",
+      ]
+    );
+    assert_eq!(
+      trace_route("/orders", &documents, &open_api_data, &lambdas, &findings),
+      vec![
+        "Tracing the route /orders",
+        "OpenAPI: /orders isn't defined in any of the 1 documents",
+        "Terraform: no lambda has a permission for /orders in lambda_permissions.tf",
+        "Reported: no findings about the route",
+      ]
+    );
+  }
+}
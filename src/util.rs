@@ -1,5 +1,141 @@
+use std::{
+  path::{Path, PathBuf},
+  str::FromStr,
+};
+
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::error::SvError;
+
+/// Name of the file listing paths to exclude from validation, using `.gitignore` semantics
+pub const IGNORE_FILE_NAME: &str = ".svignore";
+
+/// Creates a recursive directory walker that respects `.svignore` files in every directory
+pub fn walk_dir(path: &Path) -> WalkBuilder {
+  let mut builder = WalkBuilder::new(path);
+  builder
+    .standard_filters(false)
+    .add_custom_ignore_filename(IGNORE_FILE_NAME)
+    .sort_by_file_name(|a, b| a.cmp(b));
+  builder
+}
+
+/// Check if the path exists and is a folder else return an Error
+pub fn validating_path(path: &Path) -> anyhow::Result<()> {
+  if !path.exists() {
+    return Err(SvError::Path(format!("Path {:?} does not exist", path)).into());
+  } else if !path.is_dir() {
+    return Err(SvError::Path(format!("Path {:?} is not a folder", path)).into());
+  }
+  Ok(())
+}
+
+/// Finds the files matching the glob, e.g. `infra/**/lambda*.tf`, by walking the folder before the
+/// first wildcard, respecting `.svignore` files
+pub fn expand_glob(glob: &str) -> anyhow::Result<Vec<PathBuf>> {
+  let matcher = GlobBuilder::new(glob)
+    .literal_separator(true)
+    .build()
+    .map_err(|e| SvError::Config(format!("Invalid glob {}: {}", glob, e)))?
+    .compile_matcher();
+  let base: PathBuf = Path::new(glob)
+    .components()
+    .take_while(|component| {
+      !component
+        .as_os_str()
+        .to_string_lossy()
+        .contains(['*', '?', '[', '{'])
+    })
+    .collect();
+  let relative = base.as_os_str().is_empty();
+  let base = if relative { PathBuf::from(".") } else { base };
+  Ok(
+    walk_dir(&base)
+      .build()
+      .flatten()
+      .map(|entry| entry.into_path())
+      .filter(|path| path.is_file())
+      .filter(|path| match path.strip_prefix(".") {
+        Ok(stripped) if relative => matcher.is_match(stripped),
+        _ => matcher.is_match(path),
+      })
+      .collect(),
+  )
+}
+
+/// Formats the rows as a table, padding each column to its widest cell, the first row is the header
+pub fn format_table(rows: &[Vec<String>]) -> String {
+  let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+  let widths: Vec<usize> = (0..columns)
+    .map(|column| {
+      rows
+        .iter()
+        .filter_map(|row| row.get(column))
+        .map(String::len)
+        .max()
+        .unwrap_or_default()
+    })
+    .collect();
+  rows
+    .iter()
+    .map(|row| {
+      row
+        .iter()
+        .zip(&widths)
+        .map(|(cell, width)| format!("{:width$}", cell))
+        .collect::<Vec<_>>()
+        .join("  ")
+        .trim_end()
+        .to_string()
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Whether the route matches the glob, where `*` matches any characters within a segment, `?` a
+/// single character and `**` any number of segments, e.g. `/users/**` matches `/users` and
+/// `/users/{id}/posts`
+pub fn matches_route_glob(glob: &str, route: &str) -> bool {
+  let segments = |path: &'_ str| -> Vec<String> {
+    path
+      .split('/')
+      .filter(|segment| !segment.is_empty())
+      .map(str::to_string)
+      .collect()
+  };
+  matches_segments(&segments(glob), &segments(route))
+}
+
+/// Whether the route's segments match the glob's segments
+fn matches_segments(glob: &[String], route: &[String]) -> bool {
+  match glob.split_first() {
+    None => route.is_empty(),
+    Some((segment, rest)) if segment == "**" => {
+      (0..=route.len()).any(|skipped| matches_segments(rest, &route[skipped..]))
+    }
+    Some((segment, rest)) => route.split_first().is_some_and(|(first, route)| {
+      matches_segment(segment.as_bytes(), first.as_bytes()) && matches_segments(rest, route)
+    }),
+  }
+}
+
+/// Whether a segment of the route matches a segment of the glob
+fn matches_segment(glob: &[u8], segment: &[u8]) -> bool {
+  match glob.split_first() {
+    None => segment.is_empty(),
+    Some((b'*', rest)) => {
+      (0..=segment.len()).any(|skipped| matches_segment(rest, &segment[skipped..]))
+    }
+    Some((b'?', rest)) => !segment.is_empty() && matches_segment(rest, &segment[1..]),
+    Some((c, rest)) => segment.first() == Some(c) && matches_segment(rest, &segment[1..]),
+  }
+}
+
 /// HTTP methods
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum HttpMethod {
   #[default]
   Get,
@@ -12,42 +148,58 @@ pub enum HttpMethod {
   Trace,
   Connect,
   /// Any HTTP method is allowed
+  #[serde(rename = "*")]
   Any,
 }
 
-impl From<String> for HttpMethod {
-  fn from(s: String) -> Self {
+impl HttpMethod {
+  /// Whether API Gateway is able to route the method to an integration
+  pub fn is_supported(&self) -> bool {
+    !matches!(self, HttpMethod::Trace | HttpMethod::Connect)
+  }
+}
+
+/// Error for a string that isn't a known HTTP method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHttpMethod(pub String);
+
+impl std::fmt::Display for InvalidHttpMethod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Invalid HTTP method: {}", self.0)
+  }
+}
+
+impl std::error::Error for InvalidHttpMethod {}
+
+impl FromStr for HttpMethod {
+  type Err = InvalidHttpMethod;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
     match s.to_lowercase().as_str() {
-      "get" => HttpMethod::Get,
-      "post" => HttpMethod::Post,
-      "put" => HttpMethod::Put,
-      "delete" => HttpMethod::Delete,
-      "patch" => HttpMethod::Patch,
-      "head" => HttpMethod::Head,
-      "options" => HttpMethod::Options,
-      "trace" => HttpMethod::Trace,
-      "connect" => HttpMethod::Connect,
-      "*" => HttpMethod::Any,
-      _ => panic!("Invalid HTTP method"),
+      "get" => Ok(HttpMethod::Get),
+      "post" => Ok(HttpMethod::Post),
+      "put" => Ok(HttpMethod::Put),
+      "delete" => Ok(HttpMethod::Delete),
+      "patch" => Ok(HttpMethod::Patch),
+      "head" => Ok(HttpMethod::Head),
+      "options" => Ok(HttpMethod::Options),
+      "trace" => Ok(HttpMethod::Trace),
+      "connect" => Ok(HttpMethod::Connect),
+      "*" => Ok(HttpMethod::Any),
+      _ => Err(InvalidHttpMethod(s.to_string())),
     }
   }
 }
 
+impl From<String> for HttpMethod {
+  fn from(s: String) -> Self {
+    s.as_str().into()
+  }
+}
+
 impl From<&str> for HttpMethod {
   fn from(s: &str) -> Self {
-    match s.to_lowercase().as_str() {
-      "get" => HttpMethod::Get,
-      "post" => HttpMethod::Post,
-      "put" => HttpMethod::Put,
-      "delete" => HttpMethod::Delete,
-      "patch" => HttpMethod::Patch,
-      "head" => HttpMethod::Head,
-      "options" => HttpMethod::Options,
-      "trace" => HttpMethod::Trace,
-      "connect" => HttpMethod::Connect,
-      "*" => HttpMethod::Any,
-      _ => panic!("Invalid HTTP method"),
-    }
+    s.parse().expect("Invalid HTTP method")
   }
 }
 
@@ -67,3 +219,64 @@ impl std::fmt::Display for HttpMethod {
     }
   }
 }
+
+/// The kind of API Gateway API that serves a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub enum GatewayType {
+  /// An API Gateway v1 REST API
+  #[default]
+  Rest,
+  /// An API Gateway v2 HTTP API
+  Http,
+}
+
+impl std::fmt::Display for GatewayType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      GatewayType::Rest => write!(f, "REST API"),
+      GatewayType::Http => write!(f, "HTTP API"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_matches_route_glob() {
+    assert!(matches_route_glob("/users/**", "/users"));
+    assert!(matches_route_glob("/users/**", "/users/{id}/posts"));
+    assert!(!matches_route_glob("/users/**", "/orders/{id}"));
+    assert!(matches_route_glob("/users/*", "/users/{id}"));
+    assert!(!matches_route_glob("/users/*", "/users/{id}/posts"));
+    assert!(matches_route_glob("/v?/*-items", "/v1/order-items"));
+    assert!(matches_route_glob("**", "/"));
+  }
+
+  #[test]
+  fn test_expand_glob() {
+    assert_eq!(
+      expand_glob("test_files/svignore/**/*.tf").unwrap(),
+      vec![
+        PathBuf::from("test_files/svignore/lambda.tf"),
+        PathBuf::from("test_files/svignore/nested/kept.tf"),
+      ]
+    );
+    assert_eq!(
+      expand_glob("test_files/svignore/*.tf").unwrap(),
+      vec![PathBuf::from("test_files/svignore/lambda.tf")]
+    );
+  }
+
+  #[test]
+  fn test_http_method_from_str() {
+    assert_eq!("GET".parse(), Ok(HttpMethod::Get));
+    assert_eq!("Patch".parse(), Ok(HttpMethod::Patch));
+    assert_eq!("*".parse(), Ok(HttpMethod::Any));
+    assert_eq!(
+      "FETCH".parse::<HttpMethod>(),
+      Err(InvalidHttpMethod("FETCH".to_string()))
+    );
+  }
+}
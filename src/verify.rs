@@ -0,0 +1,1322 @@
+use std::{
+  collections::BTreeMap,
+  ffi::OsStr,
+  path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use simplelog::{debug, info};
+
+use crate::{
+  cache::ValidationCache,
+  config::{Config, LambdaPolicy, Profile},
+  cross_validation::{
+    cross_validation, cross_validation_located, cross_validation_per_file,
+    find_wildcard_method_permissions,
+  },
+  error::SvError,
+  findings::{Finding, FindingCode, Findings, Severity, SeverityOverride},
+  iam::{check_invoke_policy, load_policy},
+  logger::Glyphs,
+  open_api::{self, validate_open_api, OpenAPIData, OpenApiOptions},
+  pretty::format_pretty_report,
+  profile,
+  sarif::sarif_log,
+  suppressions::find_suppressions,
+  terraform::{
+    self, check_arn_suffix, check_lambda_policy, validate_terraform, Lambda, TerraformVariables,
+  },
+  terraform_plan::validate_terraform_plan,
+  trace::trace_route,
+  util::{matches_route_glob, validating_path},
+};
+
+/// The settings used to verify the OpenAPI and Terraform files
+#[derive(Debug, Default)]
+pub struct VerifyOptions {
+  /// The path to the OpenAPI files
+  pub api_path: PathBuf,
+  /// The path to the Terraform files
+  pub terraform: PathBuf,
+  /// Continue even if the CyclicRef error occurs
+  pub skip_cyclic: bool,
+  /// Warn when a POST, PUT or PATCH request body isn't marked as required
+  pub strict_bodies: bool,
+  /// Warn about the `passthroughBehavior` and `requestParameters` of the integrations
+  pub strict_integration: bool,
+  /// Warn about the 2xx responses of Lambda proxy integrations that only declare content types
+  /// other than JSON
+  pub strict_responses: bool,
+  /// Warn about the routes granted for any method in Terraform that OpenAPI only defines some
+  /// methods for
+  pub strict_methods: bool,
+  /// Warn about the paths without an OPTIONS method when the documents use CORS
+  pub check_cors: bool,
+  /// Warn about the operations without a security requirement
+  pub require_security: bool,
+  /// Fail when an operation has no tags
+  pub require_tags: bool,
+  /// Only cross validate and report the routes matching the glob, e.g. `/users/**`
+  pub filter_route: Option<String>,
+  /// Log how the route is cross validated step by step, e.g. `/users/{id}`
+  pub trace_route: Option<String>,
+  /// Only validate these OpenAPI documents and Terraform files instead of the folders, e.g. the
+  /// staged files in a pre-commit hook
+  pub files: Vec<PathBuf>,
+  /// File stems of the OpenAPI documents merged into every other document
+  pub shared_schema_names: Vec<String>,
+  /// Extensions of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi`
+  pub api_extensions: Vec<String>,
+  /// Name of the Terraform module that creates the lambdas
+  pub lambda_module_name: String,
+  /// The JSON of `terraform show -json plan.tfplan` to read the lambdas from instead of the
+  /// Terraform files
+  pub terraform_plan: Option<PathBuf>,
+  /// Values substituted for the `${var.name}` references in the Terraform files
+  pub variables: TerraformVariables,
+  /// Only the OpenAPI documents matching this glob are validated, instead of the documents in
+  /// `api_path`
+  pub api_glob: Option<String>,
+  /// Only the Terraform files matching this glob are validated, instead of the files in
+  /// `terraform`
+  pub terraform_glob: Option<String>,
+  /// Bounds for the lambda settings, not checked when it isn't set
+  pub lambda_policy: Option<LambdaPolicy>,
+  /// Validate the OpenAPI and Terraform files without checking them against each other
+  pub no_cross_validation: bool,
+  /// Validate each OpenAPI document on its own and cross validate it separately, without merging
+  /// the documents
+  pub no_merge: bool,
+  /// Warn when a lambda's ARN placeholder isn't its key followed by this suffix
+  pub arn_suffix: Option<String>,
+  /// The IAM policy, a JSON document or a Terraform file, that should allow API Gateway to invoke
+  /// the lambdas with API permissions
+  pub check_policy: Option<PathBuf>,
+  /// The value of `terraform.workspace`, used to pick the lambdas gated on the workspace
+  pub terraform_workspace: Option<String>,
+  /// Warn about the permissions for services that aren't supported and skip them instead of
+  /// failing
+  pub allow_unknown_principal: bool,
+  /// Write the merged OpenAPI document to this file, or stdout for `-`
+  pub print_merged: Option<PathBuf>,
+  /// Folder to cache the results of the OpenAPI documents in, not cached when it isn't set
+  pub cache_dir: Option<PathBuf>,
+  /// The severity to report each finding code with
+  pub severity: BTreeMap<FindingCode, SeverityOverride>,
+  /// Fail validation when there are any warnings
+  pub fail_on_warning: bool,
+}
+
+impl VerifyOptions {
+  /// Starts building the options, e.g.
+  /// `VerifyOptions::builder().api_path("apis").terraform("terraform").skip_cyclic(true).build()`
+  pub fn builder() -> VerifyOptionsBuilder {
+    VerifyOptionsBuilder::default()
+  }
+
+  /// Creates the options for the folders with the defaults and the settings from `config`
+  ///
+  /// The profile in the config is applied first so the other settings take precedence over it
+  pub fn new(api_path: PathBuf, terraform: PathBuf, config: Config) -> Self {
+    let profile = config.profile.unwrap_or_default();
+    let mut options = VerifyOptions {
+      api_path,
+      terraform,
+      shared_schema_names: config.shared_schema_names.unwrap_or_else(|| {
+        open_api::DEFAULT_SHARED_SCHEMA_NAMES
+          .iter()
+          .map(|name| name.to_string())
+          .collect()
+      }),
+      lambda_module_name: config
+        .lambda_module_name
+        .unwrap_or_else(|| terraform::DEFAULT_LAMBDA_MODULE_NAME.to_string()),
+      lambda_policy: config.lambda_policy,
+      severity: config.severity,
+      ..Default::default()
+    };
+    options.apply_profile(profile);
+    options
+  }
+
+  /// Sets the defaults of the profile, keeping the severities that are already set
+  fn apply_profile(&mut self, profile: Profile) {
+    match profile {
+      Profile::Relaxed => {
+        for code in Profile::RELAXED_CODES {
+          self.severity.entry(code).or_insert(SeverityOverride::Off);
+        }
+      }
+      Profile::Standard => {}
+      Profile::Strict => {
+        self.strict_bodies = true;
+        self.strict_methods = true;
+        self.fail_on_warning = true;
+      }
+    }
+  }
+}
+
+/// Builds the `VerifyOptions`, the settings that are set take precedence over the config and its
+/// profile, and the ones that aren't set keep the defaults or the profile's
+#[derive(Debug, Default)]
+pub struct VerifyOptionsBuilder {
+  api_path: PathBuf,
+  terraform: PathBuf,
+  config: Config,
+  skip_cyclic: bool,
+  strict_bodies: Option<bool>,
+  strict_integration: Option<bool>,
+  strict_responses: Option<bool>,
+  strict_methods: Option<bool>,
+  check_cors: bool,
+  require_security: bool,
+  require_tags: bool,
+  filter_route: Option<String>,
+  trace_route: Option<String>,
+  files: Vec<PathBuf>,
+  shared_schema_names: Option<Vec<String>>,
+  api_extensions: Vec<String>,
+  lambda_module_name: Option<String>,
+  terraform_plan: Option<PathBuf>,
+  variables: TerraformVariables,
+  api_glob: Option<String>,
+  terraform_glob: Option<String>,
+  lambda_policy: Option<LambdaPolicy>,
+  no_cross_validation: bool,
+  no_merge: bool,
+  arn_suffix: Option<String>,
+  check_policy: Option<PathBuf>,
+  terraform_workspace: Option<String>,
+  allow_unknown_principal: bool,
+  print_merged: Option<PathBuf>,
+  cache_dir: Option<PathBuf>,
+  severity: BTreeMap<FindingCode, SeverityOverride>,
+  fail_on_warning: Option<bool>,
+}
+
+impl VerifyOptionsBuilder {
+  /// The path to the OpenAPI files
+  pub fn api_path(mut self, api_path: impl Into<PathBuf>) -> Self {
+    self.api_path = api_path.into();
+    self
+  }
+
+  /// The path to the Terraform files
+  pub fn terraform(mut self, terraform: impl Into<PathBuf>) -> Self {
+    self.terraform = terraform.into();
+    self
+  }
+
+  /// The settings from `sv.toml`
+  pub fn config(mut self, config: Config) -> Self {
+    self.config = config;
+    self
+  }
+
+  /// The strictness preset, replacing the one in the config
+  pub fn profile(mut self, profile: Profile) -> Self {
+    self.config.profile = Some(profile);
+    self
+  }
+
+  /// Continue even if the CyclicRef error occurs
+  pub fn skip_cyclic(mut self, skip_cyclic: bool) -> Self {
+    self.skip_cyclic = skip_cyclic;
+    self
+  }
+
+  /// Warn when a POST, PUT or PATCH request body isn't marked as required, which the strict
+  /// profile also turns on
+  pub fn strict_bodies(mut self, strict_bodies: bool) -> Self {
+    self.strict_bodies = Some(strict_bodies);
+    self
+  }
+
+  /// Warn about the `passthroughBehavior` and `requestParameters` of the integrations
+  pub fn strict_integration(mut self, strict_integration: bool) -> Self {
+    self.strict_integration = Some(strict_integration);
+    self
+  }
+
+  /// Warn about the 2xx responses of Lambda proxy integrations that only declare content types
+  /// other than JSON, e.g. `text/plain`
+  pub fn strict_responses(mut self, strict_responses: bool) -> Self {
+    self.strict_responses = Some(strict_responses);
+    self
+  }
+
+  /// Warn about the routes granted for any method in Terraform that OpenAPI only defines some
+  /// methods for, which the strict profile also turns on
+  pub fn strict_methods(mut self, strict_methods: bool) -> Self {
+    self.strict_methods = Some(strict_methods);
+    self
+  }
+
+  /// Warn about the paths without an OPTIONS method when the documents use CORS
+  pub fn check_cors(mut self, check_cors: bool) -> Self {
+    self.check_cors = check_cors;
+    self
+  }
+
+  /// Warn about the operations without a security requirement
+  pub fn require_security(mut self, require_security: bool) -> Self {
+    self.require_security = require_security;
+    self
+  }
+
+  /// Fail when an operation has no tags
+  pub fn require_tags(mut self, require_tags: bool) -> Self {
+    self.require_tags = require_tags;
+    self
+  }
+
+  /// Only cross validate and report the routes matching the glob, e.g. `/users/**`
+  pub fn filter_route(mut self, filter_route: impl Into<String>) -> Self {
+    self.filter_route = Some(filter_route.into());
+    self
+  }
+
+  /// Log how the route is cross validated step by step, e.g. `/users/{id}`
+  pub fn trace_route(mut self, trace_route: impl Into<String>) -> Self {
+    self.trace_route = Some(trace_route.into());
+    self
+  }
+
+  /// Only validate these OpenAPI documents and Terraform files, using the folders they're in for
+  /// the shared document and the cross validation
+  pub fn files(mut self, files: Vec<PathBuf>) -> Self {
+    self.files = files;
+    self
+  }
+
+  /// File stems of the OpenAPI documents merged into every other document
+  pub fn shared_schema_names(mut self, shared_schema_names: Vec<String>) -> Self {
+    self.shared_schema_names = Some(shared_schema_names);
+    self
+  }
+
+  /// Extensions of the OpenAPI documents besides `yml` and `yaml`, e.g. `openapi`
+  pub fn api_extensions(mut self, api_extensions: Vec<String>) -> Self {
+    self.api_extensions = api_extensions;
+    self
+  }
+
+  /// Name of the Terraform module that creates the lambdas
+  pub fn lambda_module_name(mut self, lambda_module_name: impl Into<String>) -> Self {
+    self.lambda_module_name = Some(lambda_module_name.into());
+    self
+  }
+
+  /// Read the lambdas from the JSON of `terraform show -json plan.tfplan` instead of the Terraform
+  /// files
+  pub fn terraform_plan(mut self, terraform_plan: impl Into<PathBuf>) -> Self {
+    self.terraform_plan = Some(terraform_plan.into());
+    self
+  }
+
+  /// The value of `terraform.workspace`, used to pick the lambdas and permissions that are gated
+  /// on the workspace
+  pub fn terraform_workspace(mut self, terraform_workspace: impl Into<String>) -> Self {
+    self.terraform_workspace = Some(terraform_workspace.into());
+    self
+  }
+
+  /// Warn about the permissions for services that aren't supported and skip them instead of
+  /// failing
+  pub fn allow_unknown_principal(mut self, allow_unknown_principal: bool) -> Self {
+    self.allow_unknown_principal = allow_unknown_principal;
+    self
+  }
+
+  /// Values substituted for the `${var.name}` references in the Terraform files
+  pub fn variables(mut self, variables: TerraformVariables) -> Self {
+    self.variables = variables;
+    self
+  }
+
+  /// Only validate the OpenAPI documents matching the glob, e.g. `apis/**/*.yaml`, instead of the
+  /// documents in the `api_path` folder
+  pub fn api_glob(mut self, api_glob: impl Into<String>) -> Self {
+    self.api_glob = Some(api_glob.into());
+    self
+  }
+
+  /// Only validate the Terraform files matching the glob, e.g. `infra/**/lambda*.tf`, instead of
+  /// the files in the `terraform` folder
+  pub fn terraform_glob(mut self, terraform_glob: impl Into<String>) -> Self {
+    self.terraform_glob = Some(terraform_glob.into());
+    self
+  }
+
+  /// Bounds for the lambda settings
+  pub fn lambda_policy(mut self, lambda_policy: LambdaPolicy) -> Self {
+    self.lambda_policy = Some(lambda_policy);
+    self
+  }
+
+  /// Validate the OpenAPI and Terraform files without checking them against each other
+  pub fn no_cross_validation(mut self, no_cross_validation: bool) -> Self {
+    self.no_cross_validation = no_cross_validation;
+    self
+  }
+
+  /// Validate each OpenAPI document on its own and cross validate it separately, without merging
+  /// the documents
+  pub fn no_merge(mut self, no_merge: bool) -> Self {
+    self.no_merge = no_merge;
+    self
+  }
+
+  /// Warn when a lambda's ARN placeholder isn't its key, with `-` replaced by `_`, followed by
+  /// the suffix, e.g. `_arn`
+  pub fn arn_suffix(mut self, arn_suffix: impl Into<String>) -> Self {
+    self.arn_suffix = Some(arn_suffix.into());
+    self
+  }
+
+  /// Check that the IAM policy in the file, a JSON document or a Terraform file, allows
+  /// `lambda:InvokeFunction` on each lambda with API permissions
+  pub fn check_policy(mut self, check_policy: impl Into<PathBuf>) -> Self {
+    self.check_policy = Some(check_policy.into());
+    self
+  }
+
+  /// Write the merged OpenAPI document to this file, or stdout for `-`
+  pub fn print_merged(mut self, print_merged: impl Into<PathBuf>) -> Self {
+    self.print_merged = Some(print_merged.into());
+    self
+  }
+
+  /// Cache the results of the OpenAPI documents in the folder
+  pub fn cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+    self.cache_dir = Some(cache_dir.into());
+    self
+  }
+
+  /// Report the finding code with the severity, replacing the config and profile
+  pub fn severity(mut self, code: FindingCode, severity: SeverityOverride) -> Self {
+    self.severity.insert(code, severity);
+    self
+  }
+
+  /// Fail validation when there are any warnings, which the strict profile also turns on
+  pub fn fail_on_warning(mut self, fail_on_warning: bool) -> Self {
+    self.fail_on_warning = Some(fail_on_warning);
+    self
+  }
+
+  /// Creates the options from the config, its profile and then the settings that were set
+  pub fn build(self) -> VerifyOptions {
+    let mut options = VerifyOptions::new(self.api_path, self.terraform, self.config);
+    if let Some(shared_schema_names) = self.shared_schema_names {
+      options.shared_schema_names = shared_schema_names;
+    }
+    if let Some(lambda_module_name) = self.lambda_module_name {
+      options.lambda_module_name = lambda_module_name;
+    }
+    options.api_extensions = self.api_extensions;
+    if self.lambda_policy.is_some() {
+      options.lambda_policy = self.lambda_policy;
+    }
+    options.severity.extend(self.severity);
+    options.skip_cyclic = self.skip_cyclic;
+    if let Some(strict_bodies) = self.strict_bodies {
+      options.strict_bodies = strict_bodies;
+    }
+    if let Some(strict_integration) = self.strict_integration {
+      options.strict_integration = strict_integration;
+    }
+    if let Some(strict_responses) = self.strict_responses {
+      options.strict_responses = strict_responses;
+    }
+    if let Some(strict_methods) = self.strict_methods {
+      options.strict_methods = strict_methods;
+    }
+    options.check_cors = self.check_cors;
+    options.require_security = self.require_security;
+    options.require_tags = self.require_tags;
+    options.filter_route = self.filter_route;
+    options.trace_route = self.trace_route;
+    options.files = self.files;
+    if let Some(fail_on_warning) = self.fail_on_warning {
+      options.fail_on_warning = fail_on_warning;
+    }
+    options.terraform_plan = self.terraform_plan;
+    options.variables = self.variables;
+    options.api_glob = self.api_glob;
+    options.terraform_glob = self.terraform_glob;
+    options.no_cross_validation = self.no_cross_validation;
+    options.no_merge = self.no_merge;
+    options.arn_suffix = self.arn_suffix;
+    options.check_policy = self.check_policy;
+    options.terraform_workspace = self.terraform_workspace;
+    options.allow_unknown_principal = self.allow_unknown_principal;
+    options.print_merged = self.print_merged;
+    options.cache_dir = self.cache_dir;
+    options
+  }
+}
+
+/// The outcome of verifying the OpenAPI and Terraform files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyReport {
+  /// Whether the files passed validation
+  pub passed: bool,
+  /// The error that failed validation, if any
+  pub error: Option<String>,
+  /// The findings that were reported
+  pub findings: Vec<Finding>,
+}
+
+impl VerifyReport {
+  /// Creates the report from the result of verifying and the findings that were reported
+  pub fn new(result: &anyhow::Result<()>, findings: &Findings) -> Self {
+    VerifyReport {
+      passed: result.is_ok(),
+      error: result.as_ref().err().map(|e| e.to_string()),
+      findings: findings.iter().cloned().collect(),
+    }
+  }
+
+  /// Reads a report saved with `save`
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    let contents = std::fs::read_to_string(path)
+      .map_err(|e| SvError::Io(format!("Couldn't read the report {:?}: {}", path, e)))?;
+    serde_json::from_str(&contents)
+      .map_err(|e| SvError::Config(format!("Invalid report {:?}: {}", path, e)).into())
+  }
+
+  /// Writes the report as JSON
+  pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(self)?)
+      .map_err(|e| SvError::Io(format!("Couldn't write the report {:?}: {}", path, e)).into())
+  }
+
+  /// Writes the report to the directory as `report.json`, `report.sarif` and `report.txt`,
+  /// creating the directory if it doesn't exist
+  ///
+  /// The text report is the pretty report in plain text followed by the outcome, with the
+  /// suggestions for fixing the findings when `hints` is set
+  pub fn write_to_dir(&self, dir: &Path, hints: bool) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+      .map_err(|e| SvError::Io(format!("Couldn't create the folder {:?}: {}", dir, e)))?;
+    self.save(&dir.join("report.json"))?;
+    let write = |name: &str, contents: String| {
+      let path = dir.join(name);
+      std::fs::write(&path, contents)
+        .map_err(|e| SvError::Io(format!("Couldn't write the report {:?}: {}", path, e)))
+    };
+    write(
+      "report.sarif",
+      serde_json::to_string_pretty(&sarif_log(&self.findings))?,
+    )?;
+    let count = |severity: Severity| {
+      self
+        .findings
+        .iter()
+        .filter(|finding| finding.severity == severity)
+        .count()
+    };
+    let outcome = format!(
+      "Verification {}, errors: {}, warnings: {}",
+      if self.passed { "passed" } else { "failed" },
+      count(Severity::Error),
+      count(Severity::Warning)
+    );
+    let mut text = format_pretty_report(&self.findings, Glyphs::Ascii, hints);
+    text.push_str("\n\n");
+    text.push_str(&outcome);
+    if let Some(error) = &self.error {
+      text.push_str(&format!(": {}", error));
+    }
+    text.push('\n');
+    write("report.txt", text)?;
+    Ok(())
+  }
+
+  /// Renders the report with a Tera template, e.g. a Markdown table of the findings for a pull
+  /// request comment
+  ///
+  /// The template gets the report as its context: `passed`, `error` and the `findings` with their
+  /// `code`, `severity`, `message` and `file`
+  pub fn render(&self, template: &Path) -> anyhow::Result<String> {
+    let contents = std::fs::read_to_string(template).map_err(|e| {
+      SvError::Io(format!(
+        "Couldn't read the report template {:?}: {}",
+        template, e
+      ))
+    })?;
+    let context = tera::Context::from_serialize(self)?;
+    tera::Tera::one_off(&contents, &context, false).map_err(|e| {
+      // The error is wrapped in one that only says the template failed to render
+      let mut message = e.to_string();
+      let mut source = std::error::Error::source(&e);
+      while let Some(cause) = source {
+        message = format!("{}: {}", message, cause);
+        source = cause.source();
+      }
+      SvError::Config(format!(
+        "Couldn't render the report template {:?}: {}",
+        template, message
+      ))
+      .into()
+    })
+  }
+
+  /// Finds the findings that are new in this report and the ones in `baseline` that were fixed
+  pub fn compare(&self, baseline: &VerifyReport) -> ReportDiff {
+    let missing_from = |findings: &[Finding], other: &[Finding]| {
+      findings
+        .iter()
+        .filter(|finding| !other.iter().any(|x| x.is_same(finding)))
+        .cloned()
+        .collect()
+    };
+    ReportDiff {
+      new: missing_from(&self.findings, &baseline.findings),
+      fixed: missing_from(&baseline.findings, &self.findings),
+    }
+  }
+}
+
+/// The findings that changed between two reports
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportDiff {
+  /// The findings that aren't in the baseline report
+  pub new: Vec<Finding>,
+  /// The findings in the baseline report that are no longer reported
+  pub fixed: Vec<Finding>,
+}
+
+/// The totals of a verify run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+  /// Number of errors that were reported
+  pub errors: usize,
+  /// Number of warnings that were reported
+  pub warnings: usize,
+  /// Number of OpenAPI and Terraform files that were validated
+  pub files: usize,
+  /// Number of OpenAPI documents that were skipped because of cyclic references
+  pub skipped_cyclic: usize,
+}
+
+impl Summary {
+  /// Counts the files in the folders of `options` or matching its globs, or the listed files,
+  /// and the findings that were reported
+  pub fn new(options: &VerifyOptions, findings: &Findings) -> Self {
+    let files = if options.files.is_empty() {
+      let documents = api_files(options).len();
+      let terraform_files = match options.terraform_plan {
+        Some(_) => 1,
+        None => terraform_files(options).len(),
+      };
+      documents + terraform_files
+    } else {
+      let (documents, terraform_files, _) = partition_files(options);
+      documents.len() + terraform_files.len()
+    };
+    Summary {
+      errors: findings.error_count(),
+      warnings: findings.warning_count(),
+      files,
+      skipped_cyclic: findings.count(FindingCode::CyclicRef),
+    }
+  }
+}
+
+/// Validates the OpenAPI and Terraform files, recording the problems in `findings`
+///
+/// Warnings that are set to `error` in the severity config fail validation, as do any warnings
+/// when `fail_on_warning` is set
+pub fn verify(options: &VerifyOptions, findings: &mut Findings) -> anyhow::Result<()> {
+  findings.set_severities(options.severity.clone());
+  findings.set_route_filter(options.filter_route.clone());
+  findings.set_suppressions(profile::time("find_suppressions", None, || {
+    find_suppressions(&suppression_files(options))
+  }));
+  if let Some(glob) = &options.filter_route {
+    info!("Only checking the routes matching {}", glob);
+  }
+  verify_files(options, findings)?;
+  if findings.raised() > 0 {
+    return Err(anyhow!(
+      "Warnings raised to errors by the severity config: {}",
+      findings.raised()
+    ));
+  }
+  if options.fail_on_warning && findings.warning_count() > 0 {
+    return Err(anyhow!("Failing on warnings: {}", findings.warning_count()));
+  }
+  Ok(())
+}
+
+/// The OpenAPI documents and Terraform files that are searched for `# sv:ignore` comments, the
+/// listed files or the files in the folders of `options` or matching its globs
+fn suppression_files(options: &VerifyOptions) -> Vec<PathBuf> {
+  if !options.files.is_empty() {
+    return options.files.clone();
+  }
+  let mut files = api_files(options);
+  if options.terraform_plan.is_none() {
+    files.extend(terraform_files(options));
+  }
+  files
+}
+
+/// The OpenAPI documents in the folder of `options` or matching its glob, empty when there are
+/// none
+fn api_files(options: &VerifyOptions) -> Vec<PathBuf> {
+  open_api::api_files(
+    &options.api_path,
+    options.api_glob.as_deref(),
+    &options.api_extensions,
+  )
+  .unwrap_or_default()
+}
+
+/// The Terraform files in the folder of `options` or matching its glob
+fn terraform_files(options: &VerifyOptions) -> Vec<PathBuf> {
+  terraform::terraform_files(&options.terraform, options.terraform_glob.as_deref())
+    .unwrap_or_default()
+}
+
+/// The file with the lambdas' permissions, the plan or the file the `lambdas_permissions` local
+/// is in
+fn permissions_file(options: &VerifyOptions) -> Option<PathBuf> {
+  match &options.terraform_plan {
+    Some(plan) => Some(plan.clone()),
+    None => terraform::permissions_file(&options.terraform, options.terraform_glob.as_deref()),
+  }
+}
+
+/// The options of the OpenAPI validators
+fn open_api_options<'a>(
+  options: &'a VerifyOptions,
+  cache: Option<&'a ValidationCache>,
+) -> OpenApiOptions<'a> {
+  OpenApiOptions {
+    api_glob: options.api_glob.as_deref(),
+    skip_cyclic: options.skip_cyclic,
+    strict_bodies: options.strict_bodies,
+    strict_integration: options.strict_integration,
+    strict_responses: options.strict_responses,
+    check_cors: options.check_cors,
+    require_security: options.require_security,
+    require_tags: options.require_tags,
+    shared_schema_names: &options.shared_schema_names,
+    api_extensions: &options.api_extensions,
+    print_merged: options.print_merged.as_deref(),
+    cache,
+  }
+}
+
+/// Runs the validators and the cross validation
+fn verify_files(options: &VerifyOptions, findings: &mut Findings) -> anyhow::Result<()> {
+  if !options.files.is_empty() {
+    return verify_listed_files(options, findings);
+  }
+  if options.api_glob.is_none() {
+    validating_path(&options.api_path)?;
+  }
+  if options.terraform_plan.is_none() && options.terraform_glob.is_none() {
+    validating_path(&options.terraform)?;
+  }
+  let cache = options.cache_dir.as_ref().map(ValidationCache::new);
+  let open_api_options = open_api_options(options, cache.as_ref());
+  let (open_api_config, mut documents) = profile::time("validate_open_api", None, || {
+    if options.no_merge {
+      match open_api::validate_open_api_separately(&options.api_path, &open_api_options, findings) {
+        Ok(documents) => (
+          Ok(
+            documents
+              .iter()
+              .flat_map(|(_, open_api_data)| open_api_data.clone())
+              .collect(),
+          ),
+          Some(documents),
+        ),
+        Err(e) => (Err(e), None),
+      }
+    } else {
+      let open_api_config =
+        validate_open_api(options.api_path.clone(), &open_api_options, findings);
+      (open_api_config, None)
+    }
+  });
+  stop_if_failing_fast(findings)?;
+  if options.no_cross_validation {
+    let lambda_data = read_lambda_data(options, &[], findings)
+      .and_then(|lambda_data| check_lambda_data(options, &lambda_data, findings));
+    open_api_config?;
+    lambda_data?;
+    info!("Skipping cross validation");
+    return Ok(());
+  }
+  let mut open_api_config = open_api_config?;
+  let mut lambda_data = profile::time("validate_terraform", None, || {
+    read_lambda_data(options, &open_api_config, findings)
+  })?;
+  check_lambda_data(options, &lambda_data, findings)?;
+  stop_if_failing_fast(findings)?;
+  if let Some(glob) = &options.filter_route {
+    open_api_config.retain(|api| matches_route_glob(glob, &api.path));
+    lambda_data.iter_mut().for_each(|lambda| {
+      lambda
+        .apis
+        .retain(|api| matches_route_glob(glob, &api.route))
+    });
+    documents
+      .iter_mut()
+      .flatten()
+      .for_each(|(_, open_api_data)| {
+        open_api_data.retain(|api| matches_route_glob(glob, &api.path))
+      });
+  }
+  let traced = options
+    .trace_route
+    .as_ref()
+    .map(|_| (open_api_config.clone(), lambda_data.clone()));
+  let permissions = permissions_file(options);
+  let result = profile::time("cross_validation", None, || {
+    cross_validation_located(
+      &api_files(options),
+      permissions.as_deref(),
+      findings,
+      |findings| {
+        if options.strict_methods {
+          find_wildcard_method_permissions(&lambda_data, &open_api_config)
+            .into_iter()
+            .for_each(|finding| findings.push(finding));
+        }
+        match documents {
+          Some(documents) => cross_validation_per_file(lambda_data, documents, findings),
+          None => cross_validation(lambda_data, open_api_config, findings),
+        }
+      },
+    )
+  });
+  if let (Some(route), Some((open_api_data, lambdas))) = (&options.trace_route, traced) {
+    let documents: Vec<(PathBuf, String)> = api_files(options)
+      .into_iter()
+      .filter_map(|file| Some((file.clone(), std::fs::read_to_string(file).ok()?)))
+      .collect();
+    for line in trace_route(route, &documents, &open_api_data, &lambdas, findings.iter()) {
+      info!("{}", line);
+    }
+  }
+  result
+}
+
+/// Validates only the listed files, then cross validates them on a best-effort basis
+///
+/// The OpenAPI documents are validated with the shared document in `api_path`, or the folder of
+/// the first document, and each Terraform file with the folder it's in, or `terraform` when it's
+/// in there. The cross validation reads the API data and the lambdas from those folders without
+/// reporting their findings, and is skipped when either can't be read
+fn verify_listed_files(options: &VerifyOptions, findings: &mut Findings) -> anyhow::Result<()> {
+  let (documents, terraform_files, skipped) = partition_files(options);
+  for file in skipped {
+    debug!(
+      "Skipping {:?}, it's neither an OpenAPI document nor a Terraform file",
+      file
+    );
+  }
+  let api_root = match (options.api_path.as_os_str().is_empty(), documents.first()) {
+    (false, _) => Some(options.api_path.clone()),
+    (true, Some(document)) => Some(parent_folder(document)),
+    (true, None) => None,
+  };
+  let mut errors = Vec::new();
+  let cache = options.cache_dir.as_ref().map(ValidationCache::new);
+  if let Some(api_root) = api_root.as_ref().filter(|_| !documents.is_empty()) {
+    if let Err(e) = open_api::validate_open_api_files(
+      &documents,
+      api_root,
+      &open_api_options(options, cache.as_ref()),
+      findings,
+    ) {
+      errors.push(e);
+    }
+  }
+  let mut terraform_roots: Vec<PathBuf> = Vec::new();
+  for file in &terraform_files {
+    let root = if !options.terraform.as_os_str().is_empty() && file.starts_with(&options.terraform)
+    {
+      options.terraform.clone()
+    } else {
+      parent_folder(file)
+    };
+    if !terraform_roots.contains(&root) {
+      terraform_roots.push(root);
+    }
+  }
+  let mut lambda_data = None;
+  for root in terraform_roots {
+    stop_if_failing_fast(findings)?;
+    match validate_terraform(
+      root.clone(),
+      None,
+      &options.lambda_module_name,
+      &options.variables,
+      options.terraform_workspace.as_deref(),
+      options.allow_unknown_principal,
+      findings,
+    ) {
+      Ok(lambdas) => {
+        if let Err(e) = check_lambda_data(options, &lambdas, findings) {
+          errors.push(e);
+        }
+        lambda_data.get_or_insert((lambdas, terraform::permissions_file(&root, None)));
+      }
+      Err(e) => errors.push(e),
+    }
+  }
+  stop_if_failing_fast(findings)?;
+  if !errors.is_empty() {
+    return Err(errors.remove(0));
+  }
+  if options.no_cross_validation {
+    info!("Skipping cross validation");
+    return Ok(());
+  }
+  let mut context = Findings::default();
+  context.set_silent(true);
+  let api_root = api_root.filter(|root| root.exists());
+  let open_api_data = api_root
+    .as_ref()
+    .and_then(|root| open_api::read_api_data(root, &options.api_extensions, &mut context).ok());
+  let lambda_data = match (lambda_data, &open_api_data) {
+    (Some(lambdas), _) => Some(lambdas),
+    (None, Some(open_api_data))
+      if options.terraform_plan.is_some() || options.terraform.exists() =>
+    {
+      read_lambda_data(options, open_api_data, &mut context)
+        .ok()
+        .map(|lambdas| (lambdas, permissions_file(options)))
+    }
+    (None, _) => None,
+  };
+  match (api_root, open_api_data, lambda_data) {
+    (Some(api_root), Some(mut open_api_data), Some((mut lambda_data, permissions))) => {
+      if let Some(glob) = &options.filter_route {
+        open_api_data.retain(|api| matches_route_glob(glob, &api.path));
+        lambda_data.iter_mut().for_each(|lambda| {
+          lambda
+            .apis
+            .retain(|api| matches_route_glob(glob, &api.route))
+        });
+      }
+      let documents = open_api::api_files(&api_root, None, &options.api_extensions);
+      cross_validation_located(
+        &documents.unwrap_or_default(),
+        permissions.as_deref(),
+        findings,
+        |findings| {
+          if options.strict_methods {
+            find_wildcard_method_permissions(&lambda_data, &open_api_data)
+              .into_iter()
+              .for_each(|finding| findings.push(finding));
+          }
+          cross_validation(lambda_data, open_api_data, findings)
+        },
+      )
+    }
+    _ => {
+      info!("Skipping cross validation, the OpenAPI documents or the Terraform files for the listed files couldn't be read");
+      Ok(())
+    }
+  }
+}
+
+/// Splits the listed files into the OpenAPI documents, the Terraform files and the files that are
+/// neither
+fn partition_files(options: &VerifyOptions) -> (Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>) {
+  let mut documents = Vec::new();
+  let mut terraform_files = Vec::new();
+  let mut skipped = Vec::new();
+  for file in &options.files {
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or_default();
+    if extension == "tf" {
+      terraform_files.push(file.clone());
+    } else if open_api::DEFAULT_API_EXTENSIONS.contains(&extension)
+      || options
+        .api_extensions
+        .iter()
+        .any(|e| e.trim_start_matches('.') == extension)
+    {
+      documents.push(file.clone());
+    } else {
+      skipped.push(file.clone());
+    }
+  }
+  (documents, terraform_files, skipped)
+}
+
+/// The folder the file is in, the current folder for a file name without one
+fn parent_folder(file: &Path) -> PathBuf {
+  match file.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+    _ => PathBuf::from("."),
+  }
+}
+
+/// Stops verifying once an error was reported when the findings are set to fail fast
+fn stop_if_failing_fast(findings: &Findings) -> anyhow::Result<()> {
+  if findings.should_stop() {
+    return Err(anyhow!("Stopped at the first error"));
+  }
+  Ok(())
+}
+
+/// Runs the optional checks of the lambdas' settings, ARN placeholders and invoke permissions
+fn check_lambda_data(
+  options: &VerifyOptions,
+  lambda_data: &[Lambda],
+  findings: &mut Findings,
+) -> anyhow::Result<()> {
+  if let Some(policy) = &options.lambda_policy {
+    check_lambda_policy(lambda_data, policy, findings);
+  }
+  if let Some(suffix) = &options.arn_suffix {
+    check_arn_suffix(lambda_data, suffix, findings);
+  }
+  if let Some(file) = &options.check_policy {
+    let statements = load_policy(file)?;
+    let failures = findings.failures();
+    for finding in check_invoke_policy(lambda_data, &statements, file) {
+      findings.push(finding);
+    }
+    if findings.failures() > failures {
+      return Err(anyhow!("Invalid IAM policy"));
+    }
+  }
+  Ok(())
+}
+
+/// Reads the lambdas from the Terraform plan when one is set, otherwise from the Terraform files
+fn read_lambda_data(
+  options: &VerifyOptions,
+  open_api_data: &[OpenAPIData],
+  findings: &mut Findings,
+) -> anyhow::Result<Vec<Lambda>> {
+  match &options.terraform_plan {
+    Some(plan) => {
+      validate_terraform_plan(plan, &options.lambda_module_name, open_api_data, findings)
+    }
+    None => validate_terraform(
+      options.terraform.clone(),
+      options.terraform_glob.as_deref(),
+      &options.lambda_module_name,
+      &options.variables,
+      options.terraform_workspace.as_deref(),
+      options.allow_unknown_principal,
+      findings,
+    ),
+  }
+}
+
+/// Validates the OpenAPI and Terraform files, calling `on_finding` with each finding as it is
+/// produced rather than collecting them
+pub fn verify_with(
+  options: &VerifyOptions,
+  mut on_finding: impl FnMut(Finding),
+) -> anyhow::Result<()> {
+  let mut findings = Findings::with_sink(&mut on_finding);
+  verify(options, &mut findings)
+}
+
+/// Runs the whole verify pipeline on a folder with `open_api` and `terraform` sub-folders, using
+/// the `sv.toml` in the folder if there is one
+pub fn run_pipeline_on_fixture(dir: &Path) -> VerifyReport {
+  let mut findings = Findings::default();
+  let result = Config::load(dir).and_then(|config| {
+    let options = VerifyOptions::builder()
+      .api_path(dir.join("open_api"))
+      .terraform(dir.join("terraform"))
+      .config(config)
+      .build();
+    verify(&options, &mut findings)
+  });
+  VerifyReport::new(&result, &findings)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_verify_fail_fast() {
+    let options = VerifyOptions::builder()
+      .api_path("test_files/invalid_document")
+      .terraform("tests/fixtures/missing_route/terraform")
+      .no_cross_validation(true)
+      .build();
+    let mut findings = Findings::default();
+    findings.set_fail_fast(true);
+    assert_eq!(
+      verify(&options, &mut findings).unwrap_err().to_string(),
+      "Stopped at the first error"
+    );
+    assert_eq!(findings.error_count(), 1);
+  }
+
+  #[test]
+  fn test_verify_filter_route() {
+    let options = |filter_route: Option<&str>| {
+      let mut builder = VerifyOptions::builder()
+        .api_path("test_files/filtered_route/open_api")
+        .terraform("test_files/filtered_route/terraform");
+      if let Some(filter_route) = filter_route {
+        builder = builder.filter_route(filter_route);
+      }
+      builder.build()
+    };
+    let mut findings = Findings::default();
+    assert!(verify(&options(None), &mut findings).is_err());
+    assert_eq!(findings.count(FindingCode::UndeclaredPathParameter), 1);
+
+    let mut findings = Findings::default();
+    assert!(verify(&options(Some("/v1/items")), &mut findings).is_ok());
+    assert_eq!(findings.error_count(), 0);
+    assert_eq!(findings.filtered(), 1);
+  }
+
+  #[test]
+  fn test_verify_listed_files() {
+    let options = VerifyOptions::builder()
+      .files(vec![
+        PathBuf::from("tests/fixtures/missing_route/open_api/items.yaml"),
+        PathBuf::from("tests/fixtures/missing_route/terraform/lambda.tf"),
+        PathBuf::from("tests/fixtures/missing_route/expected-report.json"),
+      ])
+      .build();
+    let mut findings = Findings::default();
+    assert!(verify(&options, &mut findings).is_err());
+    assert_eq!(findings.count(FindingCode::PathNotInOpenApi), 1);
+    assert_eq!(Summary::new(&options, &findings).files, 2);
+
+    let options = VerifyOptions::builder()
+      .files(vec![PathBuf::from(
+        "tests/fixtures/missing_route/open_api/items.yaml",
+      )])
+      .build();
+    let mut findings = Findings::default();
+    assert!(verify(&options, &mut findings).is_ok());
+    assert_eq!(findings.error_count(), 0);
+  }
+
+  #[test]
+  fn test_verify_listed_files_optional_checks() {
+    let options = |strict_bodies: bool| {
+      VerifyOptions::builder()
+        .files(vec![PathBuf::from("test_files/optional_body/items.yaml")])
+        .strict_bodies(strict_bodies)
+        .no_cross_validation(true)
+        .build()
+    };
+    let mut findings = Findings::default();
+    verify(&options(false), &mut findings).unwrap();
+    assert_eq!(findings.count(FindingCode::OptionalRequestBody), 0);
+
+    let mut findings = Findings::default();
+    let _ = verify(&options(true), &mut findings);
+    assert_eq!(findings.count(FindingCode::OptionalRequestBody), 1);
+  }
+
+  #[test]
+  fn test_verify_no_merge() {
+    let options = |fixture: &str| {
+      VerifyOptions::builder()
+        .api_path(format!("tests/fixtures/{}/open_api", fixture))
+        .terraform(format!("tests/fixtures/{}/terraform", fixture))
+        .no_merge(true)
+        .build()
+    };
+    let mut findings = Findings::default();
+    assert!(verify(&options("valid"), &mut findings).is_ok());
+    let mut findings = Findings::default();
+    assert!(verify(&options("missing_route"), &mut findings).is_err());
+    assert_eq!(findings.count(FindingCode::PathNotInOpenApi), 1);
+    assert_eq!(findings.count(FindingCode::MethodNotInTerraform), 1);
+  }
+
+  #[test]
+  fn test_verify_globs() {
+    let options = |terraform_glob: &str| {
+      VerifyOptions::builder()
+        .api_glob("tests/fixtures/valid/**/*.yaml")
+        .terraform_glob(terraform_glob)
+        .build()
+    };
+    let mut findings = Findings::default();
+    assert!(verify(
+      &options("tests/fixtures/valid/terraform/*.tf"),
+      &mut findings
+    )
+    .is_ok());
+    let mut findings = Findings::default();
+    let result = verify(
+      &options("tests/fixtures/valid/terraform/lambda*.tf"),
+      &mut findings,
+    );
+    assert!(result
+      .unwrap_err()
+      .to_string()
+      .starts_with("No API gateway with a templatefile"));
+  }
+
+  #[test]
+  fn test_verify_options_builder() {
+    let config = Config {
+      lambda_module_name: Some("functions".to_string()),
+      severity: BTreeMap::from([(FindingCode::PostNoBody, SeverityOverride::Error)]),
+      ..Default::default()
+    };
+    let options = VerifyOptions::builder()
+      .api_path("apis")
+      .terraform("terraform")
+      .config(config)
+      .profile(Profile::Strict)
+      .skip_cyclic(true)
+      .severity(FindingCode::PostNoBody, SeverityOverride::Off)
+      .build();
+    assert_eq!(options.api_path, PathBuf::from("apis"));
+    assert_eq!(options.lambda_module_name, "functions");
+    assert!(options.skip_cyclic);
+    assert!(options.strict_bodies);
+    assert!(options.strict_methods);
+    assert!(options.fail_on_warning);
+    assert_eq!(
+      options.severity.get(&FindingCode::PostNoBody),
+      Some(&SeverityOverride::Off)
+    );
+    assert_eq!(
+      options.shared_schema_names,
+      open_api::DEFAULT_SHARED_SCHEMA_NAMES
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>()
+    );
+
+    let options = VerifyOptions::builder()
+      .lambda_module_name("handlers")
+      .build();
+    assert_eq!(options.lambda_module_name, "handlers");
+    assert!(!options.strict_bodies);
+
+    let options = VerifyOptions::builder()
+      .profile(Profile::Strict)
+      .strict_bodies(false)
+      .fail_on_warning(false)
+      .build();
+    assert!(!options.strict_bodies);
+    assert!(options.strict_methods);
+    assert!(!options.fail_on_warning);
+  }
+
+  #[test]
+  fn test_verify_report_compare() {
+    let report = |findings: Vec<Finding>| VerifyReport {
+      passed: false,
+      error: None,
+      findings,
+    };
+    let fixed = Finding::error(
+      FindingCode::PathNotInTerraform,
+      "The path /old is not defined",
+    );
+    let kept = Finding::warning(FindingCode::PostNoBody, "The POST method for /items");
+    let new = Finding::error(
+      FindingCode::PathNotInTerraform,
+      "The path /new is not defined",
+    );
+    let baseline = report(vec![fixed.clone(), kept.clone()]);
+    let mut raised = kept.clone();
+    raised.severity = Severity::Error;
+    let current = report(vec![raised, new.clone()]);
+    assert_eq!(
+      current.compare(&baseline),
+      ReportDiff {
+        new: vec![new],
+        fixed: vec![fixed],
+      }
+    );
+  }
+
+  #[test]
+  fn test_verify_report_write_to_dir() {
+    let dir = std::env::temp_dir().join("sv_test_write_to_dir");
+    let _ = std::fs::remove_dir_all(&dir);
+    let report = VerifyReport {
+      passed: false,
+      error: Some("Invalid Terraform configuration".to_string()),
+      findings: vec![Finding::error(
+        FindingCode::DuplicateLambdaKey,
+        "Key is duplicated: a",
+      )],
+    };
+    report.write_to_dir(&dir, false).unwrap();
+    assert_eq!(
+      VerifyReport::load(&dir.join("report.json")).unwrap(),
+      report
+    );
+    let sarif: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(dir.join("report.sarif")).unwrap()).unwrap();
+    assert_eq!(
+      sarif["runs"][0]["results"][0]["ruleId"],
+      "DUPLICATE_LAMBDA_KEY"
+    );
+    assert_eq!(
+      std::fs::read_to_string(dir.join("report.txt")).unwrap(),
+      [
+        "== OpenAPI: no findings ==",
+        "== Terraform: 1 error ==",
+        "  error: Key is duplicated: a",
+        "== Cross-validation: no findings ==",
+        "",
+        "Verification failed, errors: 1, warnings: 0: Invalid Terraform configuration",
+        "",
+      ]
+      .join("\n")
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_verify_report_render() {
+    let report = VerifyReport {
+      passed: false,
+      error: Some("Invalid Terraform configuration".to_string()),
+      findings: vec![
+        Finding::error(FindingCode::DuplicateLambdaKey, "Key is duplicated: a|b")
+          .in_file(Path::new("terraform/lambda.tf")),
+        Finding::warning(FindingCode::PostNoBody, "The POST method for /items"),
+      ],
+    };
+    assert_eq!(
+      report.render(Path::new("templates/pr-comment.md")).unwrap(),
+      [
+        "**sv verify failed**: Invalid Terraform configuration",
+        "",
+        "| Severity | Code | Message | File |",
+        "| --- | --- | --- | --- |",
+        "| error | `DUPLICATE_LAMBDA_KEY` | Key is duplicated: a\\|b | terraform/lambda.tf |",
+        "| warning | `POST_NO_BODY` | The POST method for /items |  |",
+        "",
+      ]
+      .join("\n")
+    );
+    let passed = VerifyReport {
+      passed: true,
+      error: None,
+      findings: Vec::new(),
+    };
+    assert_eq!(
+      passed.render(Path::new("templates/pr-comment.md")).unwrap(),
+      "**sv verify passed**\n\nNo findings\n"
+    );
+  }
+}
@@ -0,0 +1,10 @@
+module "service_api" {
+  source = "./modules/api_gateway"
+
+  api_config = {
+    body = templatefile("${path.module}/../apis/out/service-api.yaml", {
+      lambda_1_arn : module.lambda["lambda-1"].lambda_arn,
+      ghost_arn : module.lambda["ghost"].lambda_arn,
+    })
+  }
+}
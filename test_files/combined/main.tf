@@ -0,0 +1,62 @@
+locals {
+  lambdas = {
+    list-items = {
+      handler = "list_items.lambda_handler"
+    }
+    create-item = {
+      handler = "create_item.lambda_handler"
+    }
+    process-item = {
+      handler = "process_item.lambda_handler"
+    }
+  }
+
+  lambdas_permissions = {
+    list-items = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/GET/v1/items"
+      }
+    ],
+    create-item = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/POST/v1/items"
+      }
+    ],
+    process-item = [
+      {
+        statement_id = "AllowExecutionFromStepFunction"
+        principal    = "states.amazonaws.com"
+        source_arn   = module.step_function.state_machine_arn
+      }
+    ],
+  }
+}
+
+module "lambda" {
+  for_each = local.lambdas
+  source   = "./modules/lambda"
+}
+
+module "service_api" {
+  source = "./modules/api_gateway"
+
+  api_config = {
+    body = templatefile("${path.module}/../open_api/items.yaml", {
+      region : var.region
+      list_items_arn : module.lambda["list-items"].lambda_arn,
+      create_item_arn : module.lambda["create-item"].lambda_arn,
+    })
+  }
+}
+
+module "step_function" {
+  source = "./modules/step_function"
+
+  definition = templatefile("${path.module}/definition.json", {
+    process_item_arn = module.lambda["process-item"].lambda_arn
+  })
+}
@@ -0,0 +1,25 @@
+locals {
+  lambdas_permissions = {
+    list-items = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/GET/v1/items"
+      }
+    ],
+    create-item = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/POST/v1/items"
+      }
+    ],
+    get-item = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/GET/v1/items/{id}"
+      }
+    ],
+  }
+}
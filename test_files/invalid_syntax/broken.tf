@@ -0,0 +1,5 @@
+locals {
+  lambdas = {
+    broken = {
+      handler = "broken.handler"
+  }
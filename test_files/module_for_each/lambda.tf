@@ -0,0 +1,32 @@
+locals {
+  lambdas = {
+    lambda-1 = {
+      handler = "lambda_1.lambda_handler"
+    }
+    lambda-2 = {
+      handler = "lambda_2.lambda_handler"
+    }
+  }
+  legacy_lambdas = {
+    legacy = {
+      handler = "legacy.lambda_handler"
+    }
+  }
+  prod_lambdas = {
+    reports = {
+      handler = "reports.lambda_handler"
+    }
+  }
+}
+
+module "lambda" {
+  source   = "./modules/lambda"
+  for_each = merge(
+    { for key, lambda in local.lambdas : key => lambda },
+    local.legacy_lambdas,
+    terraform.workspace == "prod" ? local.prod_lambdas : {},
+  )
+
+  function_name = each.key
+  handler       = each.value.handler
+}
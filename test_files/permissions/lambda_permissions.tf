@@ -0,0 +1,21 @@
+locals {
+  lambdas_permissions = {
+    lambda-1 = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/GET/v1/items"
+      },
+      {
+        statement_id = "AllowExecutionFromAPIGatewayStage"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/${var.stage}/GET/v1/items"
+      },
+      {
+        statement_id = "AllowPostFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/POST/v1/items"
+      }
+    ],
+  }
+}
@@ -0,0 +1,18 @@
+locals {
+  lambdas_permissions = {
+    lambda-1 = [
+      {
+        statement_id = "AllowExecutionFromAPIGateway"
+        principal    = "apigateway.amazonaws.com"
+        source_arn   = "${module.service_api.rest_api_execution_arn}/*/GET/v1/items"
+      }
+    ],
+    notify = [
+      {
+        statement_id = "AllowExecutionFromSNS"
+        principal    = "sns.amazonaws.com"
+        source_arn   = aws_sns_topic.notifications.arn
+      }
+    ],
+  }
+}
@@ -0,0 +1,7 @@
+module "step_function" {
+  source = "./modules/step_function"
+
+  definition = templatefile("${path.module}/definition.json", {
+    x_arn = module.functions["x"].lambda_arn
+  })
+}
@@ -0,0 +1,14 @@
+locals {
+  lambdas = merge(
+    {
+      list-items = {
+        handler = "list_items.lambda_handler"
+      }
+    },
+    terraform.workspace == "prod" ? {
+      export-items = {
+        handler = "export_items.lambda_handler"
+      }
+    } : {}
+  )
+}
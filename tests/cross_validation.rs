@@ -3,7 +3,8 @@ use std::fs::read_to_string;
 use openapiv3::Operation;
 use openapiv3::{OpenAPI, PathItem, ReferenceOr};
 use sv::{
-  cross_validation::validate_aws_api_gateway_integration, terraform::APIPath, util::HttpMethod,
+  cross_validation::validate_aws_api_gateway_integration, findings::Findings, terraform::APIPath,
+  util::HttpMethod,
 };
 
 pub fn read_valid_open_api() -> String {
@@ -48,12 +49,14 @@ fn test_valid_aws_integration_extension() {
   let api = APIPath {
     method: HttpMethod::Get,
     route: "/v1/valid/path".to_string(),
+    gateway: None,
   };
   assert!(validate_aws_api_gateway_integration(
     &d,
     "random key",
     "lambda_valid_1_arn",
-    &api
+    &api,
+    &mut Findings::default()
   ));
 }
 
@@ -63,12 +66,14 @@ fn test_invalid_aws_integration_extension_arn() {
   let api = APIPath {
     method: HttpMethod::Get,
     route: "/v1/valid/path".to_string(),
+    gateway: None,
   };
   assert!(!validate_aws_api_gateway_integration(
     &d,
     "random key",
     "lambda_invalid_1_arn",
-    &api
+    &api,
+    &mut Findings::default()
   ));
 }
 
@@ -78,12 +83,14 @@ fn test_invalid_aws_integration_extension_arn_no_uri() {
   let api = APIPath {
     method: HttpMethod::Get,
     route: "/v1/valid/path".to_string(),
+    gateway: None,
   };
   assert!(!validate_aws_api_gateway_integration(
     &d,
     "random key",
     "lambda_invalid_1_arn",
-    &api
+    &api,
+    &mut Findings::default()
   ));
 }
 
@@ -93,11 +100,13 @@ fn test_invalid_no_aws_integration_extension() {
   let api = APIPath {
     method: HttpMethod::Get,
     route: "/v1/valid/path".to_string(),
+    gateway: None,
   };
   assert!(!validate_aws_api_gateway_integration(
     &d,
     "random key",
     "lambda_invalid_1_arn",
-    &api
+    &api,
+    &mut Findings::default()
   ));
 }
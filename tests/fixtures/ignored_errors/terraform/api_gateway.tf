@@ -0,0 +1,11 @@
+module "service_api" {
+  source = "./modules/api_gateway"
+
+  api_config = {
+    body = templatefile("${path.module}/../open_api/items.yaml", {
+      region : var.region
+      list_items_arn : module.lambda["list-items"].lambda_arn,
+      create_item_arn : module.lambda["create-item"].lambda_arn,
+    })
+  }
+}
@@ -0,0 +1,19 @@
+module "service_api" {
+  source = "./modules/api_gateway"
+
+  api_config = {
+    body = templatefile("${path.module}/../open_api/items.yaml", {
+      region : var.region
+      list_items_arn : module.lambda["list-items"].lambda_arn,
+      create_item_arn : module.lambda["create-item"].lambda_arn,
+    })
+  }
+}
+
+resource "aws_apigatewayv2_api" "orders" {
+  name          = "orders"
+  protocol_type = "HTTP"
+  body = templatefile("${path.module}/../open_api/orders.yaml", {
+    list_orders_arn : module.lambda["list-orders"].lambda_arn,
+  })
+}
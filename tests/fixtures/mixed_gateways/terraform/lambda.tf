@@ -0,0 +1,18 @@
+locals {
+  lambdas = {
+    list-items = {
+      handler = "list_items.lambda_handler"
+    }
+    create-item = {
+      handler = "create_item.lambda_handler"
+    }
+    list-orders = {
+      handler = "list_orders.lambda_handler"
+    }
+  }
+}
+
+module "lambda" {
+  for_each = local.lambdas
+  source   = "./modules/lambda"
+}
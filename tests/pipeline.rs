@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use sv::verify::{run_pipeline_on_fixture, VerifyReport};
+
+/// Set to rewrite the expected reports from the current output
+const UPDATE_VAR: &str = "SV_UPDATE_FIXTURES";
+
+/// Runs the pipeline on `tests/fixtures/<case>` and compares it to its `expected-report.json`
+fn check_fixture(case: &str) {
+  let dir = Path::new("tests/fixtures").join(case);
+  let expected_path = dir.join("expected-report.json");
+  let report = run_pipeline_on_fixture(&dir);
+  if std::env::var_os(UPDATE_VAR).is_some() {
+    let contents = serde_json::to_string_pretty(&report).unwrap();
+    std::fs::write(&expected_path, contents + "\n").unwrap();
+    return;
+  }
+  let expected: VerifyReport =
+    serde_json::from_str(&std::fs::read_to_string(&expected_path).unwrap()).unwrap();
+  assert_eq!(
+    report, expected,
+    "The report for {} changed, rerun with {}=1 to update it",
+    case, UPDATE_VAR
+  );
+}
+
+#[test]
+fn test_pipeline_valid() {
+  check_fixture("valid");
+}
+
+#[test]
+fn test_pipeline_missing_route() {
+  check_fixture("missing_route");
+}
+
+#[test]
+fn test_pipeline_severity() {
+  check_fixture("severity");
+}
+
+#[test]
+fn test_pipeline_strict_profile() {
+  check_fixture("strict_profile");
+}
+
+#[test]
+fn test_pipeline_mixed_gateways() {
+  check_fixture("mixed_gateways");
+}
+
+#[test]
+fn test_pipeline_downgraded_errors() {
+  check_fixture("downgraded_errors");
+}
+
+#[test]
+fn test_pipeline_ignored_errors() {
+  check_fixture("ignored_errors");
+}